@@ -0,0 +1,56 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use dns_client::dns_client_lib::*;
+use dns_client::dns_client_lib::view::DnsMessageView;
+use std::net::Ipv4Addr;
+
+// a typical-ish answer: a cname chain, a handful of addresses, and the delegation.
+fn response_bytes() -> Vec<u8> {
+    let h = DnsHeader::new(0x1234, true, DnsOpcode::QUERY, false, false, true, true,
+                           DnsRcode::NOERROR);
+    let q = vec![DnsQuestionRecord::new(String::from("www.example.com."), DnsQType::A, DnsQClass::IN)];
+    let mut an = vec![DnsResourceRecord::new(String::from("www.example.com."), DnsQType::CNAME,
+                                             DnsQClass::IN, 300,
+                                             DnsResourceRecordEnum::CNAME(DnsCNAMERecord::new(
+                                                 String::from("edge.cdn.example.com."))))];
+    for i in 1..=8 {
+        an.push(DnsResourceRecord::new(String::from("edge.cdn.example.com."), DnsQType::A, DnsQClass::IN, 60,
+                                       DnsResourceRecordEnum::A(DnsARecord::new(Ipv4Addr::new(192, 0, 2, i)))));
+    }
+    let mut ns = Vec::new();
+    let mut ar = Vec::new();
+    for i in 1..=4 {
+        let server = format!("ns{i}.example.com.");
+        ns.push(DnsResourceRecord::new(String::from("example.com."), DnsQType::NS, DnsQClass::IN, 3600,
+                                       DnsResourceRecordEnum::NS(DnsNSRecord::new(server.clone()))));
+        ar.push(DnsResourceRecord::new(server, DnsQType::A, DnsQClass::IN, 3600,
+                                       DnsResourceRecordEnum::A(DnsARecord::new(Ipv4Addr::new(198, 51, 100, i)))));
+    }
+    DnsResponse::new(h, q, an, ns, ar).to_bytes().unwrap()
+}
+
+fn parse_benchmark(c: &mut Criterion) {
+    let buf = response_bytes();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(buf.len() as u64));
+
+    group.bench_function("owned", |b| b.iter(|| {
+        DnsResponse::from_bytes(black_box(&buf), 0).unwrap()
+    }));
+
+    // what a capture tool would do: pull the addresses for a name out of the answers.
+    group.bench_function("view", |b| b.iter(|| {
+        let view = DnsMessageView::new(black_box(&buf), 0).unwrap();
+        let mut found = 0;
+        for rr in view.answers() {
+            let (_, rr) = rr.unwrap();
+            if rr.rtype() == DnsQType::A && rr.name().matches("edge.cdn.example.com") {
+                found += rr.rdata()[3] as u32;
+            }
+        }
+        found
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, parse_benchmark);
+criterion_main!(benches);
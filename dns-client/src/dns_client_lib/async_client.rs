@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::OnceCell;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::task::JoinHandle;
+use super::*;
+use super::client::{DnsAttempt, DnsAttemptOutcome, DnsClient};
+use super::transport::{DnsTransport, local_bind_addr, tcp_frame};
+
+/* an async take on client::DnsClient, for running on tokio. every udp query goes out the
+   same socket (one per address family), and a task reading that socket hands each datagram
+   to the query it answers, going by the server it came from, its id and its question. so
+   any number of queries can be in flight at once, without a thread or socket apiece. */
+
+// what the reader task hands a waiting query: the response, or why a datagram for it was dropped.
+type Delivery = Result<Vec<u8>, String>;
+
+// queries waiting on an answer, by (server, id). more than one may share an id, if their questions differ.
+type Pending = HashMap<(SocketAddr, u16), Vec<(u64, DnsQuery, UnboundedSender<Delivery>)>>;
+
+// one shared udp socket, and the task reading responses off of it.
+#[derive(Debug)]
+struct UdpMux {
+    socket: Arc<UdpSocket>,
+    pending: Arc<Mutex<Pending>>,
+    reader: JoinHandle<()>
+}
+
+impl Drop for UdpMux {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/* read datagrams until we're dropped. anything that isn't from a server with a query of
+   its id outstanding is no use to anyone (probably a late answer to a query that's given
+   up), so it goes without a word. */
+async fn read_responses(socket: Arc<UdpSocket>, pending: Arc<Mutex<Pending>>) {
+    let mut rbuf = vec![0u8; 65535];
+    loop {
+        let (response_length, from) = match socket.recv_from(&mut rbuf).await {
+            Ok(r) => r,
+            // an icmp error from an earlier send can show up here. it's nothing to do with the next datagram.
+            Err(_) => continue
+        };
+        let rbytes = &rbuf[0 .. response_length];
+        if rbytes.len() < 2 {
+            continue;
+        }
+        let key = (from, u16::from_be_bytes([rbytes[0], rbytes[1]]));
+
+        let mut pending = pending.lock().unwrap();
+        let Some(waiting) = pending.get_mut(&key) else { continue };
+        let mut errors: Vec<DnsError> = Vec::new();
+        let found = waiting.iter().position(|(_, query, _)| match query.check_response(rbytes) {
+            Ok(()) => true,
+            Err(e) => { errors.push(e); false }
+        });
+        match found {
+            Some(i) => {
+                let (_, _, tx) = waiting.remove(i);
+                if waiting.is_empty() {
+                    pending.remove(&key);
+                }
+                let _ = tx.send(Ok(rbytes.to_vec()));
+            },
+            None => {
+                for ((_, _, tx), e) in waiting.iter().zip(errors) {
+                    let _ = tx.send(Err(format!("Dropped a packet from {from}: {e}")));
+                }
+            }
+        }
+    }
+}
+
+// takes a query back out of the pending map when it's done - or when it's cancelled.
+struct PendingGuard<'a> {
+    pending: &'a Mutex<Pending>,
+    key: (SocketAddr, u16),
+    token: u64
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        let mut pending = self.pending.lock().unwrap();
+        if let Some(waiting) = pending.get_mut(&self.key) {
+            waiting.retain(|(token, _, _)| *token != self.token);
+            if waiting.is_empty() {
+                pending.remove(&self.key);
+            }
+        }
+    }
+}
+
+/* the async client. it's set up (and builds queries) just like a DnsClient, and only does
+   udp and tcp. share one between tasks with an Arc, so they share its sockets. */
+#[derive(Debug)]
+pub struct AsyncDnsClient {
+    client: DnsClient,
+    v4: OnceCell<UdpMux>,
+    v6: OnceCell<UdpMux>,
+    next_token: AtomicU64
+}
+
+impl AsyncDnsClient {
+    pub fn new(servers: Vec<SocketAddr>, timeout: Duration, retries: u32,
+               transport: DnsTransport) -> AsyncDnsClient {
+        AsyncDnsClient::from_client(DnsClient::new(servers, timeout, retries, transport))
+    }
+
+    // an async client with the servers, timeouts and options of client.
+    pub fn from_client(client: DnsClient) -> AsyncDnsClient {
+        AsyncDnsClient { client, v4: OnceCell::new(), v6: OnceCell::new(), next_token: AtomicU64::new(0) }
+    }
+
+    pub fn client(&self) -> &DnsClient {
+        &self.client
+    }
+
+    pub fn make_query(&self, name: &str, qtype: DnsQType, qclass: DnsQClass) -> Result<DnsQuery, DnsError> {
+        self.client.make_query(name, qtype, qclass)
+    }
+
+    pub async fn query(&self, name: &str, qtype: DnsQType, qclass: DnsQClass) -> Result<DnsResponse, DnsError> {
+        let q = self.make_query(name, qtype, qclass)?;
+        self.send(&q).await
+    }
+
+    pub async fn send(&self, q: &DnsQuery) -> Result<DnsResponse, DnsError> {
+        self.send_with_attempts(q).await.0
+    }
+
+    // as DnsClient::send_with_attempts: the same rotation through the servers, and the same backoff.
+    pub async fn send_with_attempts(&self, q: &DnsQuery) -> (Result<DnsResponse, DnsError>, Vec<DnsAttempt>) {
+        let servers = self.client.servers();
+        let transport = self.client.transport();
+        let mut attempts: Vec<DnsAttempt> = Vec::new();
+        if servers.is_empty() {
+            return (Err(DnsError::NoServers), attempts);
+        }
+
+        let total = (self.client.retries() + 1).saturating_mul(servers.len() as u32);
+        let mut last_err = DnsError::NoServers;
+        for attempt in 0 .. total {
+            let server = servers[attempt as usize % servers.len()];
+            let timeout = self.client.attempt_timeout(attempt);
+
+            let mut rejected: Vec<String> = Vec::new();
+            let start = Instant::now();
+            let result = self.send_to(&server, q, timeout, &mut rejected).await;
+            let latency = start.elapsed();
+
+            match result {
+                Ok((response, used)) => {
+                    let outcome = if used == transport {
+                        DnsAttemptOutcome::Answered
+                    } else {
+                        DnsAttemptOutcome::AnsweredAfterTruncation
+                    };
+                    attempts.push(DnsAttempt::new(server, transport, timeout, latency, outcome, rejected));
+                    return (Ok(response), attempts);
+                },
+                Err(e) => {
+                    last_err = DnsError::Server { server, error: Box::new(e.clone()) };
+                    attempts.push(DnsAttempt::new(server, transport, timeout, latency,
+                                                  DnsAttemptOutcome::Failed(e), rejected));
+                }
+            }
+        }
+        (Err(last_err), attempts)
+    }
+
+    // one attempt at one server. as with transport::send_query, a truncated udp answer is asked again over tcp.
+    async fn send_to(&self, server: &SocketAddr, q: &DnsQuery, timeout: Duration,
+                     rejected: &mut Vec<String>) -> Result<(DnsResponse, DnsTransport), DnsError> {
+        let qbytes = q.to_bytes()?;
+        match self.client.transport() {
+            DnsTransport::UDP => {
+                let rbytes = self.udp_exchange(server, q, &qbytes, timeout, rejected).await?;
+                if !DnsHeader::from_bytes(&rbytes, 0)?.tc() {
+                    return Ok((DnsResponse::from_bytes(&rbytes, 0)?, DnsTransport::UDP));
+                }
+            },
+            DnsTransport::TCP => {},
+            #[allow(unreachable_patterns)]
+            other => return Err(DnsError::Config(format!("The async client can't send queries over {other}.")))
+        }
+        let rbytes = tcp_exchange(server, q, &qbytes, timeout, rejected).await?;
+        Ok((DnsResponse::from_bytes(&rbytes, 0)?, DnsTransport::TCP))
+    }
+
+    // the shared socket for server's address family, bound (and its reader started) the first time it's needed.
+    async fn mux(&self, server: &SocketAddr) -> Result<&UdpMux, DnsError> {
+        let cell = if server.is_ipv4() { &self.v4 } else { &self.v6 };
+        cell.get_or_try_init(|| async {
+            let local = local_bind_addr(server);
+            let socket = match UdpSocket::bind(local).await {
+                Ok(s) => Arc::new(s),
+                Err(e) => return Err(DnsError::io(format!("Couldn't bind UDP socket to {local}"), &e))
+            };
+            let pending: Arc<Mutex<Pending>> = Arc::new(Mutex::new(HashMap::new()));
+            let reader = tokio::spawn(read_responses(socket.clone(), pending.clone()));
+            Ok(UdpMux { socket, pending, reader })
+        }).await
+    }
+
+    /* send the query bytes to server over the shared udp socket, and wait for the reader to
+       hand us an answer to q. datagrams for q's id that don't answer it go onto rejected. */
+    async fn udp_exchange(&self, server: &SocketAddr, q: &DnsQuery, qbytes: &[u8], timeout: Duration,
+                          rejected: &mut Vec<String>) -> Result<Vec<u8>, DnsError> {
+        let mux = self.mux(server).await?;
+        let (tx, mut rx): (_, UnboundedReceiver<Delivery>) = unbounded_channel();
+        let key = (*server, q.header().id());
+        let token = self.next_token.fetch_add(1, Ordering::Relaxed);
+        // the reader only needs the id and question of q, and this is the cheapest way to get an owned copy.
+        let query = DnsQuery::from_bytes(qbytes, 0)?;
+        mux.pending.lock().unwrap().entry(key).or_default().push((token, query, tx));
+        let _guard = PendingGuard { pending: &mux.pending, key, token };
+
+        match mux.socket.send_to(qbytes, server).await {
+            Ok(sent) if sent != qbytes.len() => {
+                return Err(DnsError::Transport(format!("Only sent {sent} of {} query bytes.", qbytes.len())));
+            },
+            Ok(_) => {},
+            Err(e) => return Err(DnsError::io("Error sending to socket", &e))
+        }
+
+        let wait = async {
+            while let Some(delivery) = rx.recv().await {
+                match delivery {
+                    Ok(rbytes) => return Ok(rbytes),
+                    Err(e) => rejected.push(e)
+                }
+            }
+            Err(DnsError::Transport(String::from("UDP reader went away while waiting for response.")))
+        };
+        match tokio::time::timeout(timeout, wait).await {
+            Ok(r) => r,
+            Err(_) => Err(DnsError::Timeout(timeout))
+        }
+    }
+}
+
+/* send the query bytes to server over a tcp connection of their own, and return the
+   (unframed) response to q. as with transport::tcp_exchange, other messages are dropped
+   onto rejected, until timeout has passed. */
+async fn tcp_exchange(server: &SocketAddr, q: &DnsQuery, qbytes: &[u8], timeout: Duration,
+                      rejected: &mut Vec<String>) -> Result<Vec<u8>, DnsError> {
+    let exchange = async {
+        let mut stream = match TcpStream::connect(server).await {
+            Ok(s) => s,
+            Err(e) => return Err(DnsError::io(format!("Couldn't connect to {server} over TCP"), &e))
+        };
+        if let Err(e) = stream.write_all(&tcp_frame(qbytes)?).await {
+            return Err(DnsError::io("Error writing query to stream", &e));
+        }
+        loop {
+            let mut lenbytes = [0u8, 0u8];
+            if let Err(e) = stream.read_exact(&mut lenbytes).await {
+                return Err(DnsError::io("Error reading TCP length prefix", &e));
+            }
+            let len = u16::from_be_bytes(lenbytes) as usize;
+            let mut rbytes = vec![0u8; len];
+            if let Err(e) = stream.read_exact(&mut rbytes).await {
+                return Err(DnsError::io(format!("Error reading {len} byte TCP message"), &e));
+            }
+            match q.check_response(&rbytes) {
+                Ok(()) => return Ok(rbytes),
+                Err(e) => rejected.push(format!("Dropped a message from {server}: {e}"))
+            }
+        }
+    };
+    match tokio::time::timeout(timeout, exchange).await {
+        Ok(r) => r,
+        Err(_) => Err(DnsError::Timeout(timeout))
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use super::*;
+use super::client::DnsClient;
+
+/* bulk lookups: a list of (name, type, class) run through one client, with some number of
+   queries in flight at once and, optionally, a cap on how many go out per second. */
+
+// one line of a bulk input file: "name [type] [class]", with type defaulting to A and class to IN.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct BulkLookup {
+    name: String,
+    qtype: DnsQType,
+    qclass: DnsQClass
+}
+
+impl BulkLookup {
+    pub fn new(name: String, qtype: DnsQType, qclass: DnsQClass) -> BulkLookup {
+        BulkLookup { name, qtype, qclass }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn qtype(&self) -> DnsQType {
+        self.qtype
+    }
+
+    pub fn qclass(&self) -> DnsQClass {
+        self.qclass
+    }
+}
+
+impl fmt::Display for BulkLookup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.name, self.qtype, self.qclass)
+    }
+}
+
+// parse a line of input. blank lines and # comments give Ok(None).
+pub fn parse_bulk_line(line: &str) -> Result<Option<BulkLookup>, DnsError> {
+    let line = line.split('#').next().unwrap_or("");
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let (name, qtype, qclass) = match fields[..] {
+        [] => return Ok(None),
+        [name] => (name, DnsQType::A, DnsQClass::IN),
+        [name, qtype] => (name, DnsQType::from_string(qtype)?, DnsQClass::IN),
+        [name, qtype, qclass] => (name, DnsQType::from_string(qtype)?, DnsQClass::from_string(qclass)?),
+        _ => return Err(DnsError::Config(format!("Expected 'name [type] [class]', got '{}'.", line.trim())))
+    };
+    is_valid_dns_name(name)?;
+    Ok(Some(BulkLookup::new(name.to_string(), qtype, qclass)))
+}
+
+/* hands out times to send at, no closer together than 1/rate seconds. a rate of 0 means
+   no limit. it's shared between all the workers, so the rate is for the whole run. */
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Option<Duration>,
+    next: Mutex<Instant>
+}
+
+impl RateLimiter {
+    pub fn new(rate: u32) -> RateLimiter {
+        let interval = if rate == 0 { None } else { Some(Duration::from_secs(1) / rate) };
+        RateLimiter { interval, next: Mutex::new(Instant::now()) }
+    }
+
+    // block until it's our turn to send.
+    pub fn wait(&self) {
+        let Some(interval) = self.interval else { return };
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            // don't let a slow patch bank up a burst of slots to use all at once.
+            let slot = (*next).max(now);
+            *next = slot + interval;
+            slot
+        };
+        let now = Instant::now();
+        if slot > now {
+            thread::sleep(slot - now);
+        }
+    }
+}
+
+// what came of one line of input.
+#[derive(Debug)]
+pub struct BulkResult {
+    line: usize,
+    lookup: Result<BulkLookup, DnsError>,
+    response: Result<DnsResponse, DnsError>,
+    latency: Duration
+}
+
+impl BulkResult {
+    // the (one-based) line of input this is the result for.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    // what was looked up, or why the line couldn't be parsed.
+    pub fn lookup(&self) -> &Result<BulkLookup, DnsError> {
+        &self.lookup
+    }
+
+    pub fn response(&self) -> &Result<DnsResponse, DnsError> {
+        &self.response
+    }
+
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    // all the retries timed out, as opposed to failing some other way.
+    pub fn timed_out(&self) -> bool {
+        matches!(&self.response, Err(e) if e.is_timeout())
+    }
+}
+
+/* one line per result: name, type, class, rcode (or TIMEOUT/ERROR), latency, then the
+   answers separated by "; " - or for an error, what went wrong. */
+impl fmt::Display for BulkResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lookup = match &self.lookup {
+            Ok(l) => l,
+            Err(e) => return write!(f, "line {}: BADLINE {e}", self.line)
+        };
+        let ms = self.latency.as_secs_f64() * 1000.0;
+        match &self.response {
+            Ok(r) => {
+                let answers: Vec<String> = r.answers().iter().map(|rr| rr.to_string()).collect();
+                write!(f, "{lookup} {} {ms:.1}ms {}", r.rcode(), answers.join("; "))
+            },
+            Err(e) if self.timed_out() => write!(f, "{lookup} TIMEOUT {ms:.1}ms {e}"),
+            Err(e) => write!(f, "{lookup} ERROR {ms:.1}ms {e}")
+        }
+    }
+}
+
+// counts over a whole run.
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+pub struct BulkSummary {
+    total: u64,
+    rcodes: BTreeMap<String, u64>,
+    timeouts: u64,
+    errors: u64,
+    bad_lines: u64
+}
+
+impl BulkSummary {
+    pub fn add(&mut self, result: &BulkResult) {
+        self.total += 1;
+        match (&result.lookup, &result.response) {
+            (Err(_), _) => self.bad_lines += 1,
+            (Ok(_), Ok(r)) => *self.rcodes.entry(r.rcode().to_string()).or_insert(0) += 1,
+            (Ok(_), Err(_)) if result.timed_out() => self.timeouts += 1,
+            (Ok(_), Err(_)) => self.errors += 1
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    // how many responses came back with each rcode.
+    pub fn rcodes(&self) -> &BTreeMap<String, u64> {
+        &self.rcodes
+    }
+
+    pub fn timeouts(&self) -> u64 {
+        self.timeouts
+    }
+
+    // lookups that failed some way other than timing out.
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    pub fn bad_lines(&self) -> u64 {
+        self.bad_lines
+    }
+}
+
+impl fmt::Display for BulkSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Summary: {} lookups", self.total)?;
+        for (rcode, count) in &self.rcodes {
+            write!(f, "; {rcode} {count}")?;
+        }
+        write!(f, "; TIMEOUT {}; ERROR {}; BADLINE {}", self.timeouts, self.errors, self.bad_lines)
+    }
+}
+
+/* run every line of lines through client, with concurrency queries in flight and at most
+   rate sent per second (0 for no limit). each result is handed to on_result as soon as it's
+   in - so they come in the order they finish, not the order of the input - and lines are
+   read as they're needed, so the input can be as long as you like. */
+pub fn run_bulk<I, F>(client: &DnsClient, lines: I, concurrency: usize, rate: u32,
+                      mut on_result: F) -> BulkSummary
+    where I: Iterator<Item = String> + Send, F: FnMut(&BulkResult) {
+    let lines = Mutex::new(lines.enumerate());
+    let limiter = RateLimiter::new(rate);
+    let (tx, rx) = mpsc::channel::<BulkResult>();
+    let mut summary = BulkSummary::default();
+
+    thread::scope(|s| {
+        for _ in 0 .. concurrency.max(1) {
+            let tx = tx.clone();
+            let (lines, limiter) = (&lines, &limiter);
+            s.spawn(move || loop {
+                let (i, parsed) = {
+                    let mut lines = lines.lock().unwrap();
+                    // skip blank lines and comments here, so they don't cost a turn at the rate limiter.
+                    let next = lines.by_ref().map(|(i, l)| (i, parse_bulk_line(&l)))
+                        .find(|(_, l)| !matches!(l, Ok(None)));
+                    match next {
+                        Some(n) => n,
+                        None => return
+                    }
+                };
+                let line = i + 1;
+                let result = match parsed {
+                    Ok(Some(lookup)) => {
+                        limiter.wait();
+                        let start = Instant::now();
+                        let response = client.query(&lookup.name, lookup.qtype, lookup.qclass);
+                        BulkResult { line, lookup: Ok(lookup), response, latency: start.elapsed() }
+                    },
+                    Err(e) => BulkResult { line, lookup: Err(e.clone()), response: Err(e),
+                                           latency: Duration::ZERO },
+                    Ok(None) => continue
+                };
+                if tx.send(result).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(tx);
+
+        for result in rx {
+            summary.add(&result);
+            on_result(&result);
+        }
+    });
+    summary
+}
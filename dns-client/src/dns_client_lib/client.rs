@@ -0,0 +1,340 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use super::*;
+use super::transport::{DnsTransport, send_query};
+#[cfg(feature = "dot")]
+use super::tls::{self, DnsTlsConfig};
+#[cfg(feature = "doh")]
+use super::https::{self, DnsDohConfig};
+#[cfg(feature = "doq")]
+use super::quic;
+#[cfg(feature = "odoh")]
+use super::odoh::{self, DnsOdohConfig};
+#[cfg(feature = "dnssec")]
+use super::validator::DnsValidator;
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_RETRIES: u32 = 2;
+// however many times we've backed off, never wait longer than this for a single attempt.
+pub const MAX_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(60);
+// what we advertise in the OPT RR as the largest udp response we can handle.
+pub const DEFAULT_EDNS_PAYLOAD_SIZE: u16 = 4096;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum DnsAttemptOutcome {
+    Answered,
+    // the udp response had TC set, and the answer came back over tcp instead.
+    AnsweredAfterTruncation,
+    Failed(DnsError)
+}
+
+impl fmt::Display for DnsAttemptOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DnsAttemptOutcome::Answered => write!(f, "answered"),
+            DnsAttemptOutcome::AnsweredAfterTruncation => write!(f, "truncated, answered over TCP"),
+            DnsAttemptOutcome::Failed(e) => write!(f, "failed: {e}")
+        }
+    }
+}
+
+// one try at getting an answer out of one server.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct DnsAttempt {
+    server: SocketAddr,
+    transport: DnsTransport,
+    timeout: Duration,
+    latency: Duration,
+    outcome: DnsAttemptOutcome,
+    // packets that arrived during this attempt but didn't match the query, and why.
+    rejected: Vec<String>
+}
+
+impl DnsAttempt {
+    pub fn new(server: SocketAddr, transport: DnsTransport, timeout: Duration,
+               latency: Duration, outcome: DnsAttemptOutcome, rejected: Vec<String>) -> DnsAttempt {
+        DnsAttempt { server, transport, timeout, latency, outcome, rejected }
+    }
+
+    pub fn server(&self) -> SocketAddr {
+        self.server
+    }
+
+    pub fn transport(&self) -> DnsTransport {
+        self.transport
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    pub fn outcome(&self) -> &DnsAttemptOutcome {
+        &self.outcome
+    }
+
+    pub fn rejected(&self) -> &Vec<String> {
+        &self.rejected
+    }
+}
+
+impl fmt::Display for DnsAttempt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} over {} (timeout {:?}): {} after {:?}",
+               self.server, self.transport, self.timeout, self.outcome, self.latency)?;
+        for r in &self.rejected {
+            write!(f, "\n    {r}")?;
+        }
+        Ok(())
+    }
+}
+
+/* a resolver client: holds the servers to ask and how to ask them, and turns a
+   (name, type, class) into a response. */
+#[derive(Debug, Clone)]
+pub struct DnsClient {
+    servers: Vec<SocketAddr>,
+    timeout: Duration,
+    retries: u32,
+    transport: DnsTransport,
+    ecs_subnet: Option<String>,
+    ad: bool,
+    cd: bool,
+    #[cfg(feature = "dot")]
+    tls_config: DnsTlsConfig,
+    #[cfg(feature = "doh")]
+    doh_config: Option<DnsDohConfig>,
+    #[cfg(feature = "odoh")]
+    odoh_config: Option<DnsOdohConfig>,
+    #[cfg(feature = "dnssec")]
+    validator: Option<DnsValidator>
+}
+
+impl DnsClient {
+    pub fn new(servers: Vec<SocketAddr>, timeout: Duration, retries: u32,
+               transport: DnsTransport) -> DnsClient {
+        DnsClient { servers, timeout, retries, transport, ecs_subnet: None, ad: false, cd: false,
+                    #[cfg(feature = "dot")]
+                    tls_config: DnsTlsConfig::default(),
+                    #[cfg(feature = "doh")]
+                    doh_config: None,
+                    #[cfg(feature = "odoh")]
+                    odoh_config: None,
+                    #[cfg(feature = "dnssec")]
+                    validator: None }
+    }
+
+    pub fn servers(&self) -> &Vec<SocketAddr> {
+        &self.servers
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    pub fn transport(&self) -> DnsTransport {
+        self.transport
+    }
+
+    // add an edns client subnet option (rfc7871) to queries made by this client.
+    pub fn set_ecs_subnet(&mut self, subnet: Option<String>) {
+        self.ecs_subnet = subnet;
+    }
+
+    // set the AD bit on queries made by this client, to have the resolver say if the answer validated.
+    pub fn set_ad(&mut self, ad: bool) {
+        self.ad = ad;
+    }
+
+    // set the CD bit on queries made by this client, so the resolver doesn't validate.
+    pub fn set_cd(&mut self, cd: bool) {
+        self.cd = cd;
+    }
+
+    // how to authenticate servers when the transport is TLS (or HTTPS, or QUIC).
+    #[cfg(feature = "dot")]
+    pub fn set_tls_config(&mut self, config: DnsTlsConfig) {
+        self.tls_config = config;
+    }
+
+    #[cfg(feature = "dot")]
+    pub fn tls_config(&self) -> &DnsTlsConfig {
+        &self.tls_config
+    }
+
+    /* the url (and method) to use when the transport is HTTPS. the servers are then the
+       addresses to connect to for that url. */
+    #[cfg(feature = "doh")]
+    pub fn set_doh_config(&mut self, config: Option<DnsDohConfig>) {
+        self.doh_config = config;
+    }
+
+    #[cfg(feature = "doh")]
+    pub fn doh_config(&self) -> Option<&DnsDohConfig> {
+        self.doh_config.as_ref()
+    }
+
+    /* the proxy and target to use when the transport is ODOH. the servers are then the
+       addresses to connect to for the proxy. */
+    #[cfg(feature = "odoh")]
+    pub fn set_odoh_config(&mut self, config: Option<DnsOdohConfig>) {
+        self.odoh_config = config;
+    }
+
+    #[cfg(feature = "odoh")]
+    pub fn odoh_config(&self) -> Option<&DnsOdohConfig> {
+        self.odoh_config.as_ref()
+    }
+
+    /* validate every response with this, setting its dnssec_status. queries then ask for
+       DNSSEC records (the DO bit), and the DS and DNSKEY sets the validator needs are asked
+       for the same way as the query itself. */
+    #[cfg(feature = "dnssec")]
+    pub fn set_validator(&mut self, validator: Option<DnsValidator>) {
+        self.validator = validator;
+    }
+
+    #[cfg(feature = "dnssec")]
+    pub fn validator(&self) -> Option<&DnsValidator> {
+        self.validator.as_ref()
+    }
+
+    // build a recursive query with a random id and an OPT RR, ready to be sent.
+    pub fn make_query(&self, name: &str, qtype: DnsQType, qclass: DnsQClass) ->
+        Result<DnsQuery, DnsError> {
+        // let them know we can handle big packets, and add on the ecs subnet if we have one.
+        let mut builder = DnsQuery::builder()
+            .question(name, qtype, qclass)
+            .edns_payload_size(DEFAULT_EDNS_PAYLOAD_SIZE)
+            .ad(self.ad)
+            .cd(self.cd);
+        #[cfg(feature = "dnssec")]
+        {
+            builder = builder.dnssec_ok(self.validator.is_some());
+        }
+        if let Some(subnet) = &self.ecs_subnet {
+            builder = builder.ecs(subnet);
+        }
+        builder.build()
+    }
+
+    pub fn query(&self, name: &str, qtype: DnsQType, qclass: DnsQClass) ->
+        Result<DnsResponse, DnsError> {
+        let q = self.make_query(name, qtype, qclass)?;
+        self.send(&q)
+    }
+
+    /* how long the given (zero-based) attempt waits for an answer. every full pass through
+       the server list doubles the timeout (see rfc1035, section 4.2.1), up to
+       MAX_ATTEMPT_TIMEOUT. */
+    pub fn attempt_timeout(&self, attempt: u32) -> Duration {
+        let pass = attempt / (self.servers.len().max(1) as u32);
+        let factor = 1u32.checked_shl(pass).unwrap_or(u32::MAX);
+        match self.timeout.checked_mul(factor) {
+            Some(t) if t < MAX_ATTEMPT_TIMEOUT => t,
+            _ => MAX_ATTEMPT_TIMEOUT.max(self.timeout)
+        }
+    }
+
+    pub fn send(&self, q: &DnsQuery) -> Result<DnsResponse, DnsError> {
+        self.send_with_attempts(q).0
+    }
+
+    /* send an already-built query, returning every attempt made along the way. attempts
+       rotate through the server list, so a dead server only costs us one timeout per pass.
+       each server gets (retries + 1) attempts in total, with a backed-off timeout on each pass.
+       if nothing answers, the error from the last attempt is the one returned. */
+    pub fn send_with_attempts(&self, q: &DnsQuery) -> (Result<DnsResponse, DnsError>, Vec<DnsAttempt>) {
+        #[allow(unused_mut)]
+        let (mut result, attempts) = self.send_unvalidated(q);
+        #[cfg(feature = "dnssec")]
+        if let (Some(validator), Ok(response)) = (&self.validator, &mut result) {
+            let status = validator.validate(response, |name, qtype| {
+                let q = self.make_query(name, qtype, DnsQClass::IN)?;
+                self.send_unvalidated(&q).0
+            });
+            response.set_dnssec_status(Some(status));
+        }
+        (result, attempts)
+    }
+
+    fn send_unvalidated(&self, q: &DnsQuery) -> (Result<DnsResponse, DnsError>, Vec<DnsAttempt>) {
+        let mut attempts: Vec<DnsAttempt> = Vec::new();
+        if self.servers.is_empty() {
+            return (Err(DnsError::NoServers), attempts);
+        }
+
+        let total = (self.retries + 1).saturating_mul(self.servers.len() as u32);
+        let mut last_err = DnsError::NoServers;
+        for attempt in 0 .. total {
+            let server = self.servers[attempt as usize % self.servers.len()];
+            let timeout = self.attempt_timeout(attempt);
+
+            let mut rejected: Vec<String> = Vec::new();
+            let start = Instant::now();
+            let result = self.send_to(&server, q, timeout, &mut rejected);
+            let latency = start.elapsed();
+
+            match result {
+                Ok((response, used)) => {
+                    let outcome = if used == self.transport {
+                        DnsAttemptOutcome::Answered
+                    } else {
+                        DnsAttemptOutcome::AnsweredAfterTruncation
+                    };
+                    attempts.push(DnsAttempt::new(server, self.transport, timeout, latency,
+                                                  outcome, rejected));
+                    return (Ok(response), attempts);
+                },
+                Err(e) => {
+                    last_err = DnsError::Server { server, error: Box::new(e.clone()) };
+                    attempts.push(DnsAttempt::new(server, self.transport, timeout, latency,
+                                                  DnsAttemptOutcome::Failed(e), rejected));
+                }
+            }
+        }
+        (Err(last_err), attempts)
+    }
+
+    // one attempt at one server, over whichever transport we're set up for.
+    fn send_to(&self, server: &SocketAddr, q: &DnsQuery, timeout: Duration,
+               rejected: &mut Vec<String>) -> Result<(DnsResponse, DnsTransport), DnsError> {
+        match self.transport {
+            #[cfg(feature = "dot")]
+            DnsTransport::TLS => {
+                let response = tls::send_query(server, q, &self.tls_config, timeout, rejected)?;
+                Ok((response, DnsTransport::TLS))
+            },
+            #[cfg(feature = "doh")]
+            DnsTransport::HTTPS => {
+                let Some(doh) = &self.doh_config else {
+                    return Err(DnsError::Config(String::from("No DoH url configured to send an HTTPS query to.")));
+                };
+                let response = https::send_query(server, q, doh, &self.tls_config, timeout)?;
+                Ok((response, DnsTransport::HTTPS))
+            },
+            #[cfg(feature = "doq")]
+            DnsTransport::QUIC => {
+                let response = quic::send_query(server, q, &self.tls_config, timeout)?;
+                Ok((response, DnsTransport::QUIC))
+            },
+            #[cfg(feature = "odoh")]
+            DnsTransport::ODOH => {
+                let Some(odoh) = &self.odoh_config else {
+                    return Err(DnsError::Config(String::from("No ODoH proxy and target configured to send an ODOH query through.")));
+                };
+                let response = odoh::send_query(server, q, odoh, &self.tls_config, timeout)?;
+                Ok((response, DnsTransport::ODOH))
+            },
+            _ => send_query(server, q, self.transport, timeout, rejected)
+        }
+    }
+}
@@ -0,0 +1,705 @@
+/* the DNSSEC record types: DNSKEY, RRSIG, NSEC and DS from rfc4034, and NSEC3 and
+   NSEC3PARAM from rfc5155. their Display is the presentation format from those rfcs, after
+   the usual mnemonic. names in their rdata are never compressed (rfc4034, section 3.1.7 and
+   4.1.1; rfc3597, section 4). */
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use std::cmp::Ordering;
+use std::fmt;
+use super::*;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct DnsDNSKEYRecord {
+    flags: u16,
+    protocol: u8,
+    algorithm: u8,
+    public_key: Vec<u8>
+}
+
+impl DnsDNSKEYRecord {
+    pub fn new(flags: u16, protocol: u8, algorithm: u8, public_key: Vec<u8>) -> DnsDNSKEYRecord {
+        DnsDNSKEYRecord { flags, protocol, algorithm, public_key }
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    // always 3 (rfc4034, section 2.1.2).
+    pub fn protocol(&self) -> u8 {
+        self.protocol
+    }
+
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    // the key can sign the zone's records (rfc4034, section 2.1.1).
+    pub fn zone_key(&self) -> bool {
+        self.flags & 0x0100 != 0
+    }
+
+    // secure entry point - usually the key a DS in the parent points at (rfc3757).
+    pub fn sep(&self) -> bool {
+        self.flags & 0x0001 != 0
+    }
+
+    // rfc5011, section 7.
+    pub fn revoked(&self) -> bool {
+        self.flags & 0x0080 != 0
+    }
+
+    /* the tag RRSIG and DS records use to pick this key out (rfc4034, appendix B). algorithm
+       1 (RSA/MD5) takes it from the modulus; everything else sums up the rdata. */
+    pub fn key_tag(&self) -> u16 {
+        if self.algorithm == 1 {
+            let len = self.public_key.len();
+            if len < 3 {
+                return 0;
+            }
+            return u16::from_be_bytes([self.public_key[len - 3], self.public_key[len - 2]]);
+        }
+        let mut ac: u32 = 0;
+        for (i, b) in self.to_bytes().iter().enumerate() {
+            ac += if i & 1 == 1 { *b as u32 } else { (*b as u32) << 8 };
+        }
+        ac += (ac >> 16) & 0xFFFF;
+        (ac & 0xFFFF) as u16
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut ret: Vec<u8> = Vec::new();
+        ret.extend_from_slice(&self.flags.to_be_bytes());
+        ret.push(self.protocol);
+        ret.push(self.algorithm);
+        ret.extend_from_slice(&self.public_key);
+        ret
+    }
+
+    pub fn from_bytes(buf: &[u8], offset: usize, rdlen: usize) -> Result<(DnsDNSKEYRecord, usize), DnsError> {
+        // flags, protocol and algorithm, then the key is the rest.
+        check_bounds(buf, offset, 4, "DNSKEY record")?;
+        let keylen = match rdlen.checked_sub(4) {
+            Some(l) => l,
+            None => return Err(DnsError::BadRecord { context: "DNSKEY record", offset,
+                                                     reason: "the rdata is too short." })
+        };
+        check_bounds(buf, offset + 4, keylen, "DNSKEY record public key")?;
+        let o = offset;
+        let flags = u16::from_be_bytes([buf[o], buf[o+1]]);
+        let key = buf[o + 4 .. o + 4 + keylen].to_vec();
+        Ok((DnsDNSKEYRecord::new(flags, buf[o+2], buf[o+3], key), rdlen))
+    }
+}
+
+impl fmt::Display for DnsDNSKEYRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DNSKEY: {} {} {} {}", self.flags, self.protocol, self.algorithm, BASE64.encode(&self.public_key))
+    }
+}
+
+impl RData for DnsDNSKEYRecord {
+    const TYPE: u16 = 48;
+    const MNEMONIC: &'static str = "DNSKEY";
+
+    fn parse(buf: &[u8], offset: usize, rdlen: usize) -> Result<Self, DnsError> {
+        Ok(DnsDNSKEYRecord::from_bytes(buf, offset, rdlen)?.0)
+    }
+
+    fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+        w.write_bytes(&self.to_bytes());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct DnsDSRecord {
+    key_tag: u16,
+    algorithm: u8,
+    digest_type: u8,
+    digest: Vec<u8>
+}
+
+impl DnsDSRecord {
+    pub fn new(key_tag: u16, algorithm: u8, digest_type: u8, digest: Vec<u8>) -> DnsDSRecord {
+        DnsDSRecord { key_tag, algorithm, digest_type, digest }
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    // 1 is SHA-1, 2 SHA-256 (rfc4509), 4 SHA-384 (rfc6605).
+    pub fn digest_type(&self) -> u8 {
+        self.digest_type
+    }
+
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut ret: Vec<u8> = Vec::new();
+        ret.extend_from_slice(&self.key_tag.to_be_bytes());
+        ret.push(self.algorithm);
+        ret.push(self.digest_type);
+        ret.extend_from_slice(&self.digest);
+        ret
+    }
+
+    pub fn from_bytes(buf: &[u8], offset: usize, rdlen: usize) -> Result<(DnsDSRecord, usize), DnsError> {
+        // key tag, algorithm and digest type, then the digest is the rest.
+        check_bounds(buf, offset, 4, "DS record")?;
+        let digestlen = match rdlen.checked_sub(4) {
+            Some(l) => l,
+            None => return Err(DnsError::BadRecord { context: "DS record", offset,
+                                                     reason: "the rdata is too short." })
+        };
+        check_bounds(buf, offset + 4, digestlen, "DS record digest")?;
+        let o = offset;
+        let key_tag = u16::from_be_bytes([buf[o], buf[o+1]]);
+        let digest = buf[o + 4 .. o + 4 + digestlen].to_vec();
+        Ok((DnsDSRecord::new(key_tag, buf[o+2], buf[o+3], digest), rdlen))
+    }
+}
+
+impl fmt::Display for DnsDSRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DS: {} {} {} {}", self.key_tag, self.algorithm, self.digest_type, hex(&self.digest))
+    }
+}
+
+impl RData for DnsDSRecord {
+    const TYPE: u16 = 43;
+    const MNEMONIC: &'static str = "DS";
+
+    fn parse(buf: &[u8], offset: usize, rdlen: usize) -> Result<Self, DnsError> {
+        Ok(DnsDSRecord::from_bytes(buf, offset, rdlen)?.0)
+    }
+
+    fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+        w.write_bytes(&self.to_bytes());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct DnsRRSIGRecord {
+    type_covered: DnsQType,
+    algorithm: u8,
+    labels: u8,
+    original_ttl: u32,
+    expiration: u32,
+    inception: u32,
+    key_tag: u16,
+    signer: String,
+    signature: Vec<u8>
+}
+
+impl DnsRRSIGRecord {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(type_covered: DnsQType, algorithm: u8, labels: u8, original_ttl: u32, expiration: u32,
+               inception: u32, key_tag: u16, signer: String, signature: Vec<u8>) -> DnsRRSIGRecord {
+        DnsRRSIGRecord { type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag,
+                         signer, signature }
+    }
+
+    pub fn type_covered(&self) -> DnsQType {
+        self.type_covered
+    }
+
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    // labels in the owner name, not counting the root or a leading wildcard.
+    pub fn labels(&self) -> u8 {
+        self.labels
+    }
+
+    pub fn original_ttl(&self) -> u32 {
+        self.original_ttl
+    }
+
+    // seconds since the epoch, modulo 2^32 (rfc4034, section 3.1.5).
+    pub fn expiration(&self) -> u32 {
+        self.expiration
+    }
+
+    pub fn inception(&self) -> u32 {
+        self.inception
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    pub fn signer(&self) -> &str {
+        &self.signer
+    }
+
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+        let mut ret: Vec<u8> = Vec::new();
+        ret.extend_from_slice(&self.type_covered.to_u16().to_be_bytes());
+        ret.push(self.algorithm);
+        ret.push(self.labels);
+        ret.extend_from_slice(&self.original_ttl.to_be_bytes());
+        ret.extend_from_slice(&self.expiration.to_be_bytes());
+        ret.extend_from_slice(&self.inception.to_be_bytes());
+        ret.extend_from_slice(&self.key_tag.to_be_bytes());
+        ret.append(&mut wire_name(&self.signer)?);
+        ret.extend_from_slice(&self.signature);
+        Ok(ret)
+    }
+
+    pub fn from_bytes(buf: &[u8], offset: usize, rdlen: usize) -> Result<(DnsRRSIGRecord, usize), DnsError> {
+        // 18 bytes of fixed fields, then 1+ for the signer's name.
+        check_bounds(buf, offset, 19, "RRSIG record")?;
+        let mut o = offset;
+        let type_covered = DnsQType::from_u16(u16::from_be_bytes([buf[o], buf[o+1]]));
+        let algorithm = buf[o+2];
+        let labels = buf[o+3];
+        o += 4;
+        let original_ttl = u32::from_be_bytes([buf[o], buf[o+1], buf[o+2], buf[o+3]]);
+        o += 4;
+        let expiration = u32::from_be_bytes([buf[o], buf[o+1], buf[o+2], buf[o+3]]);
+        o += 4;
+        let inception = u32::from_be_bytes([buf[o], buf[o+1], buf[o+2], buf[o+3]]);
+        o += 4;
+        let key_tag = u16::from_be_bytes([buf[o], buf[o+1]]);
+        o += 2;
+        let (signer, count) = dns_name_to_string(buf, o)?;
+        o += count;
+
+        let siglen = match rdlen.checked_sub(o - offset) {
+            Some(l) => l,
+            None => return Err(DnsError::BadRecord { context: "RRSIG record", offset,
+                                                     reason: "the signer's name runs past the rdata." })
+        };
+        check_bounds(buf, o, siglen, "RRSIG record signature")?;
+        let signature = buf[o .. o + siglen].to_vec();
+        Ok((DnsRRSIGRecord::new(type_covered, algorithm, labels, original_ttl, expiration, inception,
+                                key_tag, signer, signature), rdlen))
+    }
+}
+
+impl fmt::Display for DnsRRSIGRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RRSIG: {} {} {} {} {} {} {} {} {}", type_mnemonic(self.type_covered), self.algorithm,
+               self.labels, self.original_ttl, format_time(self.expiration), format_time(self.inception),
+               self.key_tag, self.signer, BASE64.encode(&self.signature))
+    }
+}
+
+impl RData for DnsRRSIGRecord {
+    const TYPE: u16 = 46;
+    const MNEMONIC: &'static str = "RRSIG";
+
+    fn parse(buf: &[u8], offset: usize, rdlen: usize) -> Result<Self, DnsError> {
+        Ok(DnsRRSIGRecord::from_bytes(buf, offset, rdlen)?.0)
+    }
+
+    fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+        w.write_bytes(&self.to_bytes()?);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct DnsNSECRecord {
+    next: String,
+    types: Vec<DnsQType>
+}
+
+impl DnsNSECRecord {
+    pub fn new(next: String, types: Vec<DnsQType>) -> DnsNSECRecord {
+        DnsNSECRecord { next, types }
+    }
+
+    pub fn next(&self) -> &str {
+        &self.next
+    }
+
+    // the types at the owner name, in numeric order when parsed.
+    pub fn types(&self) -> &[DnsQType] {
+        &self.types
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+        let mut ret = wire_name(&self.next)?;
+        ret.append(&mut type_bitmap_to_bytes(&self.types));
+        Ok(ret)
+    }
+
+    pub fn from_bytes(buf: &[u8], offset: usize, rdlen: usize) -> Result<(DnsNSECRecord, usize), DnsError> {
+        check_bounds(buf, offset, 1, "NSEC record")?;
+        let (next, count) = dns_name_to_string(buf, offset)?;
+        let bitmaplen = match rdlen.checked_sub(count) {
+            Some(l) => l,
+            None => return Err(DnsError::BadRecord { context: "NSEC record", offset,
+                                                     reason: "the next name runs past the rdata." })
+        };
+        let types = type_bitmap_from_bytes(buf, offset + count, bitmaplen, "NSEC record type bitmap")?;
+        Ok((DnsNSECRecord::new(next, types), rdlen))
+    }
+}
+
+impl fmt::Display for DnsNSECRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NSEC: {}", self.next)?;
+        write_types(f, &self.types)
+    }
+}
+
+impl RData for DnsNSECRecord {
+    const TYPE: u16 = 47;
+    const MNEMONIC: &'static str = "NSEC";
+
+    fn parse(buf: &[u8], offset: usize, rdlen: usize) -> Result<Self, DnsError> {
+        Ok(DnsNSECRecord::from_bytes(buf, offset, rdlen)?.0)
+    }
+
+    fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+        w.write_bytes(&self.to_bytes()?);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct DnsNSEC3Record {
+    hash_algorithm: u8,
+    flags: u8,
+    iterations: u16,
+    salt: Vec<u8>,
+    next_hashed_owner: Vec<u8>,
+    types: Vec<DnsQType>
+}
+
+impl DnsNSEC3Record {
+    pub fn new(hash_algorithm: u8, flags: u8, iterations: u16, salt: Vec<u8>, next_hashed_owner: Vec<u8>,
+               types: Vec<DnsQType>) -> DnsNSEC3Record {
+        DnsNSEC3Record { hash_algorithm, flags, iterations, salt, next_hashed_owner, types }
+    }
+
+    // 1 is SHA-1, the only one defined.
+    pub fn hash_algorithm(&self) -> u8 {
+        self.hash_algorithm
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    // the span to the next name may have unsigned delegations in it (rfc5155, section 3.1.2.1).
+    pub fn opt_out(&self) -> bool {
+        self.flags & 0x01 != 0
+    }
+
+    pub fn iterations(&self) -> u16 {
+        self.iterations
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    pub fn next_hashed_owner(&self) -> &[u8] {
+        &self.next_hashed_owner
+    }
+
+    pub fn types(&self) -> &[DnsQType] {
+        &self.types
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+        let mut ret: Vec<u8> = Vec::new();
+        ret.push(self.hash_algorithm);
+        ret.push(self.flags);
+        ret.extend_from_slice(&self.iterations.to_be_bytes());
+        push_with_len(&mut ret, &self.salt, "NSEC3 record salt")?;
+        push_with_len(&mut ret, &self.next_hashed_owner, "NSEC3 record next hashed owner")?;
+        ret.append(&mut type_bitmap_to_bytes(&self.types));
+        Ok(ret)
+    }
+
+    pub fn from_bytes(buf: &[u8], offset: usize, rdlen: usize) -> Result<(DnsNSEC3Record, usize), DnsError> {
+        // algorithm, flags, iterations and salt length, then the salt.
+        check_bounds(buf, offset, 5, "NSEC3 record")?;
+        let mut o = offset;
+        let hash_algorithm = buf[o];
+        let flags = buf[o+1];
+        let iterations = u16::from_be_bytes([buf[o+2], buf[o+3]]);
+        o += 4;
+        let salt = read_with_len(buf, o, "NSEC3 record salt")?;
+        o += 1 + salt.len();
+        let next_hashed_owner = read_with_len(buf, o, "NSEC3 record next hashed owner")?;
+        o += 1 + next_hashed_owner.len();
+
+        let bitmaplen = match rdlen.checked_sub(o - offset) {
+            Some(l) => l,
+            None => return Err(DnsError::BadRecord { context: "NSEC3 record", offset,
+                                                     reason: "the salt and hash run past the rdata." })
+        };
+        let types = type_bitmap_from_bytes(buf, o, bitmaplen, "NSEC3 record type bitmap")?;
+        Ok((DnsNSEC3Record::new(hash_algorithm, flags, iterations, salt, next_hashed_owner, types), rdlen))
+    }
+}
+
+impl fmt::Display for DnsNSEC3Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NSEC3: {} {} {} {} {}", self.hash_algorithm, self.flags, self.iterations, salt_string(&self.salt),
+               base32hex(&self.next_hashed_owner))?;
+        write_types(f, &self.types)
+    }
+}
+
+impl RData for DnsNSEC3Record {
+    const TYPE: u16 = 50;
+    const MNEMONIC: &'static str = "NSEC3";
+
+    fn parse(buf: &[u8], offset: usize, rdlen: usize) -> Result<Self, DnsError> {
+        Ok(DnsNSEC3Record::from_bytes(buf, offset, rdlen)?.0)
+    }
+
+    fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+        w.write_bytes(&self.to_bytes()?);
+        Ok(())
+    }
+}
+
+// the NSEC3 parameters a zone's authoritative servers use for it (rfc5155, section 4).
+#[derive(Debug, Eq, PartialEq)]
+pub struct DnsNSEC3PARAMRecord {
+    hash_algorithm: u8,
+    flags: u8,
+    iterations: u16,
+    salt: Vec<u8>
+}
+
+impl DnsNSEC3PARAMRecord {
+    pub fn new(hash_algorithm: u8, flags: u8, iterations: u16, salt: Vec<u8>) -> DnsNSEC3PARAMRecord {
+        DnsNSEC3PARAMRecord { hash_algorithm, flags, iterations, salt }
+    }
+
+    pub fn hash_algorithm(&self) -> u8 {
+        self.hash_algorithm
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn iterations(&self) -> u16 {
+        self.iterations
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+        let mut ret: Vec<u8> = Vec::new();
+        ret.push(self.hash_algorithm);
+        ret.push(self.flags);
+        ret.extend_from_slice(&self.iterations.to_be_bytes());
+        push_with_len(&mut ret, &self.salt, "NSEC3PARAM record salt")?;
+        Ok(ret)
+    }
+
+    pub fn from_bytes(buf: &[u8], offset: usize, rdlen: usize) -> Result<(DnsNSEC3PARAMRecord, usize), DnsError> {
+        check_bounds(buf, offset, 5, "NSEC3PARAM record")?;
+        let o = offset;
+        let iterations = u16::from_be_bytes([buf[o+2], buf[o+3]]);
+        let salt = read_with_len(buf, o + 4, "NSEC3PARAM record salt")?;
+        // the salt is the last thing in the rdata, so it has to end right where the rdata does.
+        match (5 + salt.len()).cmp(&rdlen) {
+            Ordering::Greater => Err(DnsError::BadRecord { context: "NSEC3PARAM record", offset,
+                                                           reason: "the salt runs past the rdata." }),
+            Ordering::Less => Err(DnsError::BadRecord { context: "NSEC3PARAM record", offset,
+                                                        reason: "the rdata runs on past the salt." }),
+            Ordering::Equal => Ok((DnsNSEC3PARAMRecord::new(buf[o], buf[o+1], iterations, salt), rdlen))
+        }
+    }
+}
+
+impl fmt::Display for DnsNSEC3PARAMRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NSEC3PARAM: {} {} {} {}", self.hash_algorithm, self.flags, self.iterations,
+               salt_string(&self.salt))
+    }
+}
+
+impl RData for DnsNSEC3PARAMRecord {
+    const TYPE: u16 = 51;
+    const MNEMONIC: &'static str = "NSEC3PARAM";
+
+    fn parse(buf: &[u8], offset: usize, rdlen: usize) -> Result<Self, DnsError> {
+        Ok(DnsNSEC3PARAMRecord::from_bytes(buf, offset, rdlen)?.0)
+    }
+
+    fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+        w.write_bytes(&self.to_bytes()?);
+        Ok(())
+    }
+}
+
+/* a name in rdata, uncompressed and as it is: checked against the wire format's limits, but not
+   the hostname rules string_to_dns_name holds names to. the next name of an NSEC is often a
+   wildcard, like *.example., and that has to be written out like any other. */
+fn wire_name(name: &str) -> Result<Vec<u8>, DnsError> {
+    let mut w = DnsMessageWriter::uncompressed();
+    w.write_name(name, false)?;
+    Ok(w.into_bytes())
+}
+
+/* the type bitmap NSEC and NSEC3 use (rfc4034, section 4.1.2): for each block of 256 types
+   in use, the block number, the length of its bitmap, then the bitmap, with trailing zero
+   bytes left off. */
+fn type_bitmap_to_bytes(types: &[DnsQType]) -> Vec<u8> {
+    let mut codes: Vec<u16> = types.iter().map(|t| t.to_u16()).collect();
+    codes.sort_unstable();
+    codes.dedup();
+
+    let mut ret: Vec<u8> = Vec::new();
+    let mut i = 0;
+    while i < codes.len() {
+        let window = codes[i] >> 8;
+        let mut bitmap = [0u8; 32];
+        let mut len = 0;
+        while i < codes.len() && codes[i] >> 8 == window {
+            let bit = (codes[i] & 0xFF) as usize;
+            bitmap[bit / 8] |= 0x80 >> (bit % 8);
+            len = bit / 8 + 1;
+            i += 1;
+        }
+        ret.push(window as u8);
+        ret.push(len as u8);
+        ret.extend_from_slice(&bitmap[.. len]);
+    }
+    ret
+}
+
+fn type_bitmap_from_bytes(buf: &[u8], offset: usize, len: usize, context: &'static str)
+    -> Result<Vec<DnsQType>, DnsError> {
+    check_bounds(buf, offset, len, context)?;
+    let end = offset + len;
+    let mut o = offset;
+    let mut last_window: Option<u8> = None;
+    let mut types: Vec<DnsQType> = Vec::new();
+    while o < end {
+        if end - o < 2 {
+            return Err(DnsError::BadRecord { context, offset: o, reason: "a window is cut off." });
+        }
+        let window = buf[o];
+        let bitmaplen = buf[o+1] as usize;
+        if last_window.is_some_and(|last| last >= window) {
+            return Err(DnsError::BadRecord { context, offset: o, reason: "the windows are out of order." });
+        }
+        if bitmaplen == 0 || bitmaplen > 32 || end - o - 2 < bitmaplen {
+            return Err(DnsError::BadRecord { context, offset: o, reason: "a window has a bad length." });
+        }
+        o += 2;
+        for (i, byte) in buf[o .. o + bitmaplen].iter().enumerate() {
+            for bit in 0 .. 8 {
+                if byte & (0x80 >> bit) != 0 {
+                    types.push(DnsQType::from_u16(((window as u16) << 8) | (i * 8 + bit) as u16));
+                }
+            }
+        }
+        o += bitmaplen;
+        last_window = Some(window);
+    }
+    Ok(types)
+}
+
+// the salt and hash in NSEC3 records have a length byte in front.
+fn push_with_len(ret: &mut Vec<u8>, bytes: &[u8], context: &'static str) -> Result<(), DnsError> {
+    if bytes.len() > 255 {
+        return Err(DnsError::Oversize { context, len: bytes.len(), max: 255 });
+    }
+    ret.push(bytes.len() as u8);
+    ret.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn read_with_len(buf: &[u8], offset: usize, context: &'static str) -> Result<Vec<u8>, DnsError> {
+    check_bounds(buf, offset, 1, context)?;
+    let len = buf[offset] as usize;
+    check_bounds(buf, offset + 1, len, context)?;
+    Ok(buf[offset + 1 .. offset + 1 + len].to_vec())
+}
+
+// types without a mnemonic are written TYPEnnn (rfc3597, section 5).
+fn type_mnemonic(t: DnsQType) -> String {
+    match t {
+        DnsQType::RESERVED(n) if rdata::registered_mnemonic(n).is_none() => format!("TYPE{n}"),
+        _ => t.to_string()
+    }
+}
+
+fn write_types(f: &mut fmt::Formatter, types: &[DnsQType]) -> fmt::Result {
+    for t in types {
+        write!(f, " {}", type_mnemonic(*t))?;
+    }
+    Ok(())
+}
+
+// an empty salt is written as "-" (rfc5155, section 3.3).
+fn salt_string(salt: &[u8]) -> String {
+    if salt.is_empty() { String::from("-") } else { hex(salt) }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+// base32 with the "extended hex" alphabet, unpadded (rfc4648 section 7, rfc5155 section 3.3).
+pub(crate) fn base32hex(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+    let mut ret = String::new();
+    let mut acc: u16 = 0;
+    let mut bits = 0;
+    for b in bytes {
+        acc = (acc << 8) | *b as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            ret.push(ALPHABET[((acc >> bits) & 0x1F) as usize] as char);
+        }
+        acc &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        ret.push(ALPHABET[((acc << (5 - bits)) & 0x1F) as usize] as char);
+    }
+    ret
+}
+
+// YYYYMMDDHHmmSS, in UTC (rfc4034, section 3.2).
+fn format_time(t: u32) -> String {
+    let days = (t / 86400) as i64;
+    let secs = t % 86400;
+    // days to a date, from http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{year:04}{month:02}{day:02}{:02}{:02}{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
@@ -0,0 +1,216 @@
+/* just enough of hpke (rfc9180) for oblivious doh: base mode, with the one cipher suite
+   every odoh target supports - DHKEM(X25519, HKDF-SHA256), HKDF-SHA256 and AES-128-GCM. */
+
+use ring::aead::{AES_128_GCM, Aad, LessSafeKey, Nonce, UnboundKey};
+use ring::hmac;
+use x25519_dalek::{PublicKey, StaticSecret};
+use super::DnsError;
+
+// the registry values for the suite, rfc9180 section 7.
+pub const KEM_X25519_HKDF_SHA256: u16 = 0x0020;
+pub const KDF_HKDF_SHA256: u16 = 0x0001;
+pub const AEAD_AES_128_GCM: u16 = 0x0001;
+
+// sizes for the suite: Nsecret/Nenc/Npk, Nh, Nk and Nn.
+pub const NSECRET: usize = 32;
+pub const NENC: usize = 32;
+pub const NH: usize = 32;
+pub const NK: usize = 16;
+pub const NN: usize = 12;
+
+const MODE_BASE: u8 = 0x00;
+
+// HKDF-Extract, rfc5869 section 2.2. an empty salt is the same as Nh zeros.
+pub fn extract(salt: &[u8], ikm: &[u8]) -> [u8; NH] {
+    let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, salt), ikm);
+    let mut ret = [0u8; NH];
+    ret.copy_from_slice(tag.as_ref());
+    ret
+}
+
+// HKDF-Expand, rfc5869 section 2.3.
+pub fn expand(prk: &[u8], info: &[u8], len: usize) -> Result<Vec<u8>, DnsError> {
+    if len > 255 * NH {
+        return Err(DnsError::Crypto(format!("Can't expand to {len} bytes with HKDF-SHA256.")));
+    }
+    let key = hmac::Key::new(hmac::HMAC_SHA256, prk);
+    let mut ret: Vec<u8> = Vec::with_capacity(len);
+    let mut t: Vec<u8> = Vec::new();
+    let mut counter = 1u8;
+    while ret.len() < len {
+        let mut ctx = hmac::Context::with_key(&key);
+        ctx.update(&t);
+        ctx.update(info);
+        ctx.update(&[counter]);
+        t = ctx.sign().as_ref().to_vec();
+        ret.extend_from_slice(&t);
+        counter = counter.wrapping_add(1);
+    }
+    ret.truncate(len);
+    Ok(ret)
+}
+
+// LabeledExtract and LabeledExpand, rfc9180 section 4.
+fn labeled_extract(suite_id: &[u8], salt: &[u8], label: &[u8], ikm: &[u8]) -> [u8; NH] {
+    let mut labeled_ikm: Vec<u8> = Vec::new();
+    labeled_ikm.extend_from_slice(b"HPKE-v1");
+    labeled_ikm.extend_from_slice(suite_id);
+    labeled_ikm.extend_from_slice(label);
+    labeled_ikm.extend_from_slice(ikm);
+    extract(salt, &labeled_ikm)
+}
+
+fn labeled_expand(suite_id: &[u8], prk: &[u8], label: &[u8], info: &[u8], len: usize) -> Result<Vec<u8>, DnsError> {
+    let mut labeled_info: Vec<u8> = Vec::new();
+    labeled_info.extend_from_slice(&(len as u16).to_be_bytes());
+    labeled_info.extend_from_slice(b"HPKE-v1");
+    labeled_info.extend_from_slice(suite_id);
+    labeled_info.extend_from_slice(label);
+    labeled_info.extend_from_slice(info);
+    expand(prk, &labeled_info, len)
+}
+
+fn kem_suite_id() -> Vec<u8> {
+    let mut ret = b"KEM".to_vec();
+    ret.extend_from_slice(&KEM_X25519_HKDF_SHA256.to_be_bytes());
+    ret
+}
+
+fn hpke_suite_id() -> Vec<u8> {
+    let mut ret = b"HPKE".to_vec();
+    ret.extend_from_slice(&KEM_X25519_HKDF_SHA256.to_be_bytes());
+    ret.extend_from_slice(&KDF_HKDF_SHA256.to_be_bytes());
+    ret.extend_from_slice(&AEAD_AES_128_GCM.to_be_bytes());
+    ret
+}
+
+fn public_key_from_bytes(pk: &[u8]) -> Result<PublicKey, DnsError> {
+    match <[u8; NENC]>::try_from(pk) {
+        Ok(b) => Ok(PublicKey::from(b)),
+        Err(_) => Err(DnsError::Crypto(format!("Got a {} byte X25519 public key, expected {NENC}.", pk.len())))
+    }
+}
+
+// ExtractAndExpand for DHKEM, rfc9180 section 4.1.
+fn kem_shared_secret(dh: &[u8], enc: &[u8], pk_r: &[u8]) -> Result<Vec<u8>, DnsError> {
+    // an all zero dh output means a low order point was given as a key. see rfc9180, section 7.1.4.
+    if dh.iter().all(|b| *b == 0) {
+        return Err(DnsError::Crypto(String::from("Got an X25519 public key that gives an all zero shared secret.")));
+    }
+    let mut kem_context = enc.to_vec();
+    kem_context.extend_from_slice(pk_r);
+    let suite_id = kem_suite_id();
+    let eae_prk = labeled_extract(&suite_id, b"", b"eae_prk", dh);
+    labeled_expand(&suite_id, &eae_prk, b"shared_secret", &kem_context, NSECRET)
+}
+
+// the public key that goes with an X25519 private key.
+pub fn public_key(sk: &[u8; 32]) -> [u8; NENC] {
+    PublicKey::from(&StaticSecret::from(*sk)).to_bytes()
+}
+
+/* the keys that come out of the key schedule (rfc9180, section 5.1). we only ever seal or
+   open one message per context, so there's no sequence number to keep. */
+#[derive(Debug, Clone)]
+pub struct HpkeContext {
+    key: Vec<u8>,
+    base_nonce: Vec<u8>,
+    exporter_secret: Vec<u8>
+}
+
+impl HpkeContext {
+    fn new(shared_secret: &[u8], info: &[u8]) -> Result<HpkeContext, DnsError> {
+        let suite_id = hpke_suite_id();
+        let psk_id_hash = labeled_extract(&suite_id, b"", b"psk_id_hash", b"");
+        let info_hash = labeled_extract(&suite_id, b"", b"info_hash", info);
+        let mut ks_context = vec![MODE_BASE];
+        ks_context.extend_from_slice(&psk_id_hash);
+        ks_context.extend_from_slice(&info_hash);
+
+        let secret = labeled_extract(&suite_id, shared_secret, b"secret", b"");
+        Ok(HpkeContext {
+            key: labeled_expand(&suite_id, &secret, b"key", &ks_context, NK)?,
+            base_nonce: labeled_expand(&suite_id, &secret, b"base_nonce", &ks_context, NN)?,
+            exporter_secret: labeled_expand(&suite_id, &secret, b"exp", &ks_context, NH)?
+        })
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    pub fn base_nonce(&self) -> &[u8] {
+        &self.base_nonce
+    }
+
+    pub fn exporter_secret(&self) -> &[u8] {
+        &self.exporter_secret
+    }
+
+    // encrypt the first (and only) message in this context.
+    pub fn seal(&self, aad: &[u8], pt: &[u8]) -> Result<Vec<u8>, DnsError> {
+        aead_seal(&self.key, &self.base_nonce, aad, pt)
+    }
+
+    pub fn open(&self, aad: &[u8], ct: &[u8]) -> Result<Vec<u8>, DnsError> {
+        aead_open(&self.key, &self.base_nonce, aad, ct)
+    }
+
+    // a secret derived from this context, for things like protecting a response. section 5.3.
+    pub fn export(&self, exporter_context: &[u8], len: usize) -> Result<Vec<u8>, DnsError> {
+        labeled_expand(&hpke_suite_id(), &self.exporter_secret, b"sec", exporter_context, len)
+    }
+}
+
+/* SetupBaseS: encapsulate a fresh secret to the recipient's public key pk_r, using sk_e as
+   the ephemeral private key. sk_e has to be random and never reused - it's only a parameter
+   so the test vectors can be checked. returns enc (the ephemeral public key) and the context. */
+pub fn setup_base_s(pk_r: &[u8], info: &[u8], sk_e: [u8; 32]) -> Result<(Vec<u8>, HpkeContext), DnsError> {
+    let pk_r_key = public_key_from_bytes(pk_r)?;
+    let sk_e = StaticSecret::from(sk_e);
+    let enc = PublicKey::from(&sk_e).to_bytes().to_vec();
+    let dh = sk_e.diffie_hellman(&pk_r_key);
+    let shared_secret = kem_shared_secret(dh.as_bytes(), &enc, pk_r)?;
+    Ok((enc, HpkeContext::new(&shared_secret, info)?))
+}
+
+// SetupBaseR: the recipient's side of setup_base_s, given enc and its private key sk_r.
+pub fn setup_base_r(enc: &[u8], sk_r: &[u8; 32], info: &[u8]) -> Result<HpkeContext, DnsError> {
+    let pk_e = public_key_from_bytes(enc)?;
+    let sk_r = StaticSecret::from(*sk_r);
+    let pk_r = PublicKey::from(&sk_r).to_bytes();
+    let dh = sk_r.diffie_hellman(&pk_e);
+    let shared_secret = kem_shared_secret(dh.as_bytes(), enc, &pk_r)?;
+    HpkeContext::new(&shared_secret, info)
+}
+
+fn aead_key(key: &[u8], nonce: &[u8]) -> Result<(LessSafeKey, Nonce), DnsError> {
+    let key = match UnboundKey::new(&AES_128_GCM, key) {
+        Ok(k) => LessSafeKey::new(k),
+        Err(_) => return Err(DnsError::Crypto(format!("Got a {} byte AES-128-GCM key, expected {NK}.", key.len())))
+    };
+    let nonce = match Nonce::try_assume_unique_for_key(nonce) {
+        Ok(n) => n,
+        Err(_) => return Err(DnsError::Crypto(format!("Got a {} byte AES-128-GCM nonce, expected {NN}.", nonce.len())))
+    };
+    Ok((key, nonce))
+}
+
+// AES-128-GCM, with the tag on the end of the ciphertext.
+pub fn aead_seal(key: &[u8], nonce: &[u8], aad: &[u8], pt: &[u8]) -> Result<Vec<u8>, DnsError> {
+    let (key, nonce) = aead_key(key, nonce)?;
+    let mut buf = pt.to_vec();
+    match key.seal_in_place_append_tag(nonce, Aad::from(aad), &mut buf) {
+        Ok(()) => Ok(buf),
+        Err(_) => Err(DnsError::Crypto(String::from("AES-128-GCM encryption failed.")))
+    }
+}
+
+pub fn aead_open(key: &[u8], nonce: &[u8], aad: &[u8], ct: &[u8]) -> Result<Vec<u8>, DnsError> {
+    let (key, nonce) = aead_key(key, nonce)?;
+    let mut buf = ct.to_vec();
+    match key.open_in_place(nonce, Aad::from(aad), &mut buf) {
+        Ok(pt) => Ok(pt.to_vec()),
+        Err(_) => Err(DnsError::Crypto(String::from("AES-128-GCM decryption failed - wrong key, or the message was tampered with.")))
+    }
+}
@@ -0,0 +1,369 @@
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL;
+use super::{DnsError, DnsQuery, DnsResponse};
+use super::tls::{DnsTlsConfig, TlsStream, close_tls, connect_tls};
+use super::transport::{remaining, zero_id_query};
+
+pub const DOH_PORT: u16 = 443;
+// see rfc8484, section 6.
+pub const DOH_MEDIA_TYPE: &str = "application/dns-message";
+// what we use when the url doesn't give a path. it's what just about every public resolver uses.
+pub const DEFAULT_DOH_PATH: &str = "/dns-query";
+
+// no dns message is bigger than this, so neither is any response body we'll accept.
+const MAX_BODY_LEN: usize = u16::MAX as usize;
+// and we don't want to read forever if a server sends us endless headers.
+const MAX_HEADER_LEN: usize = 64 * 1024;
+
+/* see rfc8484, section 4.1. GET puts the query in the url (so it can be cached), POST in
+   the request body. */
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum DohMethod {
+    GET,
+    POST
+}
+
+impl fmt::Display for DohMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DohMethod::GET => write!(f, "GET"),
+            DohMethod::POST => write!(f, "POST")
+        }
+    }
+}
+
+// the parts of an https:// url we care about. host is kept without the [] an ipv6 address needs.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct DohUrl {
+    host: String,
+    port: u16,
+    path: String
+}
+
+impl DohUrl {
+    pub fn new(host: String, port: u16, path: String) -> DohUrl {
+        DohUrl { host, port, path }
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    // what goes in the Host header: the host, bracketed if it's ipv6, and the port if it isn't 443.
+    pub fn authority(&self) -> String {
+        let host = if self.host.contains(':') { format!("[{}]", self.host) } else { self.host.clone() };
+        if self.port == DOH_PORT { host } else { format!("{host}:{}", self.port) }
+    }
+
+    /* the addresses to connect to for this url. an ip address is used as is; anything else
+       has to be looked up with the system resolver, as we've no other server to ask. */
+    pub fn resolve(&self) -> Result<Vec<SocketAddr>, DnsError> {
+        if let Ok(ip) = IpAddr::from_str(&self.host) {
+            return Ok(vec![SocketAddr::new(ip, self.port)]);
+        }
+        match (self.host.as_str(), self.port).to_socket_addrs() {
+            Ok(addrs) => {
+                let ret: Vec<SocketAddr> = addrs.collect();
+                if ret.is_empty() {
+                    return Err(DnsError::Config(format!("Got no addresses for DoH server {}.", self.host)));
+                }
+                Ok(ret)
+            },
+            Err(e) => Err(DnsError::Config(format!("Couldn't look up DoH server {}: {e}", self.host)))
+        }
+    }
+}
+
+impl fmt::Display for DohUrl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "https://{}{}", self.authority(), self.path)
+    }
+}
+
+/* parse an https:// url, as given on the command line. the path (and any query string in
+   it) is kept as is; if there's no path, DEFAULT_DOH_PATH is used. */
+pub fn parse_doh_url(s: &str) -> Result<DohUrl, DnsError> {
+    let s = s.trim();
+    let rest = match s.get(.. 8) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("https://") => &s[8 ..],
+        _ => return Err(DnsError::Config(format!("DoH server '{s}' isn't an https:// url.")))
+    };
+    let rest = rest.split('#').next().unwrap_or(rest);
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(i) if rest[i ..].starts_with('/') => (&rest[.. i], rest[i ..].to_string()),
+        Some(i) => (&rest[.. i], format!("/{}", &rest[i ..])),
+        None => (rest, String::from(DEFAULT_DOH_PATH))
+    };
+    if authority.contains('@') {
+        return Err(DnsError::Config(format!("DoH server '{s}' has userinfo in it, which we don't support.")));
+    }
+
+    let (host, port) = if let Some(v6) = authority.strip_prefix('[') {
+        match v6.split_once(']') {
+            Some((host, "")) => (host, None),
+            Some((host, port)) if port.starts_with(':') => (host, Some(&port[1 ..])),
+            _ => return Err(DnsError::Config(format!("DoH server '{s}' has a badly bracketed ipv6 address.")))
+        }
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None)
+        }
+    };
+    if host.is_empty() {
+        return Err(DnsError::Config(format!("DoH server '{s}' has no host.")));
+    }
+    let port = match port {
+        None => DOH_PORT,
+        Some(p) => match p.parse::<u16>() {
+            Ok(p) => p,
+            Err(_) => return Err(DnsError::Config(format!("DoH server '{s}' has a bad port ({p}).")))
+        }
+    };
+    Ok(DohUrl::new(host.to_string(), port, path))
+}
+
+// everything the client needs to know to send a query over https, beyond the server address.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct DnsDohConfig {
+    url: DohUrl,
+    method: DohMethod
+}
+
+impl DnsDohConfig {
+    pub fn new(url: DohUrl, method: DohMethod) -> DnsDohConfig {
+        DnsDohConfig { url, method }
+    }
+
+    pub fn url(&self) -> &DohUrl {
+        &self.url
+    }
+
+    pub fn method(&self) -> DohMethod {
+        self.method
+    }
+}
+
+/* an http/1.1 request for target (a path, and maybe a query string) on authority. for POST,
+   body is sent as media_type; either way, media_type is what we'll accept back. */
+pub(crate) fn http_request(method: DohMethod, target: &str, authority: &str, media_type: &str,
+                           body: &[u8]) -> Vec<u8> {
+    let mut ret = format!("{method} {target} HTTP/1.1\r\n");
+    ret.push_str(&format!("Host: {authority}\r\n"));
+    ret.push_str(&format!("Accept: {media_type}\r\n"));
+    if method == DohMethod::POST {
+        ret.push_str(&format!("Content-Type: {media_type}\r\n"));
+        ret.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    ret.push_str("Connection: close\r\n\r\n");
+
+    let mut ret = ret.into_bytes();
+    if method == DohMethod::POST {
+        ret.extend_from_slice(body);
+    }
+    ret
+}
+
+/* the http/1.1 request carrying qbytes to url. for GET, the message goes in the dns
+   parameter of the query string, base64url encoded without padding (rfc8484, section 4.1). */
+pub fn doh_request(url: &DohUrl, method: DohMethod, qbytes: &[u8]) -> Vec<u8> {
+    let target = match method {
+        DohMethod::GET => {
+            let sep = if url.path.contains('?') { '&' } else { '?' };
+            format!("{}{sep}dns={}", url.path, BASE64URL.encode(qbytes))
+        },
+        DohMethod::POST => url.path.clone()
+    };
+    http_request(method, &target, &url.authority(), DOH_MEDIA_TYPE, qbytes)
+}
+
+// read one CRLF (or bare LF) terminated line, without its line ending.
+fn read_http_line<R: BufRead>(r: &mut R, what: &str) -> Result<String, DnsError> {
+    let mut line: Vec<u8> = Vec::new();
+    match r.take(MAX_HEADER_LEN as u64).read_until(b'\n', &mut line) {
+        Ok(0) => return Err(DnsError::Transport(format!("Connection closed reading HTTP {what}."))),
+        Ok(_) if !line.ends_with(b"\n") => return Err(DnsError::Transport(format!("Got an overlong or unterminated HTTP {what}."))),
+        Ok(_) => {},
+        Err(e) => return Err(DnsError::io(format!("Error reading HTTP {what}"), &e))
+    }
+    while line.ends_with(b"\n") || line.ends_with(b"\r") {
+        line.pop();
+    }
+    match String::from_utf8(line) {
+        Ok(l) => Ok(l),
+        Err(_) => Err(DnsError::Transport(format!("Got an HTTP {what} that isn't valid UTF-8.")))
+    }
+}
+
+fn read_http_bytes<R: BufRead>(r: &mut R, len: usize, body: &mut Vec<u8>) -> Result<(), DnsError> {
+    if body.len() + len > MAX_BODY_LEN {
+        return Err(DnsError::Transport(format!("HTTP response body is over {MAX_BODY_LEN} bytes.")));
+    }
+    let start = body.len();
+    body.resize(start + len, 0);
+    match r.read_exact(&mut body[start ..]) {
+        Ok(()) => Ok(()),
+        Err(e) => Err(DnsError::io("Error reading HTTP response body", &e))
+    }
+}
+
+/* read an http/1.1 response, returning its status code, its Content-Type (if it had one)
+   and its body. the body may be sent with a Content-Length, chunked (rfc9112, section 7.1),
+   or just run until the connection is closed. */
+pub fn read_http_response<R: BufRead>(r: &mut R) -> Result<(u16, Option<String>, Vec<u8>), DnsError> {
+    let status_line = read_http_line(r, "status line")?;
+    let mut parts = status_line.splitn(3, ' ');
+    let status = match (parts.next(), parts.next()) {
+        (Some(version), Some(code)) if version.starts_with("HTTP/1.") => match code.parse::<u16>() {
+            Ok(c) => c,
+            Err(_) => return Err(DnsError::Transport(format!("Got a bad HTTP status line: {status_line}")))
+        },
+        _ => return Err(DnsError::Transport(format!("Got a bad HTTP status line: {status_line}")))
+    };
+
+    let mut content_type: Option<String> = None;
+    let mut content_length: Option<usize> = None;
+    let mut chunked = false;
+    let mut header_len = 0;
+    loop {
+        let line = read_http_line(r, "header")?;
+        if line.is_empty() {
+            break;
+        }
+        header_len += line.len();
+        if header_len > MAX_HEADER_LEN {
+            return Err(DnsError::Transport(format!("HTTP response headers are over {MAX_HEADER_LEN} bytes.")));
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(DnsError::Transport(format!("Got a bad HTTP header: {line}")));
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-type" => content_type = Some(value.to_string()),
+            "content-length" => match value.parse::<usize>() {
+                Ok(l) => content_length = Some(l),
+                Err(_) => return Err(DnsError::Transport(format!("Got a bad Content-Length: {value}")))
+            },
+            "transfer-encoding" => chunked = value.to_ascii_lowercase().split(',').any(|c| c.trim() == "chunked"),
+            _ => {}
+        }
+    }
+
+    let mut body: Vec<u8> = Vec::new();
+    if chunked {
+        loop {
+            let line = read_http_line(r, "chunk size")?;
+            let size = line.split(';').next().unwrap_or("").trim();
+            let size = match usize::from_str_radix(size, 16) {
+                Ok(s) => s,
+                Err(_) => return Err(DnsError::Transport(format!("Got a bad HTTP chunk size: {line}")))
+            };
+            if size == 0 {
+                // skip any trailers, up to the blank line that ends the message.
+                while !read_http_line(r, "trailer")?.is_empty() {}
+                break;
+            }
+            read_http_bytes(r, size, &mut body)?;
+            if !read_http_line(r, "chunk end")?.is_empty() {
+                return Err(DnsError::Transport(String::from("HTTP chunk ran past its size.")));
+            }
+        }
+    } else if let Some(len) = content_length {
+        read_http_bytes(r, len, &mut body)?;
+    } else if let Err(e) = r.take(MAX_BODY_LEN as u64 + 1).read_to_end(&mut body) {
+        return Err(DnsError::io("Error reading HTTP response body", &e));
+    } else if body.len() > MAX_BODY_LEN {
+        return Err(DnsError::Transport(format!("HTTP response body is over {MAX_BODY_LEN} bytes.")));
+    }
+
+    Ok((status, content_type, body))
+}
+
+// a tls stream that won't let any one read run past deadline.
+struct DeadlineReader<'a> {
+    stream: &'a mut TlsStream,
+    sock: &'a TcpStream,
+    deadline: Instant,
+    timeout: Duration
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let left = remaining(self.deadline, self.timeout)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::TimedOut, e))?;
+        self.sock.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+/* send an http request to server, for url, and read back the status, Content-Type and body
+   of the response. the server's certificate is checked as tls says to - and if that doesn't
+   name the server, it's checked against the host in the url. see rfc8484, section 4. */
+pub(crate) fn https_request(server: &SocketAddr, url: &DohUrl, request: &[u8], tls: &DnsTlsConfig,
+                            timeout: Duration) -> Result<(u16, Option<String>, Vec<u8>), DnsError> {
+    let mut tls = tls.clone();
+    if tls.auth_name().is_none() && IpAddr::from_str(url.host()).is_err() {
+        tls.set_auth_name(Some(url.host().to_string()));
+    }
+
+    let deadline = Instant::now() + timeout;
+    let (mut stream, sock) = connect_tls(server, &tls, &[b"http/1.1"], deadline, timeout)?;
+    if let Err(e) = stream.write_all(request).and_then(|_| stream.flush()) {
+        return Err(DnsError::io(format!("Error writing HTTP request to {server}"), &e));
+    }
+
+    let mut reader = BufReader::new(DeadlineReader { stream: &mut stream, sock: &sock, deadline, timeout });
+    let result = read_http_response(&mut reader);
+    drop(reader);
+    close_tls(&mut stream);
+    result
+}
+
+// make sure url answered a request with a 200, and a body of media_type.
+pub(crate) fn check_http_answer(url: &DohUrl, status: u16, content_type: Option<&str>,
+                                media_type: &str) -> Result<(), DnsError> {
+    if status != 200 {
+        return Err(DnsError::Transport(format!("{url} answered with HTTP status {status}.")));
+    }
+    let got = content_type.and_then(|c| c.split(';').next()).unwrap_or("").trim();
+    if !got.eq_ignore_ascii_case(media_type) {
+        return Err(DnsError::Transport(format!("{url} answered with Content-Type '{}', expected '{media_type}'.",
+                                               content_type.unwrap_or_default())));
+    }
+    Ok(())
+}
+
+// send the query bytes to server in an https request, and return the body of the response.
+pub fn https_exchange(server: &SocketAddr, qbytes: &[u8], doh: &DnsDohConfig, tls: &DnsTlsConfig,
+                      timeout: Duration) -> Result<Vec<u8>, DnsError> {
+    let request = doh_request(&doh.url, doh.method, qbytes);
+    let (status, content_type, body) = https_request(server, &doh.url, &request, tls, timeout)?;
+    check_http_answer(&doh.url, status, content_type.as_deref(), DOH_MEDIA_TYPE)?;
+    Ok(body)
+}
+
+/* send query to server over https. the id is set to 0 on the way out, as rfc8484,
+   section 4.1 says it should be, so the response is checked against that. */
+pub fn send_query(server: &SocketAddr, query: &DnsQuery, doh: &DnsDohConfig, tls: &DnsTlsConfig,
+                  timeout: Duration) -> Result<DnsResponse, DnsError> {
+    let (qbytes, query) = zero_id_query(query)?;
+
+    let rbytes = https_exchange(server, &qbytes, doh, tls, timeout)?;
+    if let Err(e) = query.check_response(&rbytes) {
+        return Err(DnsError::Transport(format!("Got a bad response from {}: {e}", doh.url)));
+    }
+    DnsResponse::from_bytes(&rbytes, 0)
+}
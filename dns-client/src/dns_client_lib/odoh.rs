@@ -0,0 +1,423 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+use rand::Rng;
+use super::{DnsError, DnsQuery, DnsResponse};
+use super::hpke;
+use super::hpke::{HpkeContext, AEAD_AES_128_GCM, KDF_HKDF_SHA256, KEM_X25519_HKDF_SHA256, NENC, NH, NK, NN};
+use super::https::{DohMethod, DohUrl, check_http_answer, http_request, https_request};
+use super::tls::DnsTlsConfig;
+use super::transport::zero_id_query;
+
+/* oblivious doh (rfc9230). the query is hpke encrypted to a key the target resolver
+   publishes, and posted to a proxy, which passes it on to the target. the proxy sees who's
+   asking but not what; the target sees what's asked but not by whom. */
+
+// see rfc9230, sections 6.1, 6.2 and 8.
+pub const ODOH_VERSION: u16 = 0x0001;
+pub const ODOH_MEDIA_TYPE: &str = "application/oblivious-dns-message";
+pub const ODOH_CONFIGS_PATH: &str = "/.well-known/odohconfigs";
+pub const ODOH_QUERY: u8 = 0x01;
+pub const ODOH_RESPONSE: u8 = 0x02;
+
+// messages are padded up to a multiple of these, as rfc8467, section 4.1 recommends for edns.
+const QUERY_PADDING_BLOCK: usize = 128;
+const RESPONSE_PADDING_BLOCK: usize = 468;
+// the response nonce is max(Nn, Nk) bytes. rfc9230, section 6.4.
+const RESPONSE_NONCE_LEN: usize = if NN > NK { NN } else { NK };
+
+// append a vector with a two byte length, as the tls presentation language has it.
+fn push_vec(buf: &mut Vec<u8>, v: &[u8], what: &'static str) -> Result<(), DnsError> {
+    if v.len() > u16::MAX as usize {
+        return Err(DnsError::Oversize { context: what, len: v.len(), max: u16::MAX as usize });
+    }
+    buf.extend_from_slice(&(v.len() as u16).to_be_bytes());
+    buf.extend_from_slice(v);
+    Ok(())
+}
+
+fn read_u16(buf: &[u8], offset: usize, what: &'static str) -> Result<u16, DnsError> {
+    match buf.get(offset .. offset + 2) {
+        Some(b) => Ok(u16::from_be_bytes([b[0], b[1]])),
+        None => Err(DnsError::Truncated { context: what, offset, needed: 2, len: buf.len() })
+    }
+}
+
+// read a vector with a two byte length at offset, returning it and the offset just past it.
+fn read_vec<'a>(buf: &'a [u8], offset: usize, what: &'static str) -> Result<(&'a [u8], usize), DnsError> {
+    let len = read_u16(buf, offset, what)? as usize;
+    match buf.get(offset + 2 .. offset + 2 + len) {
+        Some(v) => Ok((v, offset + 2 + len)),
+        None => Err(DnsError::Truncated { context: what, offset: offset + 2, needed: len, len: buf.len() })
+    }
+}
+
+/* a key the target will take queries encrypted to, and the hpke algorithms to use with it.
+   it's the ObliviousDoHConfigContents of rfc9230, section 6.2. */
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ObliviousDoHConfig {
+    kem_id: u16,
+    kdf_id: u16,
+    aead_id: u16,
+    public_key: Vec<u8>
+}
+
+impl ObliviousDoHConfig {
+    pub fn new(kem_id: u16, kdf_id: u16, aead_id: u16, public_key: Vec<u8>) -> ObliviousDoHConfig {
+        ObliviousDoHConfig { kem_id, kdf_id, aead_id, public_key }
+    }
+
+    pub fn kem_id(&self) -> u16 {
+        self.kem_id
+    }
+
+    pub fn kdf_id(&self) -> u16 {
+        self.kdf_id
+    }
+
+    pub fn aead_id(&self) -> u16 {
+        self.aead_id
+    }
+
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    // whether this is the one suite our hpke knows.
+    pub fn is_supported(&self) -> bool {
+        self.kem_id == KEM_X25519_HKDF_SHA256 && self.kdf_id == KDF_HKDF_SHA256 &&
+            self.aead_id == AEAD_AES_128_GCM && self.public_key.len() == NENC
+    }
+
+    fn contents_to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+        let mut ret: Vec<u8> = Vec::new();
+        ret.extend_from_slice(&self.kem_id.to_be_bytes());
+        ret.extend_from_slice(&self.kdf_id.to_be_bytes());
+        ret.extend_from_slice(&self.aead_id.to_be_bytes());
+        push_vec(&mut ret, &self.public_key, "ODoH public key")?;
+        Ok(ret)
+    }
+
+    // the config as it goes in ObliviousDoHConfigs: version, length, then the contents.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+        let mut ret: Vec<u8> = Vec::new();
+        ret.extend_from_slice(&ODOH_VERSION.to_be_bytes());
+        push_vec(&mut ret, &self.contents_to_bytes()?, "ODoH config")?;
+        Ok(ret)
+    }
+
+    // the id queries carry to say which key they're for. rfc9230, section 6.2.
+    pub fn key_id(&self) -> Result<Vec<u8>, DnsError> {
+        let prk = hpke::extract(b"", &self.contents_to_bytes()?);
+        hpke::expand(&prk, b"odoh key id", NH)
+    }
+}
+
+/* parse an ObliviousDoHConfigs, as served from ODOH_CONFIGS_PATH. configs of a version other
+   than ODOH_VERSION are skipped, as rfc9230, section 6.1 says to. */
+pub fn parse_odoh_configs(buf: &[u8]) -> Result<Vec<ObliviousDoHConfig>, DnsError> {
+    let (configs, end) = read_vec(buf, 0, "ODoH configs")?;
+    if end != buf.len() {
+        return Err(DnsError::Crypto(format!("Got {} bytes after the end of the ODoH configs.", buf.len() - end)));
+    }
+
+    let mut ret: Vec<ObliviousDoHConfig> = Vec::new();
+    let mut offset = 0;
+    while offset < configs.len() {
+        let version = read_u16(configs, offset, "ODoH config version")?;
+        let (contents, next) = read_vec(configs, offset + 2, "ODoH config")?;
+        offset = next;
+        if version != ODOH_VERSION {
+            continue;
+        }
+
+        let kem_id = read_u16(contents, 0, "ODoH KEM id")?;
+        let kdf_id = read_u16(contents, 2, "ODoH KDF id")?;
+        let aead_id = read_u16(contents, 4, "ODoH AEAD id")?;
+        let (public_key, end) = read_vec(contents, 6, "ODoH public key")?;
+        if end != contents.len() {
+            return Err(DnsError::Crypto(format!("Got {} bytes after the end of an ODoH config.", contents.len() - end)));
+        }
+        ret.push(ObliviousDoHConfig::new(kem_id, kdf_id, aead_id, public_key.to_vec()));
+    }
+    Ok(ret)
+}
+
+pub fn odoh_configs_to_bytes(configs: &[ObliviousDoHConfig]) -> Result<Vec<u8>, DnsError> {
+    let mut all: Vec<u8> = Vec::new();
+    for config in configs {
+        all.extend_from_slice(&config.to_bytes()?);
+    }
+    let mut ret: Vec<u8> = Vec::new();
+    push_vec(&mut ret, &all, "ODoH configs")?;
+    Ok(ret)
+}
+
+// the first config we can use. rfc9230, section 6.1 has the target list them in order of preference.
+pub fn select_odoh_config(configs: &[ObliviousDoHConfig]) -> Result<ObliviousDoHConfig, DnsError> {
+    match configs.iter().find(|c| c.is_supported()) {
+        Some(c) => Ok(c.clone()),
+        None => Err(DnsError::Crypto(String::from("None of the target's ODoH configs use a KEM, KDF and AEAD we support.")))
+    }
+}
+
+/* what actually gets sent to and from the target. for a query, key_id is the key_id of the
+   config it was encrypted to; for a response, it's the response nonce. rfc9230, section 6.1. */
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ObliviousDoHMessage {
+    message_type: u8,
+    key_id: Vec<u8>,
+    encrypted_message: Vec<u8>
+}
+
+impl ObliviousDoHMessage {
+    pub fn new(message_type: u8, key_id: Vec<u8>, encrypted_message: Vec<u8>) -> ObliviousDoHMessage {
+        ObliviousDoHMessage { message_type, key_id, encrypted_message }
+    }
+
+    pub fn message_type(&self) -> u8 {
+        self.message_type
+    }
+
+    pub fn key_id(&self) -> &[u8] {
+        &self.key_id
+    }
+
+    pub fn encrypted_message(&self) -> &[u8] {
+        &self.encrypted_message
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+        let mut ret = vec![self.message_type];
+        push_vec(&mut ret, &self.key_id, "ODoH key id")?;
+        push_vec(&mut ret, &self.encrypted_message, "ODoH encrypted message")?;
+        Ok(ret)
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<ObliviousDoHMessage, DnsError> {
+        let Some(message_type) = buf.first() else {
+            return Err(DnsError::Crypto(String::from("Got an empty ODoH message.")));
+        };
+        let (key_id, offset) = read_vec(buf, 1, "ODoH key id")?;
+        let (encrypted_message, end) = read_vec(buf, offset, "ODoH encrypted message")?;
+        if end != buf.len() {
+            return Err(DnsError::Crypto(format!("Got {} bytes after the end of an ODoH message.", buf.len() - end)));
+        }
+        Ok(ObliviousDoHMessage::new(*message_type, key_id.to_vec(), encrypted_message.to_vec()))
+    }
+
+    // the additional data the message's encryption covers. rfc9230, sections 6.3 and 6.4.
+    fn aad(&self) -> Result<Vec<u8>, DnsError> {
+        let mut ret = vec![self.message_type];
+        push_vec(&mut ret, &self.key_id, "ODoH key id")?;
+        Ok(ret)
+    }
+}
+
+/* the plaintext that gets encrypted: the dns message, then zero padding, each with a two
+   byte length. the padding brings the whole thing up to a multiple of block. */
+fn pad_message(dns_message: &[u8], block: usize) -> Result<Vec<u8>, DnsError> {
+    let len = dns_message.len() + 4;
+    let padding = (block - len % block) % block;
+    let mut ret: Vec<u8> = Vec::new();
+    push_vec(&mut ret, dns_message, "ODoH dns message")?;
+    push_vec(&mut ret, &vec![0u8; padding], "padding")?;
+    Ok(ret)
+}
+
+fn unpad_message(buf: &[u8]) -> Result<Vec<u8>, DnsError> {
+    let (dns_message, offset) = read_vec(buf, 0, "ODoH dns message")?;
+    let (padding, end) = read_vec(buf, offset, "ODoH padding")?;
+    if end != buf.len() {
+        return Err(DnsError::Crypto(format!("Got {} bytes after the padding of an ODoH message.", buf.len() - end)));
+    }
+    // rfc9230, section 7 says to reject padding that isn't all zeros.
+    if padding.iter().any(|b| *b != 0) {
+        return Err(DnsError::Crypto(String::from("Got an ODoH message with non-zero padding.")));
+    }
+    Ok(dns_message.to_vec())
+}
+
+/* what's left over from encrypting a query, for dealing with its response: the hpke context,
+   and the padded query, which goes into the response key derivation. */
+#[derive(Debug, Clone)]
+pub struct ObliviousDoHContext {
+    hpke: HpkeContext,
+    query_plain: Vec<u8>
+}
+
+impl ObliviousDoHContext {
+    // the key and nonce for a response with response_nonce. rfc9230, section 6.4.
+    fn response_key_nonce(&self, response_nonce: &[u8]) -> Result<(Vec<u8>, Vec<u8>), DnsError> {
+        let secret = self.hpke.export(b"odoh response", NK)?;
+        let mut salt = self.query_plain.clone();
+        push_vec(&mut salt, response_nonce, "ODoH response nonce")?;
+        let prk = hpke::extract(&salt, &secret);
+        Ok((hpke::expand(&prk, b"odoh key", NK)?, hpke::expand(&prk, b"odoh nonce", NN)?))
+    }
+
+    // decrypt the response to our query, returning the dns message in it.
+    pub fn decrypt_response(&self, message: &ObliviousDoHMessage) -> Result<Vec<u8>, DnsError> {
+        if message.message_type != ODOH_RESPONSE {
+            return Err(DnsError::Crypto(format!("Got an ODoH message of type {} where a response was expected.",
+                                                message.message_type)));
+        }
+        let (key, nonce) = self.response_key_nonce(&message.key_id)?;
+        let plain = hpke::aead_open(&key, &nonce, &message.aad()?, &message.encrypted_message)?;
+        unpad_message(&plain)
+    }
+
+    // the target's side: encrypt rbytes as the response to the query this context came from.
+    pub fn encrypt_response(&self, rbytes: &[u8]) -> Result<ObliviousDoHMessage, DnsError> {
+        let response_nonce: [u8; RESPONSE_NONCE_LEN] = rand::thread_rng().gen();
+        let (key, nonce) = self.response_key_nonce(&response_nonce)?;
+        let mut ret = ObliviousDoHMessage::new(ODOH_RESPONSE, response_nonce.to_vec(), vec![]);
+        ret.encrypted_message = hpke::aead_seal(&key, &nonce, &ret.aad()?,
+                                                &pad_message(rbytes, RESPONSE_PADDING_BLOCK)?)?;
+        Ok(ret)
+    }
+}
+
+/* encrypt the query bytes to config, with a fresh ephemeral key. returns the message to send,
+   and the context to decrypt its response with. rfc9230, section 6.3. */
+pub fn encrypt_query(config: &ObliviousDoHConfig, qbytes: &[u8]) -> Result<(ObliviousDoHMessage, ObliviousDoHContext), DnsError> {
+    if !config.is_supported() {
+        return Err(DnsError::Crypto(String::from("Can't encrypt a query with an ODoH config we don't support.")));
+    }
+    let (enc, context) = hpke::setup_base_s(&config.public_key, b"odoh query", rand::thread_rng().gen())?;
+    let query_plain = pad_message(qbytes, QUERY_PADDING_BLOCK)?;
+
+    let mut message = ObliviousDoHMessage::new(ODOH_QUERY, config.key_id()?, enc);
+    let ct = context.seal(&message.aad()?, &query_plain)?;
+    message.encrypted_message.extend_from_slice(&ct);
+    Ok((message, ObliviousDoHContext { hpke: context, query_plain }))
+}
+
+/* a target's key, for the target side of things - we don't run a target, but it's what the
+   tests check the client against. */
+#[derive(Clone)]
+pub struct ObliviousDoHKeyPair {
+    secret: [u8; 32],
+    config: ObliviousDoHConfig
+}
+
+impl ObliviousDoHKeyPair {
+    pub fn new(secret: [u8; 32]) -> ObliviousDoHKeyPair {
+        let config = ObliviousDoHConfig::new(KEM_X25519_HKDF_SHA256, KDF_HKDF_SHA256, AEAD_AES_128_GCM,
+                                             hpke::public_key(&secret).to_vec());
+        ObliviousDoHKeyPair { secret, config }
+    }
+
+    pub fn generate() -> ObliviousDoHKeyPair {
+        ObliviousDoHKeyPair::new(rand::thread_rng().gen())
+    }
+
+    pub fn config(&self) -> &ObliviousDoHConfig {
+        &self.config
+    }
+
+    // decrypt a query, returning the dns message in it and the context to encrypt the response with.
+    pub fn decrypt_query(&self, message: &ObliviousDoHMessage) -> Result<(Vec<u8>, ObliviousDoHContext), DnsError> {
+        if message.message_type != ODOH_QUERY {
+            return Err(DnsError::Crypto(format!("Got an ODoH message of type {} where a query was expected.",
+                                                message.message_type)));
+        }
+        if message.key_id != self.config.key_id()? {
+            return Err(DnsError::Crypto(String::from("Got an ODoH query for a key id that isn't ours.")));
+        }
+        if message.encrypted_message.len() < NENC {
+            return Err(DnsError::Crypto(format!("Got an ODoH query of {} bytes, too short to hold a key.",
+                                                message.encrypted_message.len())));
+        }
+        let (enc, ct) = message.encrypted_message.split_at(NENC);
+        let context = hpke::setup_base_r(enc, &self.secret, b"odoh query")?;
+        let query_plain = context.open(&message.aad()?, ct)?;
+        let qbytes = unpad_message(&query_plain)?;
+        Ok((qbytes, ObliviousDoHContext { hpke: context, query_plain }))
+    }
+}
+
+// everything the client needs to send a query through a proxy, beyond the proxy's address.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct DnsOdohConfig {
+    proxy: DohUrl,
+    target: DohUrl,
+    target_config: ObliviousDoHConfig
+}
+
+impl DnsOdohConfig {
+    pub fn new(proxy: DohUrl, target: DohUrl, target_config: ObliviousDoHConfig) -> DnsOdohConfig {
+        DnsOdohConfig { proxy, target, target_config }
+    }
+
+    pub fn proxy(&self) -> &DohUrl {
+        &self.proxy
+    }
+
+    pub fn target(&self) -> &DohUrl {
+        &self.target
+    }
+
+    pub fn target_config(&self) -> &ObliviousDoHConfig {
+        &self.target_config
+    }
+}
+
+// percent-encode s for a query string, leaving the unreserved characters (rfc3986) and '/' be.
+fn percent_encode(s: &str) -> String {
+    let mut ret = String::new();
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/".contains(&b) {
+            ret.push(b as char);
+        } else {
+            ret.push_str(&format!("%{b:02X}"));
+        }
+    }
+    ret
+}
+
+/* the path and query string to post to on proxy, telling it to pass the query on to target.
+   see rfc9230, section 4.1. */
+pub fn odoh_proxy_path(proxy: &DohUrl, target: &DohUrl) -> String {
+    let sep = if proxy.path().contains('?') { '&' } else { '?' };
+    format!("{}{sep}targethost={}&targetpath={}", proxy.path(), percent_encode(&target.authority()),
+            percent_encode(target.path()))
+}
+
+/* get the configs target publishes, from server (an address of the target). this goes
+   straight to the target, so it sees our address - don't do it just before a query, or the
+   target can tie the two together. fetch once and keep them (odoh_configs_to_bytes) instead.
+   only the CA certificates in tls are used; the target is checked against the host in its url. */
+pub fn fetch_odoh_configs(server: &SocketAddr, target: &DohUrl, tls: &DnsTlsConfig,
+                          timeout: Duration) -> Result<Vec<ObliviousDoHConfig>, DnsError> {
+    let mut tls = tls.clone();
+    tls.set_auth_name(None);
+    tls.set_spki_pins(vec![]);
+
+    let url = DohUrl::new(target.host().to_string(), target.port(), String::from(ODOH_CONFIGS_PATH));
+    let request = http_request(DohMethod::GET, url.path(), &url.authority(), "application/octet-stream", &[]);
+    let (status, _, body) = https_request(server, &url, &request, &tls, timeout)?;
+    if status != 200 {
+        return Err(DnsError::Transport(format!("{url} answered with HTTP status {status}.")));
+    }
+    parse_odoh_configs(&body)
+}
+
+/* send query to server (an address of the proxy) over oblivious doh. as with doh, the id
+   is set to 0 on the way out, so the response is checked against that. */
+pub fn send_query(server: &SocketAddr, query: &DnsQuery, odoh: &DnsOdohConfig, tls: &DnsTlsConfig,
+                  timeout: Duration) -> Result<DnsResponse, DnsError> {
+    let (qbytes, query) = zero_id_query(query)?;
+    let (message, context) = encrypt_query(&odoh.target_config, &qbytes)?;
+
+    let path = odoh_proxy_path(&odoh.proxy, &odoh.target);
+    let request = http_request(DohMethod::POST, &path, &odoh.proxy.authority(), ODOH_MEDIA_TYPE,
+                               &message.to_bytes()?);
+    let (status, content_type, body) = https_request(server, &odoh.proxy, &request, tls, timeout)?;
+    check_http_answer(&odoh.proxy, status, content_type.as_deref(), ODOH_MEDIA_TYPE)?;
+
+    let rbytes = context.decrypt_response(&ObliviousDoHMessage::from_bytes(&body)?)?;
+    if let Err(e) = query.check_response(&rbytes) {
+        return Err(DnsError::Transport(format!("Got a bad response from {} via {}: {e}", odoh.target, odoh.proxy)));
+    }
+    DnsResponse::from_bytes(&rbytes, 0)
+}
@@ -0,0 +1,110 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use quinn::{ClientConfig, Endpoint, VarInt};
+use quinn::crypto::rustls::QuicClientConfig;
+use super::{DnsError, DnsQuery, DnsResponse};
+use super::tls::DnsTlsConfig;
+use super::transport::{local_bind_addr, tcp_frame, zero_id_query};
+
+// see rfc9250, sections 4.1.1 and 4.1.
+pub const DOQ_PORT: u16 = 853;
+pub const DOQ_ALPN: &[u8] = b"doq";
+// the application error code for closing a connection with nothing wrong. rfc9250, section 4.3.
+pub const DOQ_NO_ERROR: u32 = 0;
+
+// how long to give the endpoint to send our close once we're done.
+const CLOSE_TIMEOUT: Duration = Duration::from_millis(100);
+
+// the most we'll read off of a stream: a two byte length, and that many bytes.
+const MAX_STREAM_LEN: usize = 2 + u16::MAX as usize;
+
+/* each doq stream carries exactly one message, with the same two byte length prefix as
+   tcp (rfc9250, section 4.2). strip it, making sure it accounts for the whole stream. */
+pub fn unframe_doq_message(buf: &[u8]) -> Result<Vec<u8>, DnsError> {
+    if buf.len() < 2 {
+        return Err(DnsError::Transport(format!("Got a {} byte QUIC stream, too short for a length prefix.", buf.len())));
+    }
+    let len = u16::from_be_bytes([buf[0], buf[1]]) as usize;
+    if len != buf.len() - 2 {
+        return Err(DnsError::Transport(format!("Got a QUIC stream with a length prefix of {len}, but {} bytes of message.",
+                                               buf.len() - 2)));
+    }
+    Ok(buf[2 ..].to_vec())
+}
+
+async fn doq_exchange(server: &SocketAddr, qbytes: &[u8], config: &DnsTlsConfig) -> Result<Vec<u8>, DnsError> {
+    let crypto = match QuicClientConfig::try_from(config.client_config(&[DOQ_ALPN])?) {
+        Ok(c) => c,
+        Err(e) => return Err(DnsError::Transport(format!("Couldn't set up TLS for QUIC: {e}")))
+    };
+    let local = local_bind_addr(server);
+    let mut endpoint = match Endpoint::client(local) {
+        Ok(e) => e,
+        Err(e) => return Err(DnsError::io(format!("Couldn't bind QUIC endpoint to {local}"), &e))
+    };
+    endpoint.set_default_client_config(ClientConfig::new(Arc::new(crypto)));
+
+    let server_name = config.server_name_string(server)?;
+    let connecting = match endpoint.connect(*server, &server_name) {
+        Ok(c) => c,
+        Err(e) => return Err(DnsError::Transport(format!("Couldn't connect to {server} over QUIC: {e}")))
+    };
+    let conn = match connecting.await {
+        Ok(c) => c,
+        Err(e) => return Err(DnsError::Transport(format!("QUIC handshake with {server} failed: {e}")))
+    };
+
+    // one bidirectional stream per query, and we have to close our side once it's sent.
+    let result = async {
+        let (mut send, mut recv) = match conn.open_bi().await {
+            Ok(s) => s,
+            Err(e) => return Err(DnsError::Transport(format!("Couldn't open QUIC stream to {server}: {e}")))
+        };
+        if let Err(e) = send.write_all(&tcp_frame(qbytes)?).await {
+            return Err(DnsError::Transport(format!("Error writing query to QUIC stream: {e}")));
+        }
+        if let Err(e) = send.finish() {
+            return Err(DnsError::Transport(format!("Error finishing QUIC stream: {e}")));
+        }
+        match recv.read_to_end(MAX_STREAM_LEN).await {
+            Ok(buf) => unframe_doq_message(&buf),
+            Err(e) => Err(DnsError::Transport(format!("Error reading response from QUIC stream: {e}")))
+        }
+    }.await;
+
+    /* wait for the CONNECTION_CLOSE to actually go out before the runtime goes away, but
+       not for the whole draining period (rfc9000, section 10.2) - we don't need it. */
+    conn.close(VarInt::from_u32(DOQ_NO_ERROR), b"");
+    let _ = tokio::time::timeout(CLOSE_TIMEOUT, endpoint.wait_idle()).await;
+    result
+}
+
+/* send the query bytes to server over quic, and return the bytes of the (unframed)
+   response. the server's certificate is checked just as for tls. quinn needs an async
+   runtime, so each exchange gets a small one of its own. */
+pub fn quic_exchange(server: &SocketAddr, qbytes: &[u8], config: &DnsTlsConfig,
+                     timeout: Duration) -> Result<Vec<u8>, DnsError> {
+    let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(r) => r,
+        Err(e) => return Err(DnsError::io("Couldn't start runtime for QUIC", &e))
+    };
+    runtime.block_on(async {
+        match tokio::time::timeout(timeout, doq_exchange(server, qbytes, config)).await {
+            Ok(r) => r,
+            Err(_) => Err(DnsError::Timeout(timeout))
+        }
+    })
+}
+
+/* send query to server over quic. the id is set to 0 on the way out, as rfc9250,
+   section 4.2.1 says it must be, so the response is checked against that. */
+pub fn send_query(server: &SocketAddr, query: &DnsQuery, config: &DnsTlsConfig,
+                  timeout: Duration) -> Result<DnsResponse, DnsError> {
+    let (qbytes, query) = zero_id_query(query)?;
+    let rbytes = quic_exchange(server, &qbytes, config, timeout)?;
+    if let Err(e) = query.check_response(&rbytes) {
+        return Err(DnsError::Transport(format!("Got a bad response from {server}: {e}")));
+    }
+    DnsResponse::from_bytes(&rbytes, 0)
+}
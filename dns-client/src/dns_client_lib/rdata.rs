@@ -0,0 +1,143 @@
+/* the RData trait every record type implements, and the registry that lets types from
+   outside this library be parsed too. the types this library has itself are listed once, in
+   the rdata_types! invocation in lib.rs; anything else can be added at runtime with
+   register_rdata, and comes back from parsing as DnsResourceRecordEnum::Registered. */
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{OnceLock, RwLock};
+use super::*;
+
+pub trait RData: fmt::Debug + fmt::Display + Eq + Send + Sync + Sized + 'static {
+    // the type code (rfc1035 section 3.2.2, and the iana registry), and its mnemonic.
+    const TYPE: u16;
+    const MNEMONIC: &'static str;
+
+    /* parse the rdlen bytes of rdata at offset. buf is the whole message, so compressed
+       names in the rdata can be followed. */
+    fn parse(buf: &[u8], offset: usize, rdlen: usize) -> Result<Self, DnsError>;
+
+    /* write the rdata into a message. names in it should only be compressed for the types
+       rfc1035 defined - see rfc3597, section 4. */
+    fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError>;
+}
+
+// the object safe side of RData, so records of any type can be held and written the same way.
+pub trait AnyRData: fmt::Debug + fmt::Display + Send + Sync {
+    fn rtype(&self) -> u16;
+    fn mnemonic(&self) -> &'static str;
+    fn write_rdata(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError>;
+    fn as_any(&self) -> &dyn Any;
+    fn rdata_eq(&self, other: &dyn AnyRData) -> bool;
+}
+
+impl<T: RData> AnyRData for T {
+    fn rtype(&self) -> u16 {
+        T::TYPE
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        T::MNEMONIC
+    }
+
+    fn write_rdata(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+        <T as RData>::write(self, w)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn rdata_eq(&self, other: &dyn AnyRData) -> bool {
+        other.as_any().downcast_ref::<T>() == Some(self)
+    }
+}
+
+// the generic record's type is whatever it was parsed with, so it can't be an RData.
+impl AnyRData for DnsGenericRecord {
+    fn rtype(&self) -> u16 {
+        self.qtype
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        "Generic"
+    }
+
+    fn write_rdata(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+        w.write_bytes(&self.v);
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn rdata_eq(&self, other: &dyn AnyRData) -> bool {
+        other.as_any().downcast_ref::<DnsGenericRecord>() == Some(self)
+    }
+}
+
+impl dyn AnyRData + '_ {
+    // the record as its own type, if it's a T.
+    pub fn downcast_ref<T: AnyRData + 'static>(&self) -> Option<&T> {
+        self.as_any().downcast_ref::<T>()
+    }
+}
+
+impl PartialEq for dyn AnyRData + '_ {
+    fn eq(&self, other: &dyn AnyRData) -> bool {
+        self.rdata_eq(other)
+    }
+}
+
+impl Eq for dyn AnyRData + '_ {}
+
+type RDataParser = fn(&[u8], usize, usize) -> Result<Box<dyn AnyRData>, DnsError>;
+
+struct RegisteredType {
+    mnemonic: &'static str,
+    parse: RDataParser
+}
+
+fn registry() -> &'static RwLock<HashMap<u16, RegisteredType>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<u16, RegisteredType>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+fn parse_boxed<T: RData>(buf: &[u8], offset: usize, rdlen: usize) -> Result<Box<dyn AnyRData>, DnsError> {
+    Ok(Box::new(T::parse(buf, offset, rdlen)?))
+}
+
+/* have records of type T::TYPE parsed as T from now on, for the whole process. types this
+   library handles itself can't be replaced, and a type can only be registered once. */
+pub fn register_rdata<T: RData>() -> Result<(), DnsError> {
+    if is_builtin_rdata(T::TYPE) {
+        return Err(DnsError::Config(format!("Record type {} is already handled by this library.", T::TYPE)));
+    }
+    let mut registry = registry().write().unwrap_or_else(|e| e.into_inner());
+    if let Some(existing) = registry.get(&T::TYPE) {
+        return Err(DnsError::Config(format!("Record type {} is already registered, as {}.",
+                                            T::TYPE, existing.mnemonic)));
+    }
+    registry.insert(T::TYPE, RegisteredType { mnemonic: T::MNEMONIC, parse: parse_boxed::<T> });
+    Ok(())
+}
+
+pub(crate) fn registered_parser(rtype: u16) -> Option<RDataParser> {
+    let registry = registry().read().unwrap_or_else(|e| e.into_inner());
+    registry.get(&rtype).map(|r| r.parse)
+}
+
+pub fn registered_mnemonic(rtype: u16) -> Option<&'static str> {
+    let registry = registry().read().unwrap_or_else(|e| e.into_inner());
+    registry.get(&rtype).map(|r| r.mnemonic)
+}
+
+// the type code registered for mnemonic, ignoring case.
+pub fn registered_type(mnemonic: &str) -> Option<u16> {
+    let registry = registry().read().unwrap_or_else(|e| e.into_inner());
+    registry.iter()
+        .find(|(_, r)| r.mnemonic.eq_ignore_ascii_case(mnemonic))
+        .map(|(rtype, _)| *rtype)
+}
@@ -0,0 +1,324 @@
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, Error, RootCertStore,
+             SignatureScheme, StreamOwned};
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::pki_types::pem::PemObject;
+use super::{DnsError, DnsQuery, DnsResponse};
+use super::transport::{connect_tcp, framed_exchange, remaining};
+
+// see rfc7858, section 3.1.
+pub const DOT_PORT: u16 = 853;
+
+/* how we decide a DoT server is the one we meant to talk to. see rfc7858, section 4.2
+   and rfc8310, section 8.
+
+   with no pins, the server's certificate chain has to validate against our roots (the
+   webpki roots, plus any CAs added here) for auth_name - or, if there's no auth_name,
+   for the server's ip address.
+   with pins, some certificate in the chain the server sends has to have a
+   SubjectPublicKeyInfo whose sha256 is one of them. if there's an auth_name as well, the
+   chain also has to validate for it; if not, the pin is all the authentication we do. */
+#[derive(Debug, Clone, Default)]
+pub struct DnsTlsConfig {
+    auth_name: Option<String>,
+    spki_pins: Vec<[u8; 32]>,
+    ca_certs: Vec<CertificateDer<'static>>
+}
+
+impl DnsTlsConfig {
+    pub fn new(auth_name: Option<String>, spki_pins: Vec<[u8; 32]>) -> DnsTlsConfig {
+        DnsTlsConfig { auth_name, spki_pins, ca_certs: Vec::new() }
+    }
+
+    pub fn auth_name(&self) -> Option<&str> {
+        self.auth_name.as_deref()
+    }
+
+    pub fn set_auth_name(&mut self, auth_name: Option<String>) {
+        self.auth_name = auth_name;
+    }
+
+    pub fn spki_pins(&self) -> &Vec<[u8; 32]> {
+        &self.spki_pins
+    }
+
+    pub fn set_spki_pins(&mut self, spki_pins: Vec<[u8; 32]>) {
+        self.spki_pins = spki_pins;
+    }
+
+    // trust the CA certificate(s) in pem, on top of the webpki roots. returns how many were added.
+    pub fn add_ca_pem(&mut self, pem: &[u8]) -> Result<usize, DnsError> {
+        let mut count = 0;
+        for cert in CertificateDer::pem_slice_iter(pem) {
+            match cert {
+                Ok(c) => self.ca_certs.push(c),
+                Err(e) => return Err(DnsError::Config(format!("Couldn't parse CA certificate: {e}")))
+            }
+            count += 1;
+        }
+        if count == 0 {
+            return Err(DnsError::Config(String::from("Didn't find any certificates in the CA PEM.")));
+        }
+        Ok(count)
+    }
+
+    pub fn add_ca_der(&mut self, der: &[u8]) {
+        self.ca_certs.push(CertificateDer::from(der.to_vec()));
+    }
+
+    // alpn is the application protocols we offer, most preferred first (see rfc7301).
+    pub(crate) fn client_config(&self, alpn: &[&[u8]]) -> Result<ClientConfig, DnsError> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        for cert in &self.ca_certs {
+            if let Err(e) = roots.add(cert.clone()) {
+                return Err(DnsError::Config(format!("Couldn't add CA certificate: {e}")));
+            }
+        }
+        let webpki = match WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone()).build() {
+            Ok(v) => v,
+            Err(e) => return Err(DnsError::Transport(format!("Couldn't build certificate verifier: {e}")))
+        };
+        let verifier = DotCertVerifier {
+            webpki,
+            spki_pins: self.spki_pins.clone(),
+            check_chain: self.spki_pins.is_empty() || self.auth_name.is_some(),
+            provider: provider.clone()
+        };
+
+        let builder = match ClientConfig::builder_with_provider(provider).with_safe_default_protocol_versions() {
+            Ok(b) => b,
+            Err(e) => return Err(DnsError::Transport(format!("Couldn't set up TLS protocol versions: {e}")))
+        };
+        let mut config = builder.dangerous()
+                                .with_custom_certificate_verifier(Arc::new(verifier))
+                                .with_no_client_auth();
+        config.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+        Ok(config)
+    }
+
+    // the name we hand to rustls: what goes out in SNI, and what the chain is checked against.
+    fn server_name(&self, server: &SocketAddr) -> Result<ServerName<'static>, DnsError> {
+        match &self.auth_name {
+            Some(name) => {
+                let trimmed = name.trim().trim_end_matches('.');
+                match ServerName::try_from(trimmed.to_string()) {
+                    Ok(sn) => Ok(sn),
+                    Err(e) => Err(DnsError::Config(format!("Couldn't use '{name}' as a TLS authentication name: {e}")))
+                }
+            },
+            None => Ok(ServerName::IpAddress(server.ip().into()))
+        }
+    }
+
+    // server_name, as a string - for apis (like quinn's) that want one.
+    #[cfg(feature = "doq")]
+    pub(crate) fn server_name_string(&self, server: &SocketAddr) -> Result<String, DnsError> {
+        match self.server_name(server)? {
+            ServerName::DnsName(name) => Ok(name.as_ref().to_string()),
+            _ => Ok(server.ip().to_string())
+        }
+    }
+}
+
+/* the verifier handed to rustls. chain validation is left to webpki, and only done if
+   check_chain is set; we just add the pin check on top. */
+#[derive(Debug)]
+struct DotCertVerifier {
+    webpki: Arc<WebPkiServerVerifier>,
+    spki_pins: Vec<[u8; 32]>,
+    check_chain: bool,
+    provider: Arc<CryptoProvider>
+}
+
+impl ServerCertVerifier for DotCertVerifier {
+    fn verify_server_cert(&self, end_entity: &CertificateDer<'_>, intermediates: &[CertificateDer<'_>],
+                          server_name: &ServerName<'_>, ocsp_response: &[u8], now: UnixTime)
+        -> Result<ServerCertVerified, Error> {
+        if self.check_chain {
+            self.webpki.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        }
+        if self.spki_pins.is_empty() {
+            return Ok(ServerCertVerified::assertion());
+        }
+
+        /* an unverified chain is just whatever the server sent, so a pin on one of the
+           intermediates proves nothing - anyone can send a copy of the pinned cert along with
+           their own. it only counts when webpki has checked the chain up to it. */
+        let intermediates = if self.check_chain { intermediates } else { &[] };
+        for cert in std::iter::once(end_entity).chain(intermediates.iter()) {
+            let pin = spki_pin(cert).map_err(|e| Error::General(e.to_string()))?;
+            if self.spki_pins.contains(&pin) {
+                return Ok(ServerCertVerified::assertion());
+            }
+        }
+        Err(Error::General(String::from("None of the server's certificates matched a configured SPKI pin.")))
+    }
+
+    fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct)
+        -> Result<HandshakeSignatureValid, Error> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct)
+        -> Result<HandshakeSignatureValid, Error> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/* parse a pin as written in rfc7469, section 2.4 (and given to us on the command line):
+   the base64 of the sha256 of a SubjectPublicKeyInfo. */
+pub fn parse_spki_pin(s: &str) -> Result<[u8; 32], DnsError> {
+    let s = s.trim();
+    let s = s.strip_prefix("pin-sha256=").unwrap_or(s).trim_matches('"');
+    let bytes = match BASE64.decode(s) {
+        Ok(b) => b,
+        Err(e) => return Err(DnsError::Config(format!("Couldn't decode SPKI pin '{s}' as base64: {e}")))
+    };
+    match <[u8; 32]>::try_from(bytes.as_slice()) {
+        Ok(pin) => Ok(pin),
+        Err(_) => Err(DnsError::Config(format!("SPKI pin '{s}' is {} bytes, expected a 32 byte sha256.", bytes.len())))
+    }
+}
+
+// the sha256 of the SubjectPublicKeyInfo of a DER certificate, for checking against a pin.
+pub fn spki_pin(cert_der: &[u8]) -> Result<[u8; 32], DnsError> {
+    let spki = cert_spki(cert_der)?;
+    let digest = ring::digest::digest(&ring::digest::SHA256, spki);
+    let mut ret = [0u8; 32];
+    ret.copy_from_slice(digest.as_ref());
+    Ok(ret)
+}
+
+/* read the DER tag and length at offset, returning (tag, start of contents, end of element).
+   only definite lengths are allowed in DER, and certificates don't need more than 4
+   length bytes. see x.690, section 8.1. */
+fn der_element(buf: &[u8], offset: usize) -> Result<(u8, usize, usize), DnsError> {
+    if offset + 2 > buf.len() {
+        return Err(DnsError::Transport(String::from("Hit certificate end reading a DER header.")));
+    }
+    let tag = buf[offset];
+    let first = buf[offset + 1] as usize;
+    let (len, start) = if first < 0x80 {
+        (first, offset + 2)
+    } else {
+        let nbytes = first & 0x7f;
+        if nbytes == 0 || nbytes > 4 || offset + 2 + nbytes > buf.len() {
+            return Err(DnsError::Transport(String::from("Got a bad DER length in certificate.")));
+        }
+        let len = buf[offset + 2 .. offset + 2 + nbytes].iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (len, offset + 2 + nbytes)
+    };
+    match start.checked_add(len) {
+        Some(end) if end <= buf.len() => Ok((tag, start, end)),
+        _ => Err(DnsError::Transport(String::from("Got a DER length running past the end of the certificate.")))
+    }
+}
+
+/* pull the SubjectPublicKeyInfo (tag and all) out of a DER certificate. see rfc5280,
+   section 4.1: it's the seventh field of the tbsCertificate, or sixth if the (optional,
+   explicitly tagged) version is missing. */
+fn cert_spki(cert_der: &[u8]) -> Result<&[u8], DnsError> {
+    const SEQUENCE: u8 = 0x30;
+    const VERSION: u8 = 0xa0; // context-specific, constructed, [0]
+
+    let (tag, cert_start, _) = der_element(cert_der, 0)?;
+    if tag != SEQUENCE {
+        return Err(DnsError::Transport(String::from("Certificate isn't a DER SEQUENCE.")));
+    }
+    let (tag, mut o, _) = der_element(cert_der, cert_start)?;
+    if tag != SEQUENCE {
+        return Err(DnsError::Transport(String::from("tbsCertificate isn't a DER SEQUENCE.")));
+    }
+
+    // skip the version (if there), serial number, signature, issuer, validity and subject.
+    let (tag, _, end) = der_element(cert_der, o)?;
+    if tag == VERSION {
+        o = end;
+    }
+    for _ in 0 .. 5 {
+        let (_, _, end) = der_element(cert_der, o)?;
+        o = end;
+    }
+
+    let (tag, _, end) = der_element(cert_der, o)?;
+    if tag != SEQUENCE {
+        return Err(DnsError::Transport(String::from("SubjectPublicKeyInfo isn't a DER SEQUENCE.")));
+    }
+    Ok(&cert_der[o .. end])
+}
+
+pub(crate) type TlsStream = StreamOwned<ClientConnection, TcpStream>;
+
+/* connect to server and do the tls handshake, checking the server's certificates as config
+   says to. returns the stream, along with a handle on the tcp connection underneath it,
+   for setting read timeouts on. */
+pub(crate) fn connect_tls(server: &SocketAddr, config: &DnsTlsConfig, alpn: &[&[u8]],
+                          deadline: Instant, timeout: Duration) -> Result<(TlsStream, TcpStream), DnsError> {
+    let client_config = Arc::new(config.client_config(alpn)?);
+    let server_name = config.server_name(server)?;
+
+    let sock = connect_tcp(server, timeout)?;
+    let conn = match ClientConnection::new(client_config, server_name) {
+        Ok(c) => c,
+        Err(e) => return Err(DnsError::Transport(format!("Couldn't set up TLS connection to {server}: {e}")))
+    };
+    let sock_handle = match sock.try_clone() {
+        Ok(s) => s,
+        Err(e) => return Err(DnsError::io("Couldn't clone TCP stream handle", &e))
+    };
+    let mut stream = StreamOwned::new(conn, sock);
+
+    // get the handshake (and so the certificate checks) out of the way on its own, so a
+    // failure there gets reported as such.
+    while stream.conn.is_handshaking() {
+        if let Err(e) = sock_handle.set_read_timeout(Some(remaining(deadline, timeout)?)) {
+            return Err(DnsError::io(format!("Couldn't set timeout of {timeout:?} on TCP stream"), &e));
+        }
+        if let Err(e) = stream.conn.complete_io(&mut stream.sock) {
+            return Err(DnsError::io(format!("TLS handshake with {server} failed"), &e));
+        }
+    }
+    Ok((stream, sock_handle))
+}
+
+// be polite, and tell the server we're done. nothing to be done if this fails.
+pub(crate) fn close_tls(stream: &mut TlsStream) {
+    stream.conn.send_close_notify();
+    let _ = stream.conn.complete_io(&mut stream.sock);
+}
+
+/* send the query bytes to server over tls, and return the bytes of the (unframed) response.
+   past the handshake, this is just tcp_exchange: the same two byte length framing, and the
+   same dropping of responses that fail check. see rfc7858, section 3.3. */
+pub fn tls_exchange(server: &SocketAddr, qbytes: &[u8], config: &DnsTlsConfig, timeout: Duration,
+                    check: &dyn Fn(&[u8]) -> Result<(), DnsError>,
+                    rejected: &mut Vec<String>) -> Result<Vec<u8>, DnsError> {
+    let deadline = Instant::now() + timeout;
+    let (mut stream, sock) = connect_tls(server, config, &[], deadline, timeout)?;
+    let ret = framed_exchange(&mut stream, &sock, server, qbytes, deadline, timeout, check, rejected);
+    close_tls(&mut stream);
+    ret
+}
+
+// send query to server over tls, accepting only responses that pass DnsQuery::check_response.
+pub fn send_query(server: &SocketAddr, query: &DnsQuery, config: &DnsTlsConfig, timeout: Duration,
+                  rejected: &mut Vec<String>) -> Result<DnsResponse, DnsError> {
+    let qbytes = query.to_bytes()?;
+    let check = |buf: &[u8]| query.check_response(buf);
+    let rbytes = tls_exchange(server, &qbytes, config, timeout, &check, rejected)?;
+    DnsResponse::from_bytes(&rbytes, 0)
+}
@@ -0,0 +1,289 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use super::{DnsError, DnsHeader, DnsQuery, DnsResponse};
+
+/* the ways we know how to get a query to a server and a response back.
+   see rfc1035, section 4.2 for udp and tcp, rfc7858 for tls, rfc8484 for https,
+   rfc9250 for quic and rfc9230 for oblivious doh. */
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum DnsTransport {
+    UDP,
+    TCP,
+    #[cfg(feature = "dot")]
+    TLS,
+    #[cfg(feature = "doh")]
+    HTTPS,
+    #[cfg(feature = "doq")]
+    QUIC,
+    #[cfg(feature = "odoh")]
+    ODOH
+}
+
+impl DnsTransport {
+    // the port a server listens on for this transport, when we aren't told otherwise.
+    pub fn default_port(&self) -> u16 {
+        match self {
+            DnsTransport::UDP | DnsTransport::TCP => DNS_PORT,
+            #[cfg(feature = "dot")]
+            DnsTransport::TLS => super::tls::DOT_PORT,
+            #[cfg(feature = "doh")]
+            DnsTransport::HTTPS => super::https::DOH_PORT,
+            #[cfg(feature = "doq")]
+            DnsTransport::QUIC => super::quic::DOQ_PORT,
+            #[cfg(feature = "odoh")]
+            DnsTransport::ODOH => super::https::DOH_PORT
+        }
+    }
+}
+
+impl fmt::Display for DnsTransport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DnsTransport::UDP => write!(f, "UDP"),
+            DnsTransport::TCP => write!(f, "TCP"),
+            #[cfg(feature = "dot")]
+            DnsTransport::TLS => write!(f, "TLS"),
+            #[cfg(feature = "doh")]
+            DnsTransport::HTTPS => write!(f, "HTTPS"),
+            #[cfg(feature = "doq")]
+            DnsTransport::QUIC => write!(f, "QUIC"),
+            #[cfg(feature = "odoh")]
+            DnsTransport::ODOH => write!(f, "ODOH")
+        }
+    }
+}
+
+pub const DNS_PORT: u16 = 53;
+
+/* parse a server address as given on the command line. accepted forms are
+   1.2.3.4, 1.2.3.4:53, 2001:db8::1, [2001:db8::1] and [2001:db8::1]:53.
+   if no port is given, port 53 is used. */
+pub fn parse_server_addr(s: &str) -> Result<SocketAddr, DnsError> {
+    parse_server_addr_with_port(s, DNS_PORT)
+}
+
+// like parse_server_addr, but with default_port used when no port is given.
+pub fn parse_server_addr_with_port(s: &str, default_port: u16) -> Result<SocketAddr, DnsError> {
+    let s = s.trim();
+    if let Ok(sa) = SocketAddr::from_str(s) {
+        return Ok(sa);
+    }
+    // a bare ipv6 address has colons in it, so it has to be tried before we go looking for a port.
+    if let Ok(ip) = IpAddr::from_str(s) {
+        return Ok(SocketAddr::new(ip, default_port));
+    }
+    if let Some(inner) = s.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+        if let Ok(ip) = Ipv6Addr::from_str(inner) {
+            return Ok(SocketAddr::new(IpAddr::V6(ip), default_port));
+        }
+    }
+    Err(DnsError::Config(format!("Couldn't parse '{s}' as a server address (expected ip, ip:port, [ipv6] or [ipv6]:port).")))
+}
+
+// the unspecified address/port of the same family as server, for binding a local socket to.
+pub fn local_bind_addr(server: &SocketAddr) -> SocketAddr {
+    match server {
+        SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)
+    }
+}
+
+// a read that hit its timeout shows up as one of these, depending on platform.
+fn is_timeout(e: &std::io::Error) -> bool {
+    matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut)
+}
+
+/* over tcp, every message is prefixed with a two byte (network order) length field.
+   see rfc1035, section 4.2.2 */
+pub fn tcp_frame(msg: &[u8]) -> Result<Vec<u8>, DnsError> {
+    if msg.len() > u16::MAX as usize {
+        return Err(DnsError::Oversize { context: "message to send over TCP", len: msg.len(), max: u16::MAX as usize });
+    }
+    let mut ret: Vec<u8> = Vec::with_capacity(msg.len() + 2);
+    ret.extend_from_slice(&(msg.len() as u16).to_be_bytes());
+    ret.extend_from_slice(msg);
+    Ok(ret)
+}
+
+// read one length-prefixed message off of a stream, returning the message without its prefix.
+pub fn read_tcp_frame<R: Read>(stream: &mut R) -> Result<Vec<u8>, DnsError> {
+    let mut lenbytes = [0u8, 0u8];
+    if let Err(e) = stream.read_exact(&mut lenbytes) {
+        return Err(DnsError::io("Error reading TCP length prefix", &e));
+    }
+    let len = u16::from_be_bytes(lenbytes) as usize;
+    let mut ret = vec![0u8; len];
+    if let Err(e) = stream.read_exact(&mut ret) {
+        return Err(DnsError::io(format!("Error reading {len} byte TCP message"), &e));
+    }
+    Ok(ret)
+}
+
+/* how long we have left until deadline, or a timeout error if it's already passed.
+   a zero read timeout means "block forever" to the socket api, so we never hand one out. */
+pub(crate) fn remaining(deadline: Instant, timeout: Duration) -> Result<Duration, DnsError> {
+    match deadline.checked_duration_since(Instant::now()) {
+        Some(d) if !d.is_zero() => Ok(d),
+        _ => Err(DnsError::Timeout(timeout))
+    }
+}
+
+/* send the query bytes to server over udp, and return the bytes of the first acceptable
+   datagram we get back. a datagram is acceptable if it came from server and check
+   returns Ok for it. anything else is dropped - with the reason pushed onto rejected -
+   and we keep waiting, until timeout has passed since the query went out. */
+pub fn udp_exchange(server: &SocketAddr, qbytes: &[u8], timeout: Duration,
+                    check: &dyn Fn(&[u8]) -> Result<(), DnsError>,
+                    rejected: &mut Vec<String>) -> Result<Vec<u8>, DnsError> {
+    let local = local_bind_addr(server);
+    let socket = match UdpSocket::bind(local) {
+        Ok(s) => s,
+        Err(e) => return Err(DnsError::io(format!("Couldn't bind UDP socket to {local}"), &e))
+    };
+
+    match socket.send_to(qbytes, server) {
+        Ok(sent) if sent != qbytes.len() => {
+            return Err(DnsError::Transport(format!("Only sent {sent} of {} query bytes.", qbytes.len())));
+        },
+        Ok(_) => {},
+        Err(e) => return Err(DnsError::io("Error sending to socket", &e))
+    }
+    let deadline = Instant::now() + timeout;
+
+    let mut rbuf = [0u8; 65535];
+    loop {
+        if let Err(e) = socket.set_read_timeout(Some(remaining(deadline, timeout)?)) {
+            return Err(DnsError::io(format!("Couldn't set timeout of {timeout:?} on UDP socket"), &e));
+        }
+        let (response_length, from) = match socket.recv_from(&mut rbuf) {
+            Ok(r) => r,
+            Err(e) if is_timeout(&e) => return Err(DnsError::Timeout(timeout)),
+            Err(e) => return Err(DnsError::io("Error reading response from server", &e))
+        };
+        if from != *server {
+            rejected.push(format!("Dropped a packet from {from}, which we didn't send a query to."));
+            continue;
+        }
+        let rbytes = &rbuf[0 .. response_length];
+        match check(rbytes) {
+            Ok(()) => return Ok(rbytes.to_vec()),
+            Err(e) => rejected.push(format!("Dropped a packet from {from}: {e}"))
+        }
+    }
+}
+
+// connect to server over tcp, giving up (and not letting writes block) past timeout.
+pub(crate) fn connect_tcp(server: &SocketAddr, timeout: Duration) -> Result<TcpStream, DnsError> {
+    let stream = match TcpStream::connect_timeout(server, timeout) {
+        Ok(s) => s,
+        Err(e) => return Err(DnsError::io(format!("Couldn't connect to {server} over TCP"), &e))
+    };
+    if let Err(e) = stream.set_write_timeout(Some(timeout)) {
+        return Err(DnsError::io(format!("Couldn't set timeout of {timeout:?} on TCP stream"), &e));
+    }
+    Ok(stream)
+}
+
+/* the part of a tcp exchange that doesn't care what's layered over the connection: write
+   the framed query to stream, then read framed messages off of it until one passes check
+   or deadline passes. sock is the tcp connection underneath stream, which is where the
+   read timeouts go. */
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn framed_exchange<S: Read + Write>(stream: &mut S, sock: &TcpStream, server: &SocketAddr,
+                                               qbytes: &[u8], deadline: Instant, timeout: Duration,
+                                               check: &dyn Fn(&[u8]) -> Result<(), DnsError>,
+                                               rejected: &mut Vec<String>) -> Result<Vec<u8>, DnsError> {
+    let set_read_timeout = || -> Result<(), DnsError> {
+        match sock.set_read_timeout(Some(remaining(deadline, timeout)?)) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(DnsError::io(format!("Couldn't set timeout of {timeout:?} on TCP stream"), &e))
+        }
+    };
+
+    let framed = tcp_frame(qbytes)?;
+    if let Err(e) = stream.write_all(&framed).and_then(|_| stream.flush()) {
+        return Err(DnsError::io("Error writing query to stream", &e));
+    }
+
+    loop {
+        set_read_timeout()?;
+        let rbytes = read_tcp_frame(stream)?;
+        match check(&rbytes) {
+            Ok(()) => return Ok(rbytes),
+            Err(e) => rejected.push(format!("Dropped a message from {server}: {e}"))
+        }
+    }
+}
+
+/* send the query bytes to server over tcp, and return the bytes of the (unframed) response.
+   as with udp_exchange, responses that fail check are dropped and we keep reading, until
+   timeout has passed since we connected. */
+pub fn tcp_exchange(server: &SocketAddr, qbytes: &[u8], timeout: Duration,
+                    check: &dyn Fn(&[u8]) -> Result<(), DnsError>,
+                    rejected: &mut Vec<String>) -> Result<Vec<u8>, DnsError> {
+    let deadline = Instant::now() + timeout;
+    let mut stream = connect_tcp(server, timeout)?;
+    let sock = match stream.try_clone() {
+        Ok(s) => s,
+        Err(e) => return Err(DnsError::io("Couldn't clone TCP stream handle", &e))
+    };
+    framed_exchange(&mut stream, &sock, server, qbytes, deadline, timeout, check, rejected)
+}
+
+/* doh and doq want the id of every query to be 0 (rfc8484, section 4.1 and rfc9250,
+   section 4.2.1). returns the bytes of query with its id zeroed, along with the query
+   those bytes parse back to, for checking responses against. */
+#[cfg(any(feature = "doh", feature = "doq"))]
+pub(crate) fn zero_id_query(query: &DnsQuery) -> Result<(Vec<u8>, DnsQuery), DnsError> {
+    let mut qbytes = query.to_bytes()?;
+    qbytes[0] = 0;
+    qbytes[1] = 0;
+    let query = DnsQuery::from_bytes(&qbytes, 0)?;
+    Ok((qbytes, query))
+}
+
+/* send query to server, and parse what comes back. only responses that pass
+   DnsQuery::check_response are accepted; the reasons for dropping any others are pushed
+   onto rejected. if we asked over udp and the server set the TC bit, the answer didn't
+   fit in a datagram - ask again over tcp, per rfc1035, section 4.2.1 (and rfc7766,
+   section 5). returns the response along with the transport that actually carried it.
+   tls, https, quic and oblivious doh need more than a server address to go on, so they go
+   through tls::send_query, https::send_query, quic::send_query and odoh::send_query. */
+pub fn send_query(server: &SocketAddr, query: &DnsQuery, transport: DnsTransport,
+                  timeout: Duration, rejected: &mut Vec<String>) ->
+    Result<(DnsResponse, DnsTransport), DnsError> {
+    #[cfg(feature = "dot")]
+    if transport == DnsTransport::TLS {
+        return Err(DnsError::Config(String::from("Can't send a TLS query without a TLS config; use tls::send_query.")));
+    }
+    #[cfg(feature = "doh")]
+    if transport == DnsTransport::HTTPS {
+        return Err(DnsError::Config(String::from("Can't send an HTTPS query without a url; use https::send_query.")));
+    }
+    #[cfg(feature = "doq")]
+    if transport == DnsTransport::QUIC {
+        return Err(DnsError::Config(String::from("Can't send a QUIC query without a TLS config; use quic::send_query.")));
+    }
+    #[cfg(feature = "odoh")]
+    if transport == DnsTransport::ODOH {
+        return Err(DnsError::Config(String::from("Can't send an ODOH query without a proxy and target; use odoh::send_query.")));
+    }
+
+    let qbytes = query.to_bytes()?;
+    let check = |buf: &[u8]| query.check_response(buf);
+
+    if transport == DnsTransport::UDP {
+        let rbytes = udp_exchange(server, &qbytes, timeout, &check, rejected)?;
+        /* check TC off the raw header before parsing the rest. a truncated response may
+           not parse cleanly, and we're going to throw it away anyway. */
+        if !DnsHeader::from_bytes(&rbytes, 0)?.tc() {
+            return Ok((DnsResponse::from_bytes(&rbytes, 0)?, DnsTransport::UDP));
+        }
+    }
+
+    let rbytes = tcp_exchange(server, &qbytes, timeout, &check, rejected)?;
+    Ok((DnsResponse::from_bytes(&rbytes, 0)?, DnsTransport::TCP))
+}
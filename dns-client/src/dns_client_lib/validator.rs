@@ -0,0 +1,732 @@
+/* DNSSEC validation (rfc4033, rfc4034 and rfc4035): a chain of trust from a trust anchor
+   down to the records in a response, checking the RRSIGs along the way, and the NSEC or
+   NSEC3 (rfc5155) records that prove a name or type doesn't exist. the DS and DNSKEY sets
+   the chain needs come from a fetch function, so this works the same against a resolver
+   or against a zone made up for a test. */
+
+use ring::{digest, signature};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use super::*;
+use super::dnssec::*;
+
+/* the root zone's KSKs, KSK-2017 (20326) and KSK-2024 (38696), as DS records - see
+   https://data.iana.org/root-anchors/root-anchors.xml */
+const ROOT_ANCHORS: [(u16, [u8; 32]); 2] = [
+    (20326, [0xe0, 0x6d, 0x44, 0xb8, 0x0b, 0x8f, 0x1d, 0x39, 0xa9, 0x5c, 0x0b, 0x0d, 0x7c, 0x65, 0xd0, 0x84,
+             0x58, 0xe8, 0x80, 0x40, 0x9b, 0xbc, 0x68, 0x34, 0x57, 0x10, 0x42, 0x37, 0xc7, 0xf8, 0xec, 0x8d]),
+    (38696, [0x68, 0x3d, 0x2d, 0x0a, 0xcb, 0x8c, 0x9b, 0x71, 0x2a, 0x19, 0x48, 0xb2, 0x7f, 0x74, 0x12, 0x19,
+             0x29, 0x8d, 0x0a, 0x45, 0x0d, 0x61, 0x2c, 0x48, 0x3a, 0xf4, 0x44, 0xa4, 0xc0, 0xfb, 0x2b, 0x16])
+];
+
+// how many fetches one validation can make, so a broken (or hostile) chain can't go on forever.
+const MAX_FETCHES: usize = 64;
+
+// NSEC3 with more iterations than this is treated as unsigned (rfc9276, section 3.2).
+const MAX_NSEC3_ITERATIONS: u16 = 150;
+
+#[derive(Debug, Clone)]
+pub struct DnsValidator {
+    anchors: Vec<(String, DnsDSRecord)>,
+    now: Option<u32>
+}
+
+impl DnsValidator {
+    // trust anchors are DS records, with the name of the zone each is for.
+    pub fn new(anchors: Vec<(String, DnsDSRecord)>) -> DnsValidator {
+        DnsValidator { anchors, now: None }
+    }
+
+    // validate from the root zone's KSKs.
+    pub fn root() -> DnsValidator {
+        let anchors = ROOT_ANCHORS.iter()
+            .map(|(tag, digest)| (String::from("."), DnsDSRecord::new(*tag, 8, 2, digest.to_vec())))
+            .collect();
+        DnsValidator::new(anchors)
+    }
+
+    pub fn anchors(&self) -> &[(String, DnsDSRecord)] {
+        &self.anchors
+    }
+
+    // check signature validity periods against this time (seconds since the epoch) instead of the clock.
+    pub fn set_now(&mut self, now: Option<u32>) {
+        self.now = now;
+    }
+
+    /* validate response. fetch is asked for the (name, type) sets the chain of trust needs,
+       and should return what a resolver would, RRSIGs included - i.e. ask with DO set. */
+    pub fn validate<F>(&self, response: &DnsResponse, fetch: F) -> DnssecStatus
+        where F: FnMut(&str, DnsQType) -> Result<DnsResponse, DnsError> {
+        let now = self.now.unwrap_or_else(|| {
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as u32).unwrap_or(0)
+        });
+        let mut v = Validation { validator: self, fetch, fetches: 0, now, zones: HashMap::new(),
+                                 enclosing: HashMap::new() };
+        v.response(response)
+    }
+}
+
+/* the data an RRSIG's signature covers (rfc4034, section 3.1.8.1): the RRSIG's own rdata up
+   to the signature, then every record of the RRset in canonical form and order (section 6). */
+pub fn signed_data(rrsig: &DnsRRSIGRecord, records: &[&DnsResourceRecord]) -> Result<Vec<u8>, DnsError> {
+    let mut data: Vec<u8> = Vec::new();
+    data.extend_from_slice(&rrsig.type_covered().to_u16().to_be_bytes());
+    data.push(rrsig.algorithm());
+    data.push(rrsig.labels());
+    data.extend_from_slice(&rrsig.original_ttl().to_be_bytes());
+    data.extend_from_slice(&rrsig.expiration().to_be_bytes());
+    data.extend_from_slice(&rrsig.inception().to_be_bytes());
+    data.extend_from_slice(&rrsig.key_tag().to_be_bytes());
+    data.append(&mut canonical_name(rrsig.signer()));
+
+    let Some(first) = records.first() else { return Ok(data); };
+    // records from a wildcard are signed as the wildcard (rfc4035, section 5.3.2).
+    let owner = if (rrsig.labels() as usize) < labels(first.name()).len() {
+        wildcard(&ancestor(first.name(), rrsig.labels() as usize))
+    } else {
+        String::from(first.name())
+    };
+    let owner = canonical_name(&owner);
+
+    let mut rdatas: Vec<Vec<u8>> = Vec::new();
+    for rr in records {
+        let mut w = DnsMessageWriter::canonical();
+        rr.record().rdata().write_rdata(&mut w)?;
+        rdatas.push(w.into_bytes());
+    }
+    rdatas.sort();
+    rdatas.dedup();
+    for rdata in rdatas {
+        if rdata.len() > u16::MAX as usize {
+            return Err(DnsError::Oversize { context: "resource record rdata", len: rdata.len(), max: u16::MAX as usize });
+        }
+        data.extend_from_slice(&owner);
+        data.extend_from_slice(&first.qtype().to_u16().to_be_bytes());
+        data.extend_from_slice(&first.class().to_u16().to_be_bytes());
+        data.extend_from_slice(&rrsig.original_ttl().to_be_bytes());
+        data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        data.extend_from_slice(&rdata);
+    }
+    Ok(data)
+}
+
+// the DS record for key, as owner's parent zone would have it (rfc4034, section 5.1.4).
+pub fn make_ds(owner: &str, key: &DnsDNSKEYRecord, digest_type: u8) -> Result<DnsDSRecord, DnsError> {
+    let algorithm = match digest_type {
+        1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        2 => &digest::SHA256,
+        4 => &digest::SHA384,
+        other => return Err(DnsError::Crypto(format!("Unsupported DS digest type {other}.")))
+    };
+    let mut data = canonical_name(owner);
+    data.extend_from_slice(&key.to_bytes());
+    let digest = digest::digest(algorithm, &data);
+    Ok(DnsDSRecord::new(key.key_tag(), key.algorithm(), digest_type, digest.as_ref().to_vec()))
+}
+
+// RSA/SHA-256 (rfc5702), ECDSA P-256 and P-384 (rfc6605), and Ed25519 (rfc8080).
+fn supported_algorithm(algorithm: u8) -> bool {
+    matches!(algorithm, 8 | 13 | 14 | 15)
+}
+
+fn verify_signature(key: &DnsDNSKEYRecord, data: &[u8], sig: &[u8]) -> bool {
+    let public_key = key.public_key();
+    match key.algorithm() {
+        8 => {
+            let Some((e, n)) = rsa_public_key(public_key) else { return false; };
+            signature::RsaPublicKeyComponents { n, e }
+                .verify(&signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY, data, sig).is_ok()
+        },
+        13 | 14 => {
+            let algorithm = if key.algorithm() == 13 {
+                &signature::ECDSA_P256_SHA256_FIXED
+            } else {
+                &signature::ECDSA_P384_SHA384_FIXED
+            };
+            // the key is just the point's x and y (rfc6605, section 4); ring wants it uncompressed.
+            let mut point = vec![0x04];
+            point.extend_from_slice(public_key);
+            signature::UnparsedPublicKey::new(algorithm, point).verify(data, sig).is_ok()
+        },
+        15 => signature::UnparsedPublicKey::new(&signature::ED25519, public_key).verify(data, sig).is_ok(),
+        _ => false
+    }
+}
+
+// an RSA key is the exponent's length (in one byte, or zero then two), the exponent, then the modulus (rfc3110, section 2).
+fn rsa_public_key(key: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (elen, rest) = match *key.first()? {
+        0 if key.len() >= 3 => (u16::from_be_bytes([key[1], key[2]]) as usize, &key[3 ..]),
+        0 => return None,
+        len => (len as usize, &key[1 ..])
+    };
+    if elen == 0 || rest.len() <= elen {
+        return None;
+    }
+    Some(rest.split_at(elen))
+}
+
+// the times are compared with serial number arithmetic (rfc4034, section 3.1.5).
+fn in_validity_period(rrsig: &DnsRRSIGRecord, now: u32) -> bool {
+    now.wrapping_sub(rrsig.inception()) as i32 >= 0 && rrsig.expiration().wrapping_sub(now) as i32 >= 0
+}
+
+// a name's labels, lowercased, without the root.
+fn labels(name: &str) -> Vec<String> {
+    name.trim().trim_end_matches('.').split('.')
+        .filter(|l| !l.is_empty())
+        .map(|l| l.to_ascii_lowercase())
+        .collect()
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    labels(a) == labels(b)
+}
+
+// is name at or below zone?
+fn is_subdomain(name: &str, zone: &str) -> bool {
+    let name = labels(name);
+    let zone = labels(zone);
+    name.len() >= zone.len() && name[name.len() - zone.len() ..] == zone[..]
+}
+
+// the ancestor of name with count labels.
+fn ancestor(name: &str, count: usize) -> String {
+    let labels = labels(name);
+    let count = count.min(labels.len());
+    let mut ret = labels[labels.len() - count ..].join(".");
+    ret.push('.');
+    ret
+}
+
+fn wildcard(name: &str) -> String {
+    match labels(name).len() {
+        0 => String::from("*."),
+        _ => format!("*.{}", ancestor(name, usize::MAX))
+    }
+}
+
+// the wire form of name, lowercased (rfc4034, section 6.2).
+fn canonical_name(name: &str) -> Vec<u8> {
+    let mut ret: Vec<u8> = Vec::new();
+    for label in labels(name) {
+        ret.push(label.len() as u8);
+        ret.extend_from_slice(label.as_bytes());
+    }
+    ret.push(0);
+    ret
+}
+
+// canonical name order (rfc4034, section 6.1): label by label, from the right.
+fn canonical_cmp(a: &str, b: &str) -> Ordering {
+    labels(a).iter().rev().cmp(labels(b).iter().rev())
+}
+
+// does the NSEC from owner to next cover name, i.e. is name between them?
+fn nsec_covers(owner: &str, next: &str, name: &str) -> bool {
+    let after_owner = canonical_cmp(owner, name) == Ordering::Less;
+    let before_next = canonical_cmp(name, next) == Ordering::Less;
+    match canonical_cmp(owner, next) {
+        Ordering::Less => after_owner && before_next,
+        // the last NSEC in the zone points back at the apex.
+        _ => after_owner || before_next
+    }
+}
+
+// the same, for the base32hex hashes of NSEC3, which sort the same as the hashes themselves.
+fn nsec3_covers(owner: &str, next: &str, hash: &str) -> bool {
+    match owner.cmp(next) {
+        Ordering::Less => owner < hash && hash < next,
+        _ => owner < hash || hash < next
+    }
+}
+
+// a name's NSEC3 hash, in base32hex: H(x) with the salt, then iterations more times (rfc5155, section 5).
+pub fn nsec3_hash(name: &str, salt: &[u8], iterations: u16) -> String {
+    let mut data = canonical_name(name);
+    data.extend_from_slice(salt);
+    let mut hash = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &data);
+    for _ in 0 .. iterations {
+        let mut data = hash.as_ref().to_vec();
+        data.extend_from_slice(salt);
+        hash = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &data);
+    }
+    base32hex(hash.as_ref())
+}
+
+// the worse of two results, where Bogus is the worst.
+fn worse(a: DnssecStatus, b: DnssecStatus) -> DnssecStatus {
+    let rank = |s: DnssecStatus| match s {
+        DnssecStatus::Secure => 0,
+        DnssecStatus::Insecure => 1,
+        DnssecStatus::Indeterminate => 2,
+        DnssecStatus::Bogus => 3
+    };
+    if rank(b) > rank(a) { b } else { a }
+}
+
+// the records in a section with the same name, type and class.
+struct RRset<'a> {
+    name: String,
+    rtype: DnsQType,
+    records: Vec<&'a DnsResourceRecord>
+}
+
+// the RRsets in a section, leaving out the RRSIGs and the OPT pseudo-RR.
+fn rrsets(section: &[DnsResourceRecord]) -> Vec<RRset<'_>> {
+    let mut sets: Vec<RRset> = Vec::new();
+    for rr in section {
+        if rr.qtype() == DnsQType::RRSIG || rr.qtype() == DnsQType::OPT {
+            continue;
+        }
+        let existing = sets.iter_mut().find(|set| {
+            same_name(&set.name, rr.name()) && set.rtype == rr.qtype() && set.records[0].class() == rr.class()
+        });
+        match existing {
+            Some(set) => set.records.push(rr),
+            None => sets.push(RRset { name: String::from(rr.name()), rtype: rr.qtype(), records: vec![rr] })
+        }
+    }
+    sets
+}
+
+fn rrsigs<'a>(section: &'a [DnsResourceRecord], name: &str, rtype: DnsQType) -> Vec<&'a DnsRRSIGRecord> {
+    section.iter()
+        .filter(|rr| same_name(rr.name(), name))
+        .filter_map(|rr| match rr.record() {
+            DnsResourceRecordEnum::RRSIG(sig) if sig.type_covered() == rtype => Some(sig),
+            _ => None
+        })
+        .collect()
+}
+
+// what a zone's DNSKEYs came to: the keys if they're trusted, or why they're not.
+#[derive(Clone)]
+enum ZoneKeys {
+    Trusted(Vec<DnsDNSKEYRecord>),
+    Untrusted(DnssecStatus)
+}
+
+// what NSEC or NSEC3 records came to, as proof that something doesn't exist.
+enum Denial {
+    Proven,
+    // an opt-out span or unsupported NSEC3 parameters; treated as unsigned.
+    Insecure,
+    Unproven
+}
+
+struct Validation<'v, F> {
+    validator: &'v DnsValidator,
+    fetch: F,
+    fetches: usize,
+    now: u32,
+    zones: HashMap<String, ZoneKeys>,
+    // the zone each name is in, as its SOA says.
+    enclosing: HashMap<String, Option<String>>
+}
+
+impl<F> Validation<'_, F> where F: FnMut(&str, DnsQType) -> Result<DnsResponse, DnsError> {
+    fn fetch(&mut self, name: &str, qtype: DnsQType) -> Option<DnsResponse> {
+        if self.fetches >= MAX_FETCHES {
+            return None;
+        }
+        self.fetches += 1;
+        (self.fetch)(name, qtype).ok()
+    }
+
+    fn response(&mut self, r: &DnsResponse) -> DnssecStatus {
+        let Some(question) = r.questions().first() else { return DnssecStatus::Indeterminate; };
+        let nxdomain = match r.rcode() {
+            DnsRcode::NOERROR => false,
+            DnsRcode::NAMERR => true,
+            // SERVFAIL and the like have nothing in them to validate.
+            _ => return DnssecStatus::Indeterminate
+        };
+
+        let mut status = DnssecStatus::Secure;
+        for set in rrsets(r.answers()) {
+            status = worse(status, self.rrset(&set, r.answers(), r.authorities()));
+        }
+
+        // follow any CNAMEs to the name the answer ends at; if that has nothing, prove it.
+        let qtype = question.qtype();
+        let mut name = String::from(question.name());
+        for _ in 0 ..= r.answers().len() {
+            let at_name = || r.answers().iter().filter(|rr| same_name(rr.name(), &name));
+            if (qtype == DnsQType::ANY && at_name().next().is_some()) || at_name().any(|rr| rr.qtype() == qtype) {
+                return status;
+            }
+            let cname = at_name().find_map(|rr| match rr.record() {
+                DnsResourceRecordEnum::CNAME(cname) => Some(String::from(cname.name())),
+                _ => None
+            });
+            match cname {
+                Some(target) => name = target,
+                None => break
+            }
+        }
+        worse(status, self.denial(&name, qtype, r.authorities(), nxdomain))
+    }
+
+    /* check set against its RRSIGs, which are in section along with it. only an RRSIG from the
+       zone the RRset is in counts (rfc4035, section 5.3.1), and if that zone is signed, nothing
+       short of one that checks out will do. */
+    fn rrset(&mut self, set: &RRset, section: &[DnsResourceRecord], authorities: &[DnsResourceRecord])
+        -> DnssecStatus {
+        let Some(zone) = self.rrset_zone(set) else { return DnssecStatus::Indeterminate; };
+        let keys = match self.zone_keys(&zone) {
+            ZoneKeys::Trusted(keys) => keys,
+            ZoneKeys::Untrusted(status) => return status
+        };
+
+        for sig in rrsigs(section, &set.name, set.rtype) {
+            if !same_name(sig.signer(), &zone) || !self.verify_rrset(set, sig, &keys) {
+                continue;
+            }
+            // an answer from a wildcard also needs proof there was nothing closer (rfc4035, section 5.3.4).
+            let expanded = (sig.labels() as usize) < labels(&set.name).len();
+            if expanded && !self.wildcard_proof(&zone, &set.name, sig.labels() as usize, authorities) {
+                continue;
+            }
+            return DnssecStatus::Secure;
+        }
+        // the zone is signed, so the records should have been too.
+        DnssecStatus::Bogus
+    }
+
+    /* the zone set is in. a DS set lives on the parent's side of the zone cut, and so does the
+       NSEC at a cut - the child's own NSEC there has SOA in it. an NSEC3's owner is always
+       right below its zone's apex (rfc5155, section 7.1). */
+    fn rrset_zone(&mut self, set: &RRset) -> Option<String> {
+        let parent = ancestor(&set.name, labels(&set.name).len().saturating_sub(1));
+        let child_nsec = || set.records.iter().any(|rr| {
+            matches!(rr.record(), DnsResourceRecordEnum::NSEC(nsec) if nsec.types().contains(&DnsQType::SOA))
+        });
+        match set.rtype {
+            DnsQType::DS => self.zone_of(&parent),
+            DnsQType::NSEC if !child_nsec() => self.zone_of(&parent),
+            DnsQType::NSEC3 => Some(parent),
+            _ => self.zone_of(&set.name)
+        }
+    }
+
+    // the checks from rfc4035, section 5.3.1, then the signature itself.
+    fn verify_rrset(&self, set: &RRset, sig: &DnsRRSIGRecord, keys: &[DnsDNSKEYRecord]) -> bool {
+        if sig.type_covered() != set.rtype || !is_subdomain(&set.name, sig.signer())
+            || sig.labels() as usize > labels(&set.name).len() || !in_validity_period(sig, self.now) {
+            return false;
+        }
+        let Ok(data) = signed_data(sig, &set.records) else { return false; };
+        keys.iter().any(|key| {
+            key.zone_key() && !key.revoked() && key.algorithm() == sig.algorithm()
+                && key.key_tag() == sig.key_tag() && verify_signature(key, &data, sig.signature())
+        })
+    }
+
+    /* the zone name is in, going by the SOA that comes back for it. that's not to be trusted
+       on its own, but the zone's keys still have to come down the chain of trust. */
+    fn zone_of(&mut self, name: &str) -> Option<String> {
+        let name = ancestor(name, usize::MAX);
+        if let Some(zone) = self.enclosing.get(&name) {
+            return zone.clone();
+        }
+        let zone = self.fetch(&name, DnsQType::SOA).and_then(|r| {
+            r.answers().iter().chain(r.authorities().iter())
+                .find(|rr| rr.qtype() == DnsQType::SOA && is_subdomain(&name, rr.name()))
+                .map(|rr| ancestor(rr.name(), usize::MAX))
+        });
+        self.enclosing.insert(name, zone.clone());
+        zone
+    }
+
+    fn zone_keys(&mut self, zone: &str) -> ZoneKeys {
+        let zone = ancestor(zone, usize::MAX);
+        if let Some(keys) = self.zones.get(&zone) {
+            return keys.clone();
+        }
+        // anything that comes back round to this zone while it's being worked out gets nowhere.
+        self.zones.insert(zone.clone(), ZoneKeys::Untrusted(DnssecStatus::Indeterminate));
+        let keys = self.find_zone_keys(&zone);
+        self.zones.insert(zone, keys.clone());
+        keys
+    }
+
+    /* a zone's keys are trusted if one of them matches a DS we trust and signs the rest: the
+       DS being a trust anchor, or a DS set the parent zone signed (rfc4035, section 5). */
+    fn find_zone_keys(&mut self, zone: &str) -> ZoneKeys {
+        let anchors: Vec<DnsDSRecord> = self.validator.anchors.iter()
+            .filter(|(name, _)| same_name(name, zone))
+            .map(|(_, ds)| ds.clone())
+            .collect();
+        if !anchors.is_empty() {
+            return self.keys_from_ds(zone, &anchors);
+        }
+        if !self.validator.anchors.iter().any(|(name, _)| is_subdomain(zone, name)) {
+            return ZoneKeys::Untrusted(DnssecStatus::Indeterminate);
+        }
+
+        let Some(r) = self.fetch(zone, DnsQType::DS) else {
+            return ZoneKeys::Untrusted(DnssecStatus::Indeterminate);
+        };
+        let sets = rrsets(r.answers());
+        let Some(set) = sets.iter().find(|set| set.rtype == DnsQType::DS && same_name(&set.name, zone)) else {
+            /* no DS. that only makes the zone unsigned if the parent proves there's a delegation
+               there without one: NS, but neither DS nor SOA (rfc4035, section 5.2, and rfc6840,
+               section 4.4). without a delegation, there's no zone there to have keys at all. */
+            return match self.denial(zone, DnsQType::DS, r.authorities(), r.rcode() == DnsRcode::NAMERR) {
+                DnssecStatus::Secure if self.insecure_delegation(zone, r.authorities()) =>
+                    ZoneKeys::Untrusted(DnssecStatus::Insecure),
+                DnssecStatus::Secure => ZoneKeys::Untrusted(DnssecStatus::Bogus),
+                status => ZoneKeys::Untrusted(status)
+            };
+        };
+        match self.rrset(set, r.answers(), r.authorities()) {
+            DnssecStatus::Secure => {
+                let ds: Vec<DnsDSRecord> = set.records.iter().filter_map(|rr| match rr.record() {
+                    DnsResourceRecordEnum::DS(ds) => Some(ds.clone()),
+                    _ => None
+                }).collect();
+                self.keys_from_ds(zone, &ds)
+            },
+            status => ZoneKeys::Untrusted(status)
+        }
+    }
+
+    fn keys_from_ds(&mut self, zone: &str, ds: &[DnsDSRecord]) -> ZoneKeys {
+        // a zone with no DS we can check is treated as unsigned (rfc4035, section 5.2).
+        let usable: Vec<&DnsDSRecord> = ds.iter()
+            .filter(|ds| supported_algorithm(ds.algorithm()) && matches!(ds.digest_type(), 1 | 2 | 4))
+            .collect();
+        if usable.is_empty() {
+            return ZoneKeys::Untrusted(DnssecStatus::Insecure);
+        }
+
+        let Some(r) = self.fetch(zone, DnsQType::DNSKEY) else {
+            return ZoneKeys::Untrusted(DnssecStatus::Indeterminate);
+        };
+        let sets = rrsets(r.answers());
+        let Some(set) = sets.iter().find(|set| set.rtype == DnsQType::DNSKEY && same_name(&set.name, zone)) else {
+            return ZoneKeys::Untrusted(DnssecStatus::Bogus);
+        };
+        let keys: Vec<DnsDNSKEYRecord> = set.records.iter().filter_map(|rr| match rr.record() {
+            DnsResourceRecordEnum::DNSKEY(key) => Some(key.clone()),
+            _ => None
+        }).collect();
+        let sigs = rrsigs(r.answers(), zone, DnsQType::DNSKEY);
+
+        for key in &keys {
+            let matches_ds = usable.iter().any(|ds| {
+                ds.key_tag() == key.key_tag() && ds.algorithm() == key.algorithm()
+                    && make_ds(zone, key, ds.digest_type()).is_ok_and(|made| made.digest() == ds.digest())
+            });
+            let signs_set = sigs.iter().any(|sig| {
+                same_name(sig.signer(), zone) && self.verify_rrset(set, sig, std::slice::from_ref(key))
+            });
+            if matches_ds && signs_set {
+                return ZoneKeys::Trusted(keys);
+            }
+        }
+        ZoneKeys::Untrusted(DnssecStatus::Bogus)
+    }
+
+    /* the NSEC and NSEC3 records in authorities that are zone's, and whose signatures check out,
+       with their owners. records from any other zone can't speak for names in this one, so
+       they're left out - whether they check out or not. */
+    #[allow(clippy::type_complexity)]
+    fn proof_records<'a>(&mut self, zone: &str, authorities: &'a [DnsResourceRecord])
+        -> (Vec<(&'a str, &'a DnsNSECRecord)>, Vec<(&'a str, &'a DnsNSEC3Record)>) {
+        let mut nsecs = Vec::new();
+        let mut nsec3s = Vec::new();
+        for set in rrsets(authorities) {
+            if set.rtype != DnsQType::NSEC && set.rtype != DnsQType::NSEC3 {
+                continue;
+            }
+            if !self.rrset_zone(&set).is_some_and(|z| same_name(&z, zone))
+                || self.rrset(&set, authorities, &[]) != DnssecStatus::Secure {
+                continue;
+            }
+            for rr in set.records {
+                match rr.record() {
+                    DnsResourceRecordEnum::NSEC(nsec) => nsecs.push((rr.name(), nsec)),
+                    DnsResourceRecordEnum::NSEC3(nsec3) => nsec3s.push((rr.name(), nsec3)),
+                    _ => {}
+                }
+            }
+        }
+        (nsecs, nsec3s)
+    }
+
+    // the zone whose NSEC or NSEC3 records can prove there's no qtype at name: for a DS, the parent's.
+    fn denial_zone(&mut self, name: &str, qtype: DnsQType) -> Option<String> {
+        match qtype {
+            DnsQType::DS => self.zone_of(&ancestor(name, labels(name).len().saturating_sub(1))),
+            _ => self.zone_of(name)
+        }
+    }
+
+    /* prove there's no qtype at name - or no name at all, if nxdomain. only the zone name is in
+       can do that, and only if it's signed; if it isn't, there's nothing to prove. */
+    fn denial(&mut self, name: &str, qtype: DnsQType, authorities: &[DnsResourceRecord], nxdomain: bool)
+        -> DnssecStatus {
+        let Some(zone) = self.denial_zone(name, qtype) else { return DnssecStatus::Indeterminate; };
+        if let ZoneKeys::Untrusted(status) = self.zone_keys(&zone) {
+            return status;
+        }
+        let (nsecs, nsec3s) = self.proof_records(&zone, authorities);
+        let denial = if !nsec3s.is_empty() {
+            nsec3_denial(&nsec3s, name, qtype, nxdomain)
+        } else if !nsecs.is_empty() {
+            nsec_denial(&nsecs, name, qtype, nxdomain)
+        } else {
+            // the zone is signed, so there should have been something to prove it with.
+            Denial::Unproven
+        };
+        match denial {
+            Denial::Proven => DnssecStatus::Secure,
+            Denial::Insecure => DnssecStatus::Insecure,
+            Denial::Unproven => DnssecStatus::Bogus
+        }
+    }
+
+    // is there an NSEC or NSEC3 in authorities from zone's parent, for a delegation to zone with no DS?
+    fn insecure_delegation(&mut self, zone: &str, authorities: &[DnsResourceRecord]) -> bool {
+        let Some(parent) = self.denial_zone(zone, DnsQType::DS) else { return false; };
+        let (nsecs, nsec3s) = self.proof_records(&parent, authorities);
+        let delegation = |types: &[DnsQType]| {
+            types.contains(&DnsQType::NS) && !types.contains(&DnsQType::DS) && !types.contains(&DnsQType::SOA)
+        };
+        if let Some((_, nsec)) = nsecs.iter().find(|(owner, _)| same_name(owner, zone)) {
+            return delegation(nsec.types());
+        }
+        nsec3s.iter().any(|(owner, nsec3)| {
+            owner_hash(owner) == nsec3_hash(zone, nsec3.salt(), nsec3.iterations()) && delegation(nsec3.types())
+        })
+    }
+
+    // proof from zone that an answer expanded from the wildcard with the given labels had nothing closer to match.
+    fn wildcard_proof(&mut self, zone: &str, name: &str, labels: usize, authorities: &[DnsResourceRecord]) -> bool {
+        let (nsecs, nsec3s) = self.proof_records(zone, authorities);
+        if !nsecs.is_empty() {
+            return nsecs.iter().any(|(owner, nsec)| nsec_covers(owner, nsec.next(), name));
+        }
+        // with NSEC3, the next closer name is covered (rfc5155, section 8.8).
+        let next_closer = ancestor(name, labels + 1);
+        nsec3s.iter().any(|(owner, nsec3)| {
+            let hash = nsec3_hash(&next_closer, nsec3.salt(), nsec3.iterations());
+            nsec3_covers(&owner_hash(owner), &base32hex(nsec3.next_hashed_owner()), &hash)
+        })
+    }
+}
+
+// types present at a name can't be denied, and neither can anything if there's a CNAME.
+fn types_deny(types: &[DnsQType], qtype: DnsQType) -> bool {
+    if types.contains(&qtype) || types.contains(&DnsQType::CNAME) {
+        return false;
+    }
+    /* at a zone cut the parent's NSEC has NS but not SOA: it can only speak for the DS, and
+       the child's has SOA, so can't (rfc4035, section 5.4, and rfc6840, section 4.1). */
+    let parent_side = types.contains(&DnsQType::NS) && !types.contains(&DnsQType::SOA);
+    match qtype {
+        DnsQType::DS => !types.contains(&DnsQType::SOA),
+        _ => !parent_side
+    }
+}
+
+// rfc4035, section 5.4.
+fn nsec_denial(nsecs: &[(&str, &DnsNSECRecord)], name: &str, qtype: DnsQType, nxdomain: bool) -> Denial {
+    if !nxdomain {
+        if let Some((_, nsec)) = nsecs.iter().find(|(owner, _)| same_name(owner, name)) {
+            return if types_deny(nsec.types(), qtype) { Denial::Proven } else { Denial::Unproven };
+        }
+    }
+
+    // the name doesn't exist: an NSEC covers it, and another the wildcard that could have matched it.
+    let Some((owner, nsec)) = nsecs.iter().find(|(owner, nsec)| nsec_covers(owner, nsec.next(), name)) else {
+        return Denial::Unproven;
+    };
+    let common = |other: &str| {
+        let (a, b) = (labels(name), labels(other));
+        a.iter().rev().zip(b.iter().rev()).take_while(|(x, y)| x == y).count()
+    };
+    let closest_encloser = ancestor(name, common(owner).max(common(nsec.next())));
+    let wildcard = wildcard(&closest_encloser);
+    if nsecs.iter().any(|(owner, nsec)| nsec_covers(owner, nsec.next(), &wildcard)) {
+        return Denial::Proven;
+    }
+    // or for NODATA, the wildcard exists but doesn't have the type.
+    if !nxdomain {
+        if let Some((_, nsec)) = nsecs.iter().find(|(owner, _)| same_name(owner, &wildcard)) {
+            if types_deny(nsec.types(), qtype) {
+                return Denial::Proven;
+            }
+        }
+    }
+    Denial::Unproven
+}
+
+// the hash an NSEC3 is for is the first label of its owner.
+fn owner_hash(owner: &str) -> String {
+    labels(owner).into_iter().next().unwrap_or_default().to_ascii_uppercase()
+}
+
+// rfc5155, section 8.
+fn nsec3_denial(nsec3s: &[(&str, &DnsNSEC3Record)], name: &str, qtype: DnsQType, nxdomain: bool) -> Denial {
+    // only SHA-1 is defined; anything else, or too much work to check, is as good as unsigned.
+    let (first_owner, first) = nsec3s[0];
+    if first.hash_algorithm() != 1 || first.iterations() > MAX_NSEC3_ITERATIONS {
+        return Denial::Insecure;
+    }
+    let zone = ancestor(first_owner, labels(first_owner).len().saturating_sub(1));
+    let records: Vec<(String, &DnsNSEC3Record)> = nsec3s.iter()
+        .filter(|(owner, nsec3)| {
+            same_name(&ancestor(owner, labels(owner).len().saturating_sub(1)), &zone)
+                && nsec3.hash_algorithm() == first.hash_algorithm() && nsec3.iterations() == first.iterations()
+                && nsec3.salt() == first.salt()
+        })
+        .map(|(owner, nsec3)| (owner_hash(owner), *nsec3))
+        .collect();
+    if !is_subdomain(name, &zone) {
+        return Denial::Unproven;
+    }
+
+    let hash = |n: &str| nsec3_hash(n, first.salt(), first.iterations());
+    let matching = |n: &str| {
+        let h = hash(n);
+        records.iter().find(|(owner, _)| *owner == h).map(|(_, nsec3)| *nsec3)
+    };
+    let covering = |n: &str| {
+        let h = hash(n);
+        records.iter().find(|(owner, nsec3)| nsec3_covers(owner, &base32hex(nsec3.next_hashed_owner()), &h))
+            .map(|(_, nsec3)| *nsec3)
+    };
+
+    if !nxdomain {
+        if let Some(nsec3) = matching(name) {
+            return if types_deny(nsec3.types(), qtype) { Denial::Proven } else { Denial::Unproven };
+        }
+    }
+
+    // the closest encloser proof (section 8.3): the closest ancestor that exists, and the next one down that doesn't.
+    let count = labels(name).len();
+    let closest = (labels(&zone).len() .. count).rev().find(|n| matching(&ancestor(name, *n)).is_some());
+    let Some(closest) = closest else { return Denial::Unproven; };
+    let closest_encloser = ancestor(name, closest);
+    let Some(next_closer) = covering(&ancestor(name, closest + 1)) else { return Denial::Unproven; };
+
+    // a DS for an opt-out delegation (section 8.6), or anything under one, may just be unsigned.
+    if next_closer.opt_out() {
+        return Denial::Insecure;
+    }
+
+    let wildcard = wildcard(&closest_encloser);
+    if nxdomain {
+        return if covering(&wildcard).is_some() { Denial::Proven } else { Denial::Unproven };
+    }
+    // NODATA from a wildcard (section 8.7).
+    match matching(&wildcard) {
+        Some(nsec3) if types_deny(nsec3.types(), qtype) => Denial::Proven,
+        _ => Denial::Unproven
+    }
+}
@@ -0,0 +1,438 @@
+/* a borrowed view of a message on the wire. nothing is copied or allocated until it's asked
+   for: sections are walked lazily as they're iterated, and names are left where they are in
+   the buffer, with compression pointers followed only when the labels are read. this is
+   what the owned types (DnsQuery, DnsResponse and the records) are parsed with, and it can
+   be used directly to pick through lots of messages (say, from a capture) cheaply. */
+
+use std::fmt;
+use super::*;
+
+// the four sections of a message, rfc1035 section 4.1.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum DnsSection {
+    QUESTION,
+    ANSWER,
+    AUTHORITY,
+    ADDITIONAL
+}
+
+impl fmt::Display for DnsSection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            DnsSection::QUESTION => "Question",
+            DnsSection::ANSWER => "Answer",
+            DnsSection::AUTHORITY => "Authority",
+            DnsSection::ADDITIONAL => "Additional"
+        };
+        write!(f, "{s}")
+    }
+}
+
+// one step along a name: a label, the end of the name, or a pointer to the rest of it.
+enum NameStep<'a> {
+    Label(&'a [u8]),
+    End,
+    Pointer(usize)
+}
+
+/* read whatever is at o in a name, start being where the current run of labels began.
+   pointers have to point back before start - that's what stops pointer loops. */
+fn name_step(buf: &[u8], o: usize, start: usize) -> Result<NameStep<'_>, DnsError> {
+    check_bounds(buf, o, 1, "name length byte")?;
+    let lenbyte = buf[o];
+    match lenbyte {
+        0 => Ok(NameStep::End),
+        1..=63 => {
+            check_bounds(buf, o + 1, lenbyte as usize, "name label")?;
+            Ok(NameStep::Label(&buf[o + 1 .. o + 1 + lenbyte as usize]))
+        },
+        64..=191 => Err(DnsError::BadLabel { offset: o, lenbyte }),
+        192..=255 => {
+            check_bounds(buf, o, 2, "name compression pointer")?;
+            let target = (u16::from_be_bytes([buf[o], buf[o+1]]) & 0x3FFF) as usize;
+            if target >= start {
+                return Err(DnsError::BadPointer { offset: o, target });
+            }
+            Ok(NameStep::Pointer(target))
+        }
+    }
+}
+
+/* a name in a message, left in the buffer. only the part of the name at offset is checked
+   when it's made; anything behind a compression pointer is checked as it's read. */
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct DnsNameRef<'a> {
+    buf: &'a [u8],
+    offset: usize,
+    wire_len: usize
+}
+
+impl<'a> DnsNameRef<'a> {
+    pub fn parse(buf: &'a [u8], offset: usize) -> Result<DnsNameRef<'a>, DnsError> {
+        let mut o = offset;
+        loop {
+            match name_step(buf, o, offset)? {
+                NameStep::Label(label) => o += 1 + label.len(),
+                NameStep::End => { o += 1; break; },
+                NameStep::Pointer(_) => { o += 2; break; }
+            }
+        }
+        Ok(DnsNameRef { buf, offset, wire_len: o - offset })
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // bytes the name takes up where it is - not counting anything behind a pointer.
+    pub fn wire_len(&self) -> usize {
+        self.wire_len
+    }
+
+    // the raw labels of the name, following compression pointers as they come up.
+    pub fn labels(&self) -> DnsLabels<'a> {
+        DnsLabels { buf: self.buf, o: self.offset, start: self.offset, done: false }
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.buf[self.offset] == 0
+    }
+
+    // the name as a string, in the same form as dns_name_to_string gives.
+    pub fn to_name(&self) -> Result<String, DnsError> {
+        let mut ret = String::new();
+        let mut labels = self.labels();
+        while let Some(label) = labels.next() {
+            let label = label?;
+            match std::str::from_utf8(label) {
+                Ok(s) => ret.push_str(s),
+                Err(_) => return Err(DnsError::InvalidUtf8 { context: "name label",
+                                                             offset: labels.o - label.len() })
+            }
+            ret.push('.');
+        }
+        if ret.is_empty() {
+            ret.push('.');
+        }
+        Ok(ret)
+    }
+
+    /* does this name match name, ignoring case (rfc4343) and any trailing '.'? doesn't
+       allocate, so it's cheap to use for filtering. */
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.trim().trim_end_matches('.');
+        let mut rest = name.as_bytes();
+        for label in self.labels() {
+            let Ok(label) = label else { return false; };
+            if rest.len() < label.len() || !rest[.. label.len()].eq_ignore_ascii_case(label) {
+                return false;
+            }
+            rest = &rest[label.len() ..];
+            match rest.first() {
+                Some(b'.') => rest = &rest[1 ..],
+                Some(_) => return false,
+                None => {}
+            }
+        }
+        rest.is_empty()
+    }
+}
+
+pub struct DnsLabels<'a> {
+    buf: &'a [u8],
+    o: usize,
+    start: usize,
+    done: bool
+}
+
+impl<'a> Iterator for DnsLabels<'a> {
+    type Item = Result<&'a [u8], DnsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match name_step(self.buf, self.o, self.start) {
+                Ok(NameStep::Label(label)) => {
+                    self.o += 1 + label.len();
+                    return Some(Ok(label));
+                },
+                Ok(NameStep::End) => self.done = true,
+                Ok(NameStep::Pointer(target)) => {
+                    self.o = target;
+                    self.start = target;
+                },
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct DnsQuestionView<'a> {
+    name: DnsNameRef<'a>,
+    qtype: u16,
+    qclass: u16
+}
+
+impl<'a> DnsQuestionView<'a> {
+    pub fn parse(buf: &'a [u8], offset: usize) -> Result<DnsQuestionView<'a>, DnsError> {
+        let name = DnsNameRef::parse(buf, offset)?;
+        let o = offset + name.wire_len();
+        check_bounds(buf, o, 4, "question record qtype/class")?;
+        Ok(DnsQuestionView { name,
+                             qtype: u16::from_be_bytes([buf[o], buf[o+1]]),
+                             qclass: u16::from_be_bytes([buf[o+2], buf[o+3]]) })
+    }
+
+    pub fn name(&self) -> DnsNameRef<'a> {
+        self.name
+    }
+
+    pub fn qtype(&self) -> DnsQType {
+        DnsQType::from_u16(self.qtype)
+    }
+
+    pub fn qclass(&self) -> DnsQClass {
+        DnsQClass::from_u16(self.qclass)
+    }
+
+    pub fn wire_len(&self) -> usize {
+        self.name.wire_len() + 4
+    }
+
+    pub fn to_record(&self) -> Result<DnsQuestionRecord, DnsError> {
+        Ok(DnsQuestionRecord::new(self.name.to_name()?, self.qtype(), self.qclass()))
+    }
+}
+
+/* a resource record, with its rdata left in the buffer. the rdata is only bounds checked
+   here - it isn't parsed until to_record. */
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct DnsRecordView<'a> {
+    buf: &'a [u8],
+    name: DnsNameRef<'a>,
+    rtype: u16,
+    class: u16,
+    ttl: u32,
+    rdata_offset: usize,
+    rdlen: usize
+}
+
+impl<'a> DnsRecordView<'a> {
+    pub fn parse(buf: &'a [u8], offset: usize) -> Result<DnsRecordView<'a>, DnsError> {
+        check_bounds(buf, offset, 1, "resource record")?;
+        let name = DnsNameRef::parse(buf, offset)?;
+        let o = offset + name.wire_len();
+
+        // type, class, ttl, rdlen
+        check_bounds(buf, o, 10, "resource record type/class/ttl/rdlen")?;
+        let rtype = u16::from_be_bytes([buf[o], buf[o+1]]);
+        let class = u16::from_be_bytes([buf[o+2], buf[o+3]]);
+        let ttl = u32::from_be_bytes([buf[o+4], buf[o+5], buf[o+6], buf[o+7]]);
+        let rdlen = u16::from_be_bytes([buf[o+8], buf[o+9]]) as usize;
+        check_bounds(buf, o + 10, rdlen, "resource record rdata")?;
+
+        Ok(DnsRecordView { buf, name, rtype, class, ttl, rdata_offset: o + 10, rdlen })
+    }
+
+    pub fn name(&self) -> DnsNameRef<'a> {
+        self.name
+    }
+
+    pub fn rtype(&self) -> DnsQType {
+        DnsQType::from_u16(self.rtype)
+    }
+
+    pub fn class(&self) -> DnsQClass {
+        DnsQClass::from_u16(self.class)
+    }
+
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    /* the rdata as it is on the wire. names in it may be compressed, pointing elsewhere in
+       the message - use rdata_offset with the whole buffer to follow them. */
+    pub fn rdata(&self) -> &'a [u8] {
+        &self.buf[self.rdata_offset .. self.rdata_offset + self.rdlen]
+    }
+
+    pub fn rdata_offset(&self) -> usize {
+        self.rdata_offset
+    }
+
+    pub fn wire_len(&self) -> usize {
+        self.rdata_offset + self.rdlen - self.name.offset()
+    }
+
+    pub fn to_record(&self) -> Result<DnsResourceRecord, DnsError> {
+        let record = rdata_from_bytes(self.buf, self.rdata_offset, self.rtype, self.rdlen)?;
+        Ok(DnsResourceRecord::new(self.name.to_name()?, self.rtype(), self.class(), self.ttl,
+                                  record))
+    }
+}
+
+// a message header and section counts, with the sections left to be walked on demand.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct DnsMessageView<'a> {
+    buf: &'a [u8],
+    offset: usize,
+    header: DnsHeader,
+    counts: [u16; 4]
+}
+
+impl<'a> DnsMessageView<'a> {
+    pub fn new(buf: &'a [u8], offset: usize) -> Result<DnsMessageView<'a>, DnsError> {
+        DnsMessageView::parse(buf, offset, "DNS message")
+    }
+
+    // what is only used to make errors say what we were parsing.
+    pub(crate) fn parse(buf: &'a [u8], offset: usize, what: &'static str)
+        -> Result<DnsMessageView<'a>, DnsError> {
+        check_bounds(buf, offset, 12, what)?;
+        let header = DnsHeader::from_bytes(buf, offset)?;
+        let mut counts = [0u16; 4];
+        for (i, count) in counts.iter_mut().enumerate() {
+            let o = offset + 4 + 2 * i;
+            *count = u16::from_be_bytes([buf[o], buf[o+1]]);
+        }
+        Ok(DnsMessageView { buf, offset, header, counts })
+    }
+
+    pub fn header(&self) -> &DnsHeader {
+        &self.header
+    }
+
+    // the record count the header gives for section.
+    pub fn count(&self, section: DnsSection) -> u16 {
+        self.counts[section as usize]
+    }
+
+    pub fn questions(&self) -> DnsQuestions<'a> {
+        DnsQuestions { buf: self.buf, o: self.offset + 12, remaining: self.counts[0], done: false }
+    }
+
+    // every resource record in the message, answers then authorities then additionals.
+    pub fn records(&self) -> DnsRecords<'a> {
+        DnsRecords { buf: self.buf, o: self.offset + 12, skip_questions: self.counts[0],
+                     skip_records: 0,
+                     remaining: [self.counts[1], self.counts[2], self.counts[3]], done: false }
+    }
+
+    pub fn answers(&self) -> DnsRecords<'a> {
+        self.section(DnsSection::ANSWER)
+    }
+
+    pub fn authorities(&self) -> DnsRecords<'a> {
+        self.section(DnsSection::AUTHORITY)
+    }
+
+    pub fn additionals(&self) -> DnsRecords<'a> {
+        self.section(DnsSection::ADDITIONAL)
+    }
+
+    /* the records of one section. the sections before it have to be walked to find where
+       it starts, which happens on the first call to next. */
+    fn section(&self, section: DnsSection) -> DnsRecords<'a> {
+        let mut records = self.records();
+        let index = section as usize - 1;
+        for i in 0..3 {
+            if i < index {
+                records.skip_records += records.remaining[i] as u32;
+                records.remaining[i] = 0;
+            } else if i > index {
+                records.remaining[i] = 0;
+            }
+        }
+        records
+    }
+}
+
+pub struct DnsQuestions<'a> {
+    buf: &'a [u8],
+    o: usize,
+    remaining: u16,
+    done: bool
+}
+
+impl<'a> Iterator for DnsQuestions<'a> {
+    type Item = Result<DnsQuestionView<'a>, DnsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.remaining == 0 {
+            return None;
+        }
+        match DnsQuestionView::parse(self.buf, self.o) {
+            Ok(q) => {
+                self.o += q.wire_len();
+                self.remaining -= 1;
+                Some(Ok(q))
+            },
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+// iterating stops after the first error, since there's no telling where the next record is.
+pub struct DnsRecords<'a> {
+    buf: &'a [u8],
+    o: usize,
+    skip_questions: u16,
+    skip_records: u32,
+    remaining: [u16; 3],
+    done: bool
+}
+
+impl<'a> DnsRecords<'a> {
+    // the section of the record the next call to next will give.
+    fn advance_section(&mut self) -> Option<DnsSection> {
+        for (i, section) in [DnsSection::ANSWER, DnsSection::AUTHORITY, DnsSection::ADDITIONAL]
+            .into_iter().enumerate() {
+            if self.remaining[i] > 0 {
+                self.remaining[i] -= 1;
+                return Some(section);
+            }
+        }
+        None
+    }
+
+    fn skip(&mut self) -> Result<(), DnsError> {
+        while self.skip_questions > 0 {
+            self.o += DnsQuestionView::parse(self.buf, self.o)?.wire_len();
+            self.skip_questions -= 1;
+        }
+        while self.skip_records > 0 {
+            self.o += DnsRecordView::parse(self.buf, self.o)?.wire_len();
+            self.skip_records -= 1;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for DnsRecords<'a> {
+    type Item = Result<(DnsSection, DnsRecordView<'a>), DnsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let section = self.advance_section()?;
+        let result = self.skip().and_then(|_| DnsRecordView::parse(self.buf, self.o));
+        match result {
+            Ok(rr) => {
+                self.o += rr.wire_len();
+                Some(Ok((section, rr)))
+            },
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
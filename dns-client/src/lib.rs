@@ -1,16 +1,168 @@
 pub mod dns_client_lib {
-    use std::net::{IpAddr,Ipv4Addr,Ipv6Addr};
+    pub mod transport;
+    pub mod client;
+    pub mod bulk;
+    pub mod view;
+    pub mod rdata;
+    pub mod dnssec;
+    #[cfg(feature = "dnssec")]
+    pub mod validator;
+    #[cfg(feature = "dot")]
+    pub mod tls;
+    #[cfg(feature = "doh")]
+    pub mod https;
+    #[cfg(feature = "doq")]
+    pub mod quic;
+    #[cfg(feature = "odoh")]
+    pub mod hpke;
+    #[cfg(feature = "odoh")]
+    pub mod odoh;
+    #[cfg(feature = "async")]
+    pub mod async_client;
+
+    use std::collections::HashMap;
+    use std::net::{IpAddr,Ipv4Addr,Ipv6Addr,SocketAddr};
     use std::fmt;
     use std::str::FromStr;
+    use std::time::Duration;
     use cidr_utils::cidr::IpCidr;
     use cidr_utils::cidr::IpCidr::{V4,V6};
+    use rand::Rng;
+    use rdata::{AnyRData, RData};
+    use dnssec::{DnsDNSKEYRecord, DnsDSRecord, DnsNSEC3PARAMRecord, DnsNSEC3Record, DnsNSECRecord, DnsRRSIGRecord};
+    use view::{DnsMessageView, DnsNameRef, DnsQuestionView, DnsRecordView, DnsSection};
 
     /* pages used to construct this library:
        https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml
        https://datatracker.ietf.org/doc/html/rfc1035
        https://www.rfc-editor.org/rfc/rfc6895.html
+       https://datatracker.ietf.org/doc/html/rfc4034
        */
 
+    /* everything that can go wrong in this library. errors from parsing the wire format say
+       what was being read and where in the buffer, so a caller can tell a truncated message
+       from a malformed one without picking apart a message string. */
+    #[derive(Debug, Eq, PartialEq, Clone)]
+    pub enum DnsError {
+        // ran off the end of the buffer (len bytes long) reading needed bytes of context at offset.
+        Truncated { context: &'static str, offset: usize, needed: usize, len: usize },
+        // a name length byte with 10 or 01 in its top two bits. see rfc1035 section 4.1.4 and rfc6891 section 5.
+        BadLabel { offset: usize, lenbyte: u8 },
+        // a compression pointer at offset that doesn't point back before the name it's in, to target.
+        BadPointer { offset: usize, target: usize },
+        InvalidUtf8 { context: &'static str, offset: usize },
+        // a field of len bytes (or records) that only has room for max.
+        Oversize { context: &'static str, len: usize, max: usize },
+        // a name that doesn't follow the rules of rfc1035 section 2.3.1, and which rule it breaks.
+        InvalidName { name: String, reason: String },
+        UnknownQType(String),
+        UnknownQClass(String),
+        // a record that's all there, but doesn't hang together.
+        BadRecord { context: &'static str, offset: usize, reason: &'static str },
+        // a message that isn't a response to our query. see DnsQuery::check_response.
+        NotResponse,
+        IdMismatch { got: u16, expected: u16 },
+        QuestionCount { got: u16, expected: u16 },
+        QuestionMismatch { got: String, expected: String },
+        NoServers,
+        Timeout(Duration),
+        // what went wrong talking to one particular server.
+        Server { server: SocketAddr, error: Box<DnsError> },
+        /* an i/o error from a socket or stream: what we were doing, the io::ErrorKind, and the
+           error's own message. the kind is kept so a caller can tell a reset from a refusal
+           without picking apart the message. */
+        Io { context: String, kind: std::io::ErrorKind, error: String },
+        // sockets, tls, http and quic: anything else that goes wrong getting bytes to and from a server.
+        Transport(String),
+        // odoh's encryption, and the formats of the keys and messages that go with it.
+        Crypto(String),
+        // bad settings or input: server addresses, urls, pins, subnets and the like.
+        Config(String)
+    }
+
+    impl DnsError {
+        // an Io error for e, which happened while doing context.
+        pub fn io(context: impl Into<String>, e: &std::io::Error) -> DnsError {
+            DnsError::Io { context: context.into(), kind: e.kind(), error: e.to_string() }
+        }
+
+        /* a timeout, whether or not it's wrapped up with the server that timed out. a socket
+           read that runs past its timeout gives WouldBlock or TimedOut, depending on the os. */
+        pub fn is_timeout(&self) -> bool {
+            match self {
+                DnsError::Timeout(_) => true,
+                DnsError::Io { kind, .. } =>
+                    matches!(kind, std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut),
+                DnsError::Server { error, .. } => error.is_timeout(),
+                _ => false
+            }
+        }
+
+        // the io::ErrorKind of an Io error, whether or not it's wrapped up with the server it came from.
+        pub fn io_kind(&self) -> Option<std::io::ErrorKind> {
+            match self {
+                DnsError::Io { kind, .. } => Some(*kind),
+                DnsError::Server { error, .. } => error.io_kind(),
+                _ => None
+            }
+        }
+    }
+
+    impl fmt::Display for DnsError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                DnsError::Truncated { context, offset, needed, len } =>
+                    write!(f, "Hit buffer bounds reading {context}: needed {needed} bytes at offset {offset} \
+                               of a {len} byte buffer."),
+                DnsError::BadLabel { offset, lenbyte } =>
+                    write!(f, "Got 10/01 in top bits of dns name length byte {lenbyte:#04x} at offset {offset}."),
+                DnsError::BadPointer { offset, target } =>
+                    write!(f, "Got a compression pointer at offset {offset} to {target}, which isn't \
+                               before the name it's in."),
+                DnsError::InvalidUtf8 { context, offset } =>
+                    write!(f, "Got invalid UTF-8 in {context} at offset {offset}."),
+                DnsError::Oversize { context, len, max } =>
+                    write!(f, "Got a {context} of {len}, more than the {max} that fit."),
+                DnsError::InvalidName { name, reason } =>
+                    write!(f, "'{name}' doesn't appear to be a valid DNS name: {reason}"),
+                DnsError::UnknownQType(t) => write!(f, "Unknown query type {t}"),
+                DnsError::UnknownQClass(c) => write!(f, "Unknown query class {c}"),
+                DnsError::BadRecord { context, offset, reason } =>
+                    write!(f, "Got a bad {context} at offset {offset}: {reason}"),
+                DnsError::NotResponse => write!(f, "Got a packet without the QR bit set."),
+                DnsError::IdMismatch { got, expected } =>
+                    write!(f, "Got a response with QID {got:x}, expected {expected:x}."),
+                DnsError::QuestionCount { got, expected } =>
+                    write!(f, "Got a response with {got} questions, expected {expected}."),
+                DnsError::QuestionMismatch { got, expected } =>
+                    write!(f, "Got a response to question '{got}', expected '{expected}'."),
+                DnsError::NoServers => write!(f, "No servers configured to send query to."),
+                DnsError::Timeout(timeout) => write!(f, "Timed out after {timeout:?} waiting for response."),
+                DnsError::Server { server, error } => write!(f, "{server}: {error}"),
+                DnsError::Io { context, error, .. } => write!(f, "{context}: {error}"),
+                DnsError::Transport(e) | DnsError::Crypto(e) | DnsError::Config(e) => write!(f, "{e}")
+            }
+        }
+    }
+
+    impl std::error::Error for DnsError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                DnsError::Server { error, .. } => Some(error.as_ref()),
+                _ => None
+            }
+        }
+    }
+
+    /* check there are needed bytes of buf from offset on, for reading context. every
+       from_bytes starts with this. */
+    fn check_bounds(buf: &[u8], offset: usize, needed: usize, context: &'static str) -> Result<(), DnsError> {
+        match offset.checked_add(needed) {
+            Some(end) if end <= buf.len() => Ok(()),
+            _ => Err(DnsError::Truncated { context, offset, needed, len: buf.len() })
+        }
+    }
+
     #[derive(Debug, Eq, PartialEq, Copy, Clone)]
     pub enum DnsOpcode {
         QUERY = 0,
@@ -50,31 +202,44 @@ pub mod dns_client_lib {
         }
     }
 
+    /* rcodes past 15 need more than the header's 4 bits. with EDNS, the OPT RR carries the
+       upper 8 bits of a 12-bit rcode (rfc6891, section 6.1.3) - see DnsEdns and
+       DnsResponse::rcode. TSIG has a full 16-bit error field of its own (rfc8945, section
+       4.2). the values are all from the one iana registry - see rfc6895, section 2.3. */
     #[derive(Debug, Eq, PartialEq, Copy, Clone)]
     pub enum DnsRcode {
-        NOERROR = 0,
-        FORMERR = 1,
-        SERVFAIL = 2,
-        NAMERR = 3,
-        NOTIMP = 4,
-        REFUSED = 5,
-        YXDOMAIN = 6,
-        YXRRSET = 7,
-        NXRRSET = 8,
-        NOTAUTH = 9,
-        NOTZONE = 10,
-        RESERVED // 11-15
-        /*
-          there's lots of other rcodes. see rfc6895, section 2.3.
-          basically, there are RRs for which these rcodes also have meaning,
-          but they have more than 4 bits to store an rcode, so those other
-          bits are used with type-specific meaning
-         */
-
+        NOERROR,
+        FORMERR,
+        SERVFAIL,
+        NAMERR,
+        NOTIMP,
+        REFUSED,
+        YXDOMAIN,
+        YXRRSET,
+        NXRRSET,
+        NOTAUTH,
+        NOTZONE,
+        DSOTYPENI, // rfc8490
+        BADVERS, // rfc6891. also BADSIG in a TSIG record, see below.
+        BADKEY, // rfc8945
+        BADTIME, // rfc8945
+        BADMODE, // rfc2930
+        BADNAME, // rfc2930
+        BADALG, // rfc2930
+        BADTRUNC, // rfc8945
+        BADCOOKIE, // rfc7873
+        RESERVED(u16) // catch-all
     }
 
     impl DnsRcode {
+        // 16 is BADVERS in an OPT RR, and BADSIG in a TSIG record (rfc8945, section 5.3.2).
+        pub const BADSIG: DnsRcode = DnsRcode::BADVERS;
+
         pub fn from_u8(value: u8) -> DnsRcode {
+            DnsRcode::from_u16(value as u16)
+        }
+
+        pub fn from_u16(value: u16) -> DnsRcode {
             match value {
                 0 => DnsRcode::NOERROR,
                 1 => DnsRcode::FORMERR,
@@ -87,7 +252,42 @@ pub mod dns_client_lib {
                 8 => DnsRcode::NXRRSET,
                 9 => DnsRcode::NOTAUTH,
                 10 => DnsRcode::NOTZONE,
-                _ => DnsRcode::RESERVED
+                11 => DnsRcode::DSOTYPENI,
+                16 => DnsRcode::BADVERS,
+                17 => DnsRcode::BADKEY,
+                18 => DnsRcode::BADTIME,
+                19 => DnsRcode::BADMODE,
+                20 => DnsRcode::BADNAME,
+                21 => DnsRcode::BADALG,
+                22 => DnsRcode::BADTRUNC,
+                23 => DnsRcode::BADCOOKIE,
+                other => DnsRcode::RESERVED(other)
+            }
+        }
+
+        pub fn to_u16(&self) -> u16 {
+            match self {
+                DnsRcode::NOERROR => 0,
+                DnsRcode::FORMERR => 1,
+                DnsRcode::SERVFAIL => 2,
+                DnsRcode::NAMERR => 3,
+                DnsRcode::NOTIMP => 4,
+                DnsRcode::REFUSED => 5,
+                DnsRcode::YXDOMAIN => 6,
+                DnsRcode::YXRRSET => 7,
+                DnsRcode::NXRRSET => 8,
+                DnsRcode::NOTAUTH => 9,
+                DnsRcode::NOTZONE => 10,
+                DnsRcode::DSOTYPENI => 11,
+                DnsRcode::BADVERS => 16,
+                DnsRcode::BADKEY => 17,
+                DnsRcode::BADTIME => 18,
+                DnsRcode::BADMODE => 19,
+                DnsRcode::BADNAME => 20,
+                DnsRcode::BADALG => 21,
+                DnsRcode::BADTRUNC => 22,
+                DnsRcode::BADCOOKIE => 23,
+                DnsRcode::RESERVED(o) => *o
             }
         }
     }
@@ -106,18 +306,27 @@ pub mod dns_client_lib {
                 DnsRcode::NXRRSET => write!(f, "NXRRSET"),
                 DnsRcode::NOTAUTH => write!(f, "NOTAUTH"),
                 DnsRcode::NOTZONE => write!(f, "NOTZONE"),
-                DnsRcode::RESERVED => write!(f, "RESERVED")
-            }
-        }
-    }
-
-    /* to add support for a new qtype, a few things must be done:
-       1) add the new qtype to the DnsQType struct and its functions (to/from_u16, fmt)
-       2) create the new struct associated with the new qtype, and its functions:
-          to/from_bytes, new, fmt
-          see any of the Dns*Record structs for an example of this.
-       3) add an entry to the DnsResourceRecordEnum enum and its fmt function, using the struct from (2)
-       4) add a match arm to DnsResourceRecord::to/from_bytes for the struct from (2)
+                DnsRcode::DSOTYPENI => write!(f, "DSOTYPENI"),
+                DnsRcode::BADVERS => write!(f, "BADVERS"),
+                DnsRcode::BADKEY => write!(f, "BADKEY"),
+                DnsRcode::BADTIME => write!(f, "BADTIME"),
+                DnsRcode::BADMODE => write!(f, "BADMODE"),
+                DnsRcode::BADNAME => write!(f, "BADNAME"),
+                DnsRcode::BADALG => write!(f, "BADALG"),
+                DnsRcode::BADTRUNC => write!(f, "BADTRUNC"),
+                DnsRcode::BADCOOKIE => write!(f, "BADCOOKIE"),
+                DnsRcode::RESERVED(o) => write!(f, "RESERVED({o})")
+            }
+        }
+    }
+
+    /* to add support for a new record type:
+       1) create the struct for it, with new and fmt, and implement rdata::RData for it.
+          see DnsFOORecord below, or any of the Dns*Record structs.
+       2) add it to the rdata_types! list further down.
+       3) if it should be something you can ask for by name, add it to DnsQType too.
+       types from outside this library only need (1), then rdata::register_rdata to have
+       them parsed. they can be asked for by their mnemonic once registered.
      */
     #[derive(Debug, Eq, PartialEq, Copy, Clone)]
     pub enum DnsQType {
@@ -125,10 +334,18 @@ pub mod dns_client_lib {
         NS,
         CNAME,
         SOA,
+        PTR,
         MX,
         TXT,
         AAAA,
+        SRV,
         OPT,
+        DS,
+        RRSIG,
+        NSEC,
+        DNSKEY,
+        NSEC3,
+        NSEC3PARAM,
         ANY,
         CAA,
         RESERVED(u16) // catch-all
@@ -141,10 +358,18 @@ pub mod dns_client_lib {
                 2 => DnsQType::NS,
                 5 => DnsQType::CNAME,
                 6 => DnsQType::SOA,
+                12 => DnsQType::PTR,
                 15 => DnsQType::MX,
                 16 => DnsQType::TXT,
                 28 => DnsQType::AAAA,
+                33 => DnsQType::SRV,
                 41 => DnsQType::OPT,
+                43 => DnsQType::DS,
+                46 => DnsQType::RRSIG,
+                47 => DnsQType::NSEC,
+                48 => DnsQType::DNSKEY,
+                50 => DnsQType::NSEC3,
+                51 => DnsQType::NSEC3PARAM,
                 255 => DnsQType::ANY,
                 257 => DnsQType::CAA,
                 other => DnsQType::RESERVED(other)
@@ -157,29 +382,48 @@ pub mod dns_client_lib {
                 DnsQType::NS => 2,
                 DnsQType::CNAME => 5,
                 DnsQType::SOA => 6,
+                DnsQType::PTR => 12,
                 DnsQType::MX => 15,
                 DnsQType::TXT => 16,
                 DnsQType::AAAA => 28,
+                DnsQType::SRV => 33,
                 DnsQType::OPT => 41,
+                DnsQType::DS => 43,
+                DnsQType::RRSIG => 46,
+                DnsQType::NSEC => 47,
+                DnsQType::DNSKEY => 48,
+                DnsQType::NSEC3 => 50,
+                DnsQType::NSEC3PARAM => 51,
                 DnsQType::ANY => 255,
                 DnsQType::CAA => 257,
                 DnsQType::RESERVED(o) => *o
             }
         }
 
-        pub fn from_string(s: &String) -> Result<DnsQType, String> {
-            match s.as_str() {
+        pub fn from_string(s: &str) -> Result<DnsQType, DnsError> {
+            match s {
                 "A" | "a"  => Ok(DnsQType::A),
                 "NS" | "ns" => Ok(DnsQType::NS),
                 "CNAME" | "cname" => Ok(DnsQType::CNAME),
                 "SOA" | "soa" => Ok(DnsQType::SOA),
+                "PTR" | "ptr" => Ok(DnsQType::PTR),
                 "MX" | "mx" => Ok(DnsQType::MX),
                 "TXT" | "txt" => Ok(DnsQType::TXT),
                 "AAAA" | "aaaa" => Ok(DnsQType::AAAA),
+                "SRV" | "srv" => Ok(DnsQType::SRV),
                 "OPT" | "opt" => Ok(DnsQType::OPT),
+                "DS" | "ds" => Ok(DnsQType::DS),
+                "RRSIG" | "rrsig" => Ok(DnsQType::RRSIG),
+                "NSEC" | "nsec" => Ok(DnsQType::NSEC),
+                "DNSKEY" | "dnskey" => Ok(DnsQType::DNSKEY),
+                "NSEC3" | "nsec3" => Ok(DnsQType::NSEC3),
+                "NSEC3PARAM" | "nsec3param" => Ok(DnsQType::NSEC3PARAM),
                 "ANY" | "any" => Ok(DnsQType::ANY),
                 "CAA" | "caa" => Ok(DnsQType::CAA),
-                unknown => Err(format!("Unknown query type {unknown}"))
+                unknown => match rdata::registered_type(unknown) {
+                    Some(rtype) => Ok(DnsQType::RESERVED(rtype)),
+                    None => Err(DnsError::UnknownQType(unknown.to_string()))
+                }
             }
         }
     }
@@ -191,13 +435,24 @@ pub mod dns_client_lib {
                 DnsQType::NS => write!(f, "NS"),
                 DnsQType::CNAME => write!(f, "CNAME"),
                 DnsQType::SOA => write!(f, "SOA"),
+                DnsQType::PTR => write!(f, "PTR"),
                 DnsQType::MX => write!(f, "MX"),
                 DnsQType::TXT => write!(f, "TXT"),
                 DnsQType::AAAA => write!(f, "AAAA"),
+                DnsQType::SRV => write!(f, "SRV"),
                 DnsQType::OPT => write!(f, "OPT"),
+                DnsQType::DS => write!(f, "DS"),
+                DnsQType::RRSIG => write!(f, "RRSIG"),
+                DnsQType::NSEC => write!(f, "NSEC"),
+                DnsQType::DNSKEY => write!(f, "DNSKEY"),
+                DnsQType::NSEC3 => write!(f, "NSEC3"),
+                DnsQType::NSEC3PARAM => write!(f, "NSEC3PARAM"),
                 DnsQType::ANY => write!(f, "ANY"),
                 DnsQType::CAA => write!(f, "CAA"),
-                DnsQType::RESERVED(_) => write!(f, "RESERVED")
+                DnsQType::RESERVED(rtype) => match rdata::registered_mnemonic(*rtype) {
+                    Some(mnemonic) => write!(f, "{mnemonic}"),
+                    None => write!(f, "RESERVED")
+                }
             }
         }
     }
@@ -236,14 +491,14 @@ pub mod dns_client_lib {
             }
         }
 
-        pub fn from_string(s: &String) -> Result<DnsQClass, String> {
-            match s.as_str() {
+        pub fn from_string(s: &str) -> Result<DnsQClass, DnsError> {
+            match s {
                 "IN" | "in" => Ok(DnsQClass::IN),
                 "CH" | "ch" => Ok(DnsQClass::CH),
                 "HS" | "hs" => Ok(DnsQClass::HS),
                 "NONE" | "none" => Ok(DnsQClass::NONE),
                 "ANY" | "any" => Ok(DnsQClass::ANY),
-                unknown => Err(format!("Unknown query class {unknown}"))
+                unknown => Err(DnsError::UnknownQClass(unknown.to_string()))
             }
         }
     }
@@ -273,7 +528,19 @@ pub mod dns_client_lib {
             DnsQuestionRecord { name: n, qtype: t, qclass: c }
         }
 
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+
+        pub fn qtype(&self) -> DnsQType {
+            self.qtype
+        }
+
+        pub fn qclass(&self) -> DnsQClass {
+            self.qclass
+        }
+
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
             let mut ret: Vec<u8> = Vec::new();
             let mut name_bytes = string_to_dns_name(&self.name)?;
             ret.append(&mut name_bytes); // append consumes the target
@@ -284,30 +551,27 @@ pub mod dns_client_lib {
             Ok(ret)
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<(DnsQuestionRecord, usize), String> {
-            let mut o = offset;
-
-            let name = match dns_name_to_string(buf, offset) {
-                Ok((s, bytes_read)) => {
-                    o += bytes_read;
-                    s
-                },
-                Err(e) => return Err(e)
-            };
-            let buflen = buf.len();
-            if o >= buflen || o+4 > buflen {
-                return Err(String::from("Hit buffer bounds reading qtype/class in QuestionRecord."));
-            }
-            let mut twobytes = [0u8, 0u8];
-            twobytes.clone_from_slice(&buf[o .. o+2]);
-            let qtype = DnsQType::from_u16(u16::from_be_bytes(twobytes));
-            twobytes.clone_from_slice(&buf[o+2 .. o+4]);
-            let qclass = DnsQClass::from_u16(u16::from_be_bytes(twobytes));
-            o += 4;
+        // like to_bytes, but written into a whole message, so the name can be compressed.
+        pub fn write_to(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            w.write_name(&self.name, true)?;
+            w.write_u16(self.qtype.to_u16());
+            w.write_u16(self.qclass.to_u16());
+            Ok(())
+        }
 
-            Ok((DnsQuestionRecord::new(name, qtype, qclass), o - offset))
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<(DnsQuestionRecord, usize), DnsError> {
+            let view = DnsQuestionView::parse(buf, offset)?;
+            Ok((view.to_record()?, view.wire_len()))
         }
 
+        /* is other asking the same question as self? names are compared case-insensitively
+           (see rfc4343, and draft-vixie-dnsext-dns0x20 for why a server might not preserve
+           case), and a trailing '.' is ignored. */
+        pub fn matches(&self, other: &DnsQuestionRecord) -> bool {
+            let a = self.name.trim().trim_end_matches('.');
+            let b = other.name.trim().trim_end_matches('.');
+            a.eq_ignore_ascii_case(b) && self.qtype == other.qtype && self.qclass == other.qclass
+        }
     }
 
     impl fmt::Display for DnsQuestionRecord {
@@ -326,21 +590,12 @@ pub mod dns_client_lib {
             DnsARecord { addr: a }
         }
 
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
             Ok(self.addr.octets().to_vec())
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<DnsARecord, String> {
-            let buflen = buf.len();
-            if buflen == 0 {
-                return Err(String::from("Got a zero-length buffer."));
-            }
-            if offset >= buflen {
-                return Err(String::from("Got an offset outside of the buffer parsing A record."));
-            }
-            if (offset + 4) > buflen {
-                return Err(String::from("Got a buffer with too few bytes to read."));
-            }
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<DnsARecord, DnsError> {
+            check_bounds(buf, offset, 4, "A record")?;
             Ok(DnsARecord::new(Ipv4Addr::new(buf[offset], buf[offset+1], buf[offset+2], buf[offset+3])))
         }
     }
@@ -351,6 +606,20 @@ pub mod dns_client_lib {
         }
     }
 
+    impl RData for DnsARecord {
+        const TYPE: u16 = 1;
+        const MNEMONIC: &'static str = "A";
+
+        fn parse(buf: &[u8], offset: usize, _rdlen: usize) -> Result<Self, DnsError> {
+            DnsARecord::from_bytes(buf, offset)
+        }
+
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            w.write_bytes(&self.to_bytes()?);
+            Ok(())
+        }
+    }
+
     #[derive(Debug, Eq, PartialEq)]
     pub struct DnsAAAARecord {
         addr: Ipv6Addr
@@ -361,21 +630,12 @@ pub mod dns_client_lib {
             DnsAAAARecord { addr: a }
         }
 
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
             Ok(self.addr.octets().to_vec())
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<DnsAAAARecord, String> {
-            let buflen = buf.len();
-            if buflen == 0 {
-                return Err(String::from("Got a zero-length buffer."));
-            }
-            if offset >= buflen {
-                return Err(String::from("Got an offset outside of the buffer parsing AAAA record."));
-            }
-            if (offset + 16) > buflen {
-                return Err(String::from("Got a buffer with too few bytes to read."));
-            }
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<DnsAAAARecord, DnsError> {
+            check_bounds(buf, offset, 16, "AAAA record")?;
             // TODO this is ugly.
             let bytes = [buf[offset],    buf[offset+1],  buf[offset+2],  buf[offset+3],
                          buf[offset+4],  buf[offset+5],  buf[offset+6],  buf[offset+7],
@@ -393,6 +653,20 @@ pub mod dns_client_lib {
         }
     }
 
+    impl RData for DnsAAAARecord {
+        const TYPE: u16 = 28;
+        const MNEMONIC: &'static str = "AAAA";
+
+        fn parse(buf: &[u8], offset: usize, _rdlen: usize) -> Result<Self, DnsError> {
+            DnsAAAARecord::from_bytes(buf, offset)
+        }
+
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            w.write_bytes(&self.to_bytes()?);
+            Ok(())
+        }
+    }
+
     #[derive(Debug, Eq, PartialEq)]
     pub struct DnsTXTRecord {
         text: String
@@ -403,12 +677,12 @@ pub mod dns_client_lib {
             DnsTXTRecord { text: t }
         }
 
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
             /* len byte, then string. string can be up to 255 chars as a character-string.
                see rfc1035, 3.3.14 (TXT RDATA format) and 3.3 (re: character-string)
              */
             if self.text.len() > 255 {
-                return Err(String::from("Got a TXT record with too much data!"));
+                return Err(DnsError::Oversize { context: "TXT record string", len: self.text.len(), max: 255 });
             }
             let lenbyte = self.text.len() as u8;
             let mut ret: Vec<u8> = Vec::new();
@@ -417,23 +691,15 @@ pub mod dns_client_lib {
             Ok(ret)
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<(DnsTXTRecord, usize), String> {
-            let buflen = buf.len();
-            if buflen == 0 {
-                return Err(String::from("Got a zero-length buffer."));
-            }
-            if offset >= buflen {
-                return Err(String::from("Got an offset outside of the buffer parsing TXT record."));
-            }
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<(DnsTXTRecord, usize), DnsError> {
+            check_bounds(buf, offset, 1, "TXT record")?;
             let lenbyte = buf[offset]; // first byte is len, followed by that many characters.
             let txtstart = offset + 1;
-            if txtstart + (lenbyte as usize) > buflen {
-                return Err(String::from("Got a TXT record with a len byte pointing outside buffer."))
-            }
+            check_bounds(buf, txtstart, lenbyte as usize, "TXT record string")?;
 
             let txt = match String::from_utf8(buf[txtstart .. txtstart + (lenbyte as usize)].to_vec()) {
                 Ok(s) => s,
-                Err(e) => return Err(e.to_string())
+                Err(_) => return Err(DnsError::InvalidUtf8 { context: "TXT record", offset: txtstart })
             };
             Ok((DnsTXTRecord::new(txt), (1 + lenbyte) as usize))
         }
@@ -445,6 +711,20 @@ pub mod dns_client_lib {
         }
     }
 
+    impl RData for DnsTXTRecord {
+        const TYPE: u16 = 16;
+        const MNEMONIC: &'static str = "TXT";
+
+        fn parse(buf: &[u8], offset: usize, _rdlen: usize) -> Result<Self, DnsError> {
+            Ok(DnsTXTRecord::from_bytes(buf, offset)?.0)
+        }
+
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            w.write_bytes(&self.to_bytes()?);
+            Ok(())
+        }
+    }
+
     #[derive(Debug, Eq, PartialEq)]
     pub struct DnsCNAMERecord {
         name: String
@@ -455,18 +735,16 @@ pub mod dns_client_lib {
             DnsCNAMERecord { name: n }
         }
 
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
             string_to_dns_name(&self.name)
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<(DnsCNAMERecord, usize), String> {
-            let buflen = buf.len();
-            if buflen == 0 {
-                return Err(String::from("Got a zero-length buffer."));
-            }
-            if offset >= buflen {
-                return Err(String::from("Got an offset outside of the buffer parsing CNAME record."));
-            }
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<(DnsCNAMERecord, usize), DnsError> {
+            check_bounds(buf, offset, 1, "CNAME record")?;
 
             let (cname, count) = dns_name_to_string(buf, offset)?;
             Ok((DnsCNAMERecord::new(cname), count))
@@ -479,6 +757,19 @@ pub mod dns_client_lib {
         }
     }
 
+    impl RData for DnsCNAMERecord {
+        const TYPE: u16 = 5;
+        const MNEMONIC: &'static str = "CNAME";
+
+        fn parse(buf: &[u8], offset: usize, _rdlen: usize) -> Result<Self, DnsError> {
+            Ok(DnsCNAMERecord::from_bytes(buf, offset)?.0)
+        }
+
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            w.write_name(&self.name, true)
+        }
+    }
+
     #[derive(Debug, Eq, PartialEq)]
     pub struct DnsMXRecord {
         preference: u16,
@@ -490,7 +781,7 @@ pub mod dns_client_lib {
             DnsMXRecord { preference: p, exchange: e }
         }
 
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
             let mut ret: Vec<u8> = Vec::new();
             ret.extend_from_slice(&self.preference.to_be_bytes());
             match string_to_dns_name(&self.exchange) {
@@ -502,17 +793,9 @@ pub mod dns_client_lib {
             Ok(ret)
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<(DnsMXRecord, usize), String> {
-            let buflen = buf.len();
-            if buflen == 0 {
-                return Err(String::from("Got a zero-length buffer."));
-            }
-            if offset >= buflen {
-                return Err(String::from("Got an offset outside of the buffer parsing MX record."));
-            }
-            if offset + 3 > buflen { // two bytes for prefs, and at least 1 byte for exchange.
-                return Err(String::from("Got an offset with not enough buf for prefs/exchange."));
-            }
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<(DnsMXRecord, usize), DnsError> {
+            // two bytes for prefs, and at least 1 byte for exchange.
+            check_bounds(buf, offset, 3, "MX record")?;
 
             let prefbytes = [buf[offset], buf[offset+1]];
             let prefs = u16::from_be_bytes(prefbytes);
@@ -527,6 +810,20 @@ pub mod dns_client_lib {
         }
     }
 
+    impl RData for DnsMXRecord {
+        const TYPE: u16 = 15;
+        const MNEMONIC: &'static str = "MX";
+
+        fn parse(buf: &[u8], offset: usize, _rdlen: usize) -> Result<Self, DnsError> {
+            Ok(DnsMXRecord::from_bytes(buf, offset)?.0)
+        }
+
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            w.write_u16(self.preference);
+            w.write_name(&self.exchange, true)
+        }
+    }
+
     #[derive(Debug, Eq, PartialEq)]
     pub struct DnsNSRecord {
         name: String
@@ -537,18 +834,12 @@ pub mod dns_client_lib {
             DnsNSRecord { name: n }
         }
 
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
             string_to_dns_name(&self.name)
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<(DnsNSRecord, usize), String> {
-            let buflen = buf.len();
-            if buflen == 0 {
-                return Err(String::from("Got a zero-length buffer."));
-            }
-            if offset >= buflen {
-                return Err(String::from("Got an offset outside of the buffer parsing NS record."));
-            }
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<(DnsNSRecord, usize), DnsError> {
+            check_bounds(buf, offset, 1, "NS record")?;
 
             let (name, count) = dns_name_to_string(buf, offset)?;
             Ok((DnsNSRecord::new(name), count))
@@ -561,6 +852,174 @@ pub mod dns_client_lib {
         }
     }
 
+    impl RData for DnsNSRecord {
+        const TYPE: u16 = 2;
+        const MNEMONIC: &'static str = "NS";
+
+        fn parse(buf: &[u8], offset: usize, _rdlen: usize) -> Result<Self, DnsError> {
+            Ok(DnsNSRecord::from_bytes(buf, offset)?.0)
+        }
+
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            w.write_name(&self.name, true)
+        }
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct DnsPTRRecord {
+        name: String
+    }
+
+    impl DnsPTRRecord {
+        pub fn new(n: String) -> DnsPTRRecord {
+            DnsPTRRecord { name: n }
+        }
+
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+            string_to_dns_name(&self.name)
+        }
+
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<(DnsPTRRecord, usize), DnsError> {
+            check_bounds(buf, offset, 1, "PTR record")?;
+
+            let (name, count) = dns_name_to_string(buf, offset)?;
+            Ok((DnsPTRRecord::new(name), count))
+        }
+    }
+
+    impl fmt::Display for DnsPTRRecord {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "PTR: {}", self.name)
+        }
+    }
+
+    impl RData for DnsPTRRecord {
+        const TYPE: u16 = 12;
+        const MNEMONIC: &'static str = "PTR";
+
+        fn parse(buf: &[u8], offset: usize, _rdlen: usize) -> Result<Self, DnsError> {
+            Ok(DnsPTRRecord::from_bytes(buf, offset)?.0)
+        }
+
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            w.write_name(&self.name, true)
+        }
+    }
+
+    /* see rfc2782. that rfc says name compression isn't to be used for target, so unlike
+       MX, it's always written out in full. */
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct DnsSRVRecord {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String
+    }
+
+    impl DnsSRVRecord {
+        pub fn new(priority: u16, weight: u16, port: u16, target: String) -> DnsSRVRecord {
+            DnsSRVRecord { priority, weight, port, target }
+        }
+
+        pub fn priority(&self) -> u16 {
+            self.priority
+        }
+
+        pub fn weight(&self) -> u16 {
+            self.weight
+        }
+
+        pub fn port(&self) -> u16 {
+            self.port
+        }
+
+        pub fn target(&self) -> &str {
+            &self.target
+        }
+
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+            let mut ret: Vec<u8> = Vec::new();
+            ret.extend_from_slice(&self.priority.to_be_bytes());
+            ret.extend_from_slice(&self.weight.to_be_bytes());
+            ret.extend_from_slice(&self.port.to_be_bytes());
+            ret.append(&mut string_to_dns_name(&self.target)?);
+            Ok(ret)
+        }
+
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<(DnsSRVRecord, usize), DnsError> {
+            // priority, weight, port, and at least 1 byte for target.
+            check_bounds(buf, offset, 7, "SRV record")?;
+
+            let priority = u16::from_be_bytes([buf[offset], buf[offset+1]]);
+            let weight = u16::from_be_bytes([buf[offset+2], buf[offset+3]]);
+            let port = u16::from_be_bytes([buf[offset+4], buf[offset+5]]);
+            let (target, count) = dns_name_to_string(buf, offset+6)?;
+            Ok((DnsSRVRecord::new(priority, weight, port, target), count + 6))
+        }
+    }
+
+    impl fmt::Display for DnsSRVRecord {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "SRV: Priority {}; Weight {}; Port {}; Target {}",
+                   self.priority, self.weight, self.port, self.target)
+        }
+    }
+
+    impl RData for DnsSRVRecord {
+        const TYPE: u16 = 33;
+        const MNEMONIC: &'static str = "SRV";
+
+        fn parse(buf: &[u8], offset: usize, _rdlen: usize) -> Result<Self, DnsError> {
+            Ok(DnsSRVRecord::from_bytes(buf, offset)?.0)
+        }
+
+        // the target goes through write_name, so it's lowercased in canonical form (rfc4034, section 6.2).
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            for field in [self.priority, self.weight, self.port] {
+                w.write_u16(field);
+            }
+            w.write_name(&self.target, false)
+        }
+    }
+
+    /* the order to try the targets of a set of SRV records in, per the "usage rules" in
+       rfc2782: lowest priority first, and within a priority, a weighted random pick of
+       what's left, over and over. a target of "." means the service is decidedly not
+       available, so those records are left out. the randomness comes from rng, so callers
+       (tests, mostly) can make it repeatable. */
+    pub fn order_srv_records<'a, R: Rng + ?Sized>(records: &'a [DnsSRVRecord], rng: &mut R)
+        -> Vec<&'a DnsSRVRecord> {
+        let mut remaining: Vec<&DnsSRVRecord> = records.iter()
+            .filter(|r| r.target != ".")
+            .collect();
+        // weight 0 records go first within their priority, so they only get picked when the
+        // random number comes up 0.
+        remaining.sort_by_key(|r| (r.priority, r.weight != 0));
+
+        let mut ret: Vec<&DnsSRVRecord> = Vec::with_capacity(remaining.len());
+        while !remaining.is_empty() {
+            let priority = remaining[0].priority;
+            let group_len = remaining.iter().take_while(|r| r.priority == priority).count();
+            let mut group: Vec<&DnsSRVRecord> = remaining.drain(.. group_len).collect();
+
+            while !group.is_empty() {
+                let total: u32 = group.iter().map(|r| r.weight as u32).sum();
+                let pick = rng.gen_range(0 ..= total);
+                let mut running: u32 = 0;
+                let mut idx = group.len() - 1;
+                for (i, r) in group.iter().enumerate() {
+                    running += r.weight as u32;
+                    if running >= pick {
+                        idx = i;
+                        break;
+                    }
+                }
+                ret.push(group.remove(idx));
+            }
+        }
+        ret
+    }
+
     #[derive(Debug, Eq, PartialEq)]
     pub struct DnsSOARecord {
         mname: String,
@@ -575,11 +1034,11 @@ pub mod dns_client_lib {
     impl DnsSOARecord {
         pub fn new(mname: String, rname: String, serial: u32, refresh: u32, retry: u32, 
                    expire: u32, minimum: u32) -> DnsSOARecord {
-            DnsSOARecord { mname: mname, rname: rname, serial: serial, refresh: refresh,
-                           retry: retry, expire: expire, minimum: minimum }
+            DnsSOARecord { mname, rname, serial, refresh,
+                           retry, expire, minimum }
         }
 
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
             let mut ret: Vec<u8> = Vec::new();
             match string_to_dns_name(&self.mname) {
                 Ok(mut mnamebytes) => { ret.append(&mut mnamebytes) },
@@ -598,19 +1057,9 @@ pub mod dns_client_lib {
             Ok(ret)
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<(DnsSOARecord, usize), String> {
-            let buflen = buf.len();
-            if buflen == 0 {
-                return Err(String::from("Got a zero-length buffer."));
-            }
-            if offset >= buflen {
-                return Err(String::from("Got an offset outside of the buffer parsing SOA record."));
-            }
-
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<(DnsSOARecord, usize), DnsError> {
             // we need at least 22 bytes - 1+ for each name, then 4 for each of the 5 u32s.
-            if offset + 22 > buflen {
-                return Err(String::from("Hit buffer bounds reading SOA RR."));
-            }
+            check_bounds(buf, offset, 22, "SOA record")?;
 
             let mut o = offset;
 
@@ -618,6 +1067,7 @@ pub mod dns_client_lib {
             o += count;
             let (rname, count) = dns_name_to_string(buf, o)?;
             o += count;
+            check_bounds(buf, o, 20, "SOA record serial/timers")?;
 
             let serial = u32::from_be_bytes([buf[o], buf[o+1], buf[o+2], buf[o+3]]);
             o += 4;
@@ -642,6 +1092,24 @@ pub mod dns_client_lib {
         }
     }
 
+    impl RData for DnsSOARecord {
+        const TYPE: u16 = 6;
+        const MNEMONIC: &'static str = "SOA";
+
+        fn parse(buf: &[u8], offset: usize, _rdlen: usize) -> Result<Self, DnsError> {
+            Ok(DnsSOARecord::from_bytes(buf, offset)?.0)
+        }
+
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            w.write_name(&self.mname, true)?;
+            w.write_name(&self.rname, true)?;
+            for field in [self.serial, self.refresh, self.retry, self.expire, self.minimum] {
+                w.write_u32(field);
+            }
+            Ok(())
+        }
+    }
+
     #[derive(Debug, Eq, PartialEq)]
     pub struct DnsOPTRecordOption {
         code: u16,
@@ -651,13 +1119,22 @@ pub mod dns_client_lib {
 
     impl DnsOPTRecordOption {
         pub fn new(code: u16, data: Vec<u8>) -> DnsOPTRecordOption {
-            DnsOPTRecordOption { code: code, data: data }
+            DnsOPTRecordOption { code, data }
+        }
+
+        pub fn code(&self) -> u16 {
+            self.code
+        }
+
+        pub fn data(&self) -> &[u8] {
+            &self.data
         }
 
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
             let mut ret: Vec<u8> = Vec::new();
             if self.data.len() > u16::MAX as usize {
-                return Err(String::from("Got an OPT record option with too many bytes."));
+                return Err(DnsError::Oversize { context: "OPT record option", len: self.data.len(),
+                                                max: u16::MAX as usize });
             }
             let datalen = self.data.len() as u16;
             ret.extend_from_slice(&self.code.to_be_bytes());
@@ -666,17 +1143,8 @@ pub mod dns_client_lib {
             Ok(ret)
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<(DnsOPTRecordOption, usize), String> {
-            let buflen = buf.len();
-            if buflen == 0 {
-                return Err(String::from("Got a zero-length buffer."));
-            }
-            if offset >= buflen {
-                return Err(String::from("Got an offset outside of the buffer parsing OPT record option."));
-            }
-            if offset + 4 > buflen {
-                return Err(String::from("hit buffer bounds reading an OPT record option."));
-            }
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<(DnsOPTRecordOption, usize), DnsError> {
+            check_bounds(buf, offset, 4, "OPT record option")?;
 
             let mut o = offset;
             let mut twobytes = [0u8, 0u8];
@@ -689,9 +1157,7 @@ pub mod dns_client_lib {
             let optlen = u16::from_be_bytes(twobytes);
             o += 2;
 
-            if o + (optlen as usize) > buflen {
-                return Err(format!("hit buffer bounds reading an OPT record option data: o = {o}, optlen = {optlen}, buflen = {buflen}."));
-            }
+            check_bounds(buf, o, optlen as usize, "OPT record option data")?;
 
             let data: Vec<u8> = buf[o .. o+(optlen as usize)].to_vec();
             o += optlen as usize;
@@ -749,10 +1215,14 @@ pub mod dns_client_lib {
 
     impl DnsOPTRecord {
         pub fn new(options: Vec<DnsOPTRecordOption>) -> DnsOPTRecord {
-            DnsOPTRecord { options: options }
+            DnsOPTRecord { options }
+        }
+
+        pub fn options(&self) -> &Vec<DnsOPTRecordOption> {
+            &self.options
         }
 
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
             let mut ret: Vec<u8> = Vec::new();
             for o in &self.options {
                 ret.append(&mut o.to_bytes()?);
@@ -760,31 +1230,23 @@ pub mod dns_client_lib {
             Ok(ret)
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize, rdlen: usize) ->
-            Result<(DnsOPTRecord, usize), String> {
+        pub fn from_bytes(buf: &[u8], offset: usize, rdlen: usize) ->
+            Result<(DnsOPTRecord, usize), DnsError> {
             let mut options: Vec<DnsOPTRecordOption> = Vec::new();
             if rdlen == 0 {
                 return Ok((DnsOPTRecord::new(options), 0));
             }
 
-            let buflen = buf.len();
-            if buflen == 0 {
-                return Err(String::from("Got a zero-length buffer."));
-            }
-            if offset >= buflen {
-                return Err(format!("Got an offset {offset} outside of the buffer (len: {}) parsing OPT record.", buflen));
-            }
+            check_bounds(buf, offset, rdlen, "OPT record")?;
             let end = offset + rdlen;
-            if end > buflen {
-                return Err(String::from("Got an offset+rdlen pointing outside a buffer."));
-            }
 
             let mut o = offset;
 
             loop {
                 if o == end { break; }
                 if o > end { // this should never happen - it means we read past what rdlen told us to.
-                    return Err(String::from("went past rdlen in buf when parsing OPT record option."));
+                    return Err(DnsError::BadRecord { context: "OPT record", offset,
+                                                     reason: "an option runs past the end of the rdata." });
                 }
                 let (option, bytes_read) = DnsOPTRecordOption::from_bytes(buf, o)?;
                 options.push(option);
@@ -805,6 +1267,20 @@ pub mod dns_client_lib {
         }
     }
 
+    impl RData for DnsOPTRecord {
+        const TYPE: u16 = 41;
+        const MNEMONIC: &'static str = "OPT";
+
+        fn parse(buf: &[u8], offset: usize, rdlen: usize) -> Result<Self, DnsError> {
+            Ok(DnsOPTRecord::from_bytes(buf, offset, rdlen)?.0)
+        }
+
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            w.write_bytes(&self.to_bytes()?);
+            Ok(())
+        }
+    }
+
     /* this is the struct to hold dns records for which we don't yet have an associated struct. */
     #[derive(Debug, Eq, PartialEq)]
     pub struct DnsGenericRecord {
@@ -814,25 +1290,16 @@ pub mod dns_client_lib {
 
     impl DnsGenericRecord {
         pub fn new(q: u16, v: Vec<u8>) -> DnsGenericRecord {
-            DnsGenericRecord { qtype: q, v: v }
+            DnsGenericRecord { qtype: q, v }
         }
 
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
             Ok(self.v.clone())
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize, len: usize, qtype: u16) ->
-                          Result<DnsGenericRecord, String> {
-            let buflen = buf.len();
-            if buflen == 0 {
-                return Err(String::from("Got a zero-length buffer parsing generic record."));
-            }
-            if offset >= buflen {
-                return Err(String::from("Got an offset outside the buffer parsing generic record."));
-            }
-            if offset + len > buflen {
-                return Err(String::from("ran out of bytes parsing generic record."));
-            }
+        pub fn from_bytes(buf: &[u8], offset: usize, len: usize, qtype: u16) ->
+                          Result<DnsGenericRecord, DnsError> {
+            check_bounds(buf, offset, len, "generic record")?;
 
             Ok(DnsGenericRecord::new(qtype, buf[offset .. offset+len].to_vec()))
         }
@@ -855,52 +1322,57 @@ pub mod dns_client_lib {
         pub fn new(critical: bool, tag: String, value: String) -> DnsCAARecord {
             // TODO validate that tag contains only A-Za-z0-9
             // TODO validate value characters - see rfc8659.
-            DnsCAARecord { critical: critical, tag: tag, value: value }
+            DnsCAARecord { critical, tag, value }
         }
 
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
             let mut ret: Vec<u8> = Vec::new();
             let flagbyte: u8 = if self.critical { 0x80 } else { 0 };
             ret.push(flagbyte);
             // TODO validate tag/value characters? see above
+            if self.tag.is_empty() {
+                return Err(DnsError::BadRecord { context: "CAA record", offset: 0, reason: "the tag is empty." });
+            }
+            if self.tag.len() > 255 {
+                return Err(DnsError::Oversize { context: "CAA record tag", len: self.tag.len(), max: 255 });
+            }
+            ret.push(self.tag.len() as u8);
             ret.extend_from_slice(self.tag.as_bytes());
             ret.extend_from_slice(self.value.as_bytes());
             Ok(ret)
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize, rdlen: usize) ->
-                          Result<(DnsCAARecord, usize), String> {
-            let buflen = buf.len();
-            if buflen == 0 {
-                return Err(String::from("Got a zero-length buffer."));
-            }
-            if offset >= buflen {
-                return Err(String::from("Got an offset outside of the buffer parsing CAA record."));
-            }
-            if offset + 3 > buflen { // flag, tag len, and at least 1 tag char
-                return Err(String::from("Got too few bytes to read a CAA record."));
-            }
+        pub fn from_bytes(buf: &[u8], offset: usize, rdlen: usize) ->
+                          Result<(DnsCAARecord, usize), DnsError> {
+            // flag, tag len, and at least 1 tag char
+            check_bounds(buf, offset, 3, "CAA record")?;
             let mut o = offset;
             let flags = buf[o];
             o += 1;
             let taglen = buf[o];
             o += 1;
-            if o + taglen as usize > buflen {
-                return Err(String::from("Got too few bytes to read a CAA record tag."));
-            }
+            check_bounds(buf, o, taglen as usize, "CAA record tag")?;
             let mut tagvec: Vec<u8> = Vec::new();
             tagvec.extend_from_slice(&buf[o .. o + taglen as usize]);
             let tag = match String::from_utf8(tagvec) {
                 Ok(t) => t,
-                Err(e) => return Err(e.to_string())
+                Err(_) => return Err(DnsError::InvalidUtf8 { context: "CAA record tag", offset: o })
             };
             o += taglen as usize;
+            // the value is whatever is left of the rdata after the flags, tag len and tag.
+            let valuelen = match rdlen.checked_sub(taglen as usize + 2) {
+                Some(l) => l,
+                None => return Err(DnsError::BadRecord { context: "CAA record", offset,
+                                                         reason: "the tag is longer than the rdata." })
+            };
+            check_bounds(buf, o, valuelen, "CAA record value")?;
             let mut valuevec: Vec<u8> = Vec::new();
-            valuevec.extend_from_slice(&buf[o .. o + (rdlen - taglen as usize - 2)]);
+            valuevec.extend_from_slice(&buf[o .. o + valuelen]);
             let value = match String::from_utf8(valuevec) {
                 Ok(v) => v,
-                Err(e) => return Err(e.to_string())
+                Err(_) => return Err(DnsError::InvalidUtf8 { context: "CAA record value", offset: o })
             };
+            o += valuelen;
             Ok((DnsCAARecord::new(flags == 0x80u8, tag, value), o - offset))
         }
     }
@@ -918,73 +1390,253 @@ pub mod dns_client_lib {
         }
     }
 
-    /* skeleton functions for new Dns*Record
+    impl RData for DnsCAARecord {
+        const TYPE: u16 = 257;
+        const MNEMONIC: &'static str = "CAA";
+
+        fn parse(buf: &[u8], offset: usize, rdlen: usize) -> Result<Self, DnsError> {
+            Ok(DnsCAARecord::from_bytes(buf, offset, rdlen)?.0)
+        }
+
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            w.write_bytes(&self.to_bytes()?);
+            Ok(())
+        }
+    }
+
+    /* a transaction signature (rfc8945, section 4.2). this only carries the fields - nothing
+       here computes or checks the mac. */
     #[derive(Debug, Eq, PartialEq)]
-    pub struct DnsFOORecord {
-        // TODO
+    pub struct DnsTSIGRecord {
+        algorithm: String,
+        time_signed: u64, // only 48 bits on the wire
+        fudge: u16,
+        mac: Vec<u8>,
+        original_id: u16,
+        error: DnsRcode,
+        other: Vec<u8>
     }
 
-    impl DnsFOORecord {
-        pub fn new() -> DnsFOORecord {
-            DnsFOORecord { }
+    impl DnsTSIGRecord {
+        pub fn new(algorithm: String, time_signed: u64, fudge: u16, mac: Vec<u8>, original_id: u16,
+                   error: DnsRcode, other: Vec<u8>) -> DnsTSIGRecord {
+            DnsTSIGRecord { algorithm, time_signed, fudge, mac, original_id, error, other }
         }
-        /*
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
 
+        pub fn algorithm(&self) -> &str {
+            &self.algorithm
         }
-        */
 
-        // choose signature based on length (variable or static) of record
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<DnsFOORecord, String> {
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<(DnsFOORecord, usize), String> {
-            let count = 0;
-            Ok(DnsFOORecord::new()) // or
-            Ok((DnsFOORecord::new(), count))
+        pub fn time_signed(&self) -> u64 {
+            self.time_signed
         }
-    }
 
-    impl fmt::Display for DnsFOORecord {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            write!(f, "FOO: ")
+        pub fn fudge(&self) -> u16 {
+            self.fudge
         }
-    }
-    */
 
-    #[derive(Debug, Eq, PartialEq)]
-    pub enum DnsResourceRecordEnum {
-        A(DnsARecord),
-        NS(DnsNSRecord),
-        CNAME(DnsCNAMERecord),
-        SOA(DnsSOARecord),
-        MX(DnsMXRecord),
-        TXT(DnsTXTRecord),
-        AAAA(DnsAAAARecord),
-        OPT(DnsOPTRecord),
-        CAA(DnsCAARecord),
-        Generic(DnsGenericRecord)
-        /* Generic is a string of bytes from the wire (network order), and it's meant to 
-           handle records for which the struct associated with the type
-           has yet to be implemented in this code */
-    }
+        pub fn mac(&self) -> &[u8] {
+            &self.mac
+        }
 
-    impl fmt::Display for DnsResourceRecordEnum {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            match self {
-                DnsResourceRecordEnum::A(rr) => write!(f, "{rr}"),
-                DnsResourceRecordEnum::NS(rr) => write!(f, "{rr}"),
-                DnsResourceRecordEnum::CNAME(rr) => write!(f, "{rr}"),
-                DnsResourceRecordEnum::SOA(rr) => write!(f, "{rr}"),
-                DnsResourceRecordEnum::MX(rr) => write!(f, "{rr}"),
-                DnsResourceRecordEnum::TXT(rr) => write!(f, "{rr}"),
-                DnsResourceRecordEnum::AAAA(rr) => write!(f, "{rr}"),
-                DnsResourceRecordEnum::OPT(rr) => write!(f, "{rr}"),
-                DnsResourceRecordEnum::CAA(rr) => write!(f, "{rr}"),
-                DnsResourceRecordEnum::Generic(rr) => write!(f, "{rr}")
-            }
+        pub fn original_id(&self) -> u16 {
+            self.original_id
         }
-    }
 
-    #[derive(Debug, Eq, PartialEq)]
+        // the whole 16 bits of it - TSIG errors don't go through the header or OPT RR.
+        pub fn error(&self) -> DnsRcode {
+            self.error
+        }
+
+        pub fn other(&self) -> &[u8] {
+            &self.other
+        }
+
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<(DnsTSIGRecord, usize), DnsError> {
+            // 1+ for the algorithm name, 10 for time, fudge and mac len, 6 for id, error and other len.
+            check_bounds(buf, offset, 17, "TSIG record")?;
+            let mut o = offset;
+
+            let (algorithm, count) = dns_name_to_string(buf, o)?;
+            o += count;
+            check_bounds(buf, o, 10, "TSIG record time/fudge/mac len")?;
+            let time_signed = u64::from_be_bytes([0, 0, buf[o], buf[o+1], buf[o+2], buf[o+3], buf[o+4], buf[o+5]]);
+            o += 6;
+            let fudge = u16::from_be_bytes([buf[o], buf[o+1]]);
+            o += 2;
+            let maclen = u16::from_be_bytes([buf[o], buf[o+1]]) as usize;
+            o += 2;
+            check_bounds(buf, o, maclen, "TSIG record mac")?;
+            let mac = buf[o .. o + maclen].to_vec();
+            o += maclen;
+
+            check_bounds(buf, o, 6, "TSIG record id/error/other len")?;
+            let original_id = u16::from_be_bytes([buf[o], buf[o+1]]);
+            o += 2;
+            let error = DnsRcode::from_u16(u16::from_be_bytes([buf[o], buf[o+1]]));
+            o += 2;
+            let otherlen = u16::from_be_bytes([buf[o], buf[o+1]]) as usize;
+            o += 2;
+            check_bounds(buf, o, otherlen, "TSIG record other data")?;
+            let other = buf[o .. o + otherlen].to_vec();
+            o += otherlen;
+
+            Ok((DnsTSIGRecord::new(algorithm, time_signed, fudge, mac, original_id, error, other), o - offset))
+        }
+    }
+
+    impl fmt::Display for DnsTSIGRecord {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "TSIG: algorithm: {}, time signed: {}, fudge: {}, mac: {:x?}, original id: {}, error: ",
+                   self.algorithm, self.time_signed, self.fudge, self.mac, self.original_id)?;
+            // 16 means a bad signature here, not a bad EDNS version.
+            if self.error == DnsRcode::BADSIG {
+                write!(f, "BADSIG")?;
+            } else {
+                write!(f, "{}", self.error)?;
+            }
+            write!(f, ", other: {:x?}", self.other)
+        }
+    }
+
+    impl RData for DnsTSIGRecord {
+        const TYPE: u16 = 250;
+        const MNEMONIC: &'static str = "TSIG";
+
+        fn parse(buf: &[u8], offset: usize, _rdlen: usize) -> Result<Self, DnsError> {
+            Ok(DnsTSIGRecord::from_bytes(buf, offset)?.0)
+        }
+
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            if self.time_signed >> 48 != 0 {
+                return Err(DnsError::BadRecord { context: "TSIG record", offset: 0,
+                                                 reason: "the time signed doesn't fit in 48 bits." });
+            }
+            if self.mac.len() > u16::MAX as usize {
+                return Err(DnsError::Oversize { context: "TSIG record mac", len: self.mac.len(),
+                                                max: u16::MAX as usize });
+            }
+            if self.other.len() > u16::MAX as usize {
+                return Err(DnsError::Oversize { context: "TSIG record other data", len: self.other.len(),
+                                                max: u16::MAX as usize });
+            }
+            // the algorithm name must not be compressed (rfc8945, section 4.2).
+            w.write_name(&self.algorithm, false)?;
+            w.write_u16((self.time_signed >> 32) as u16);
+            w.write_u32(self.time_signed as u32);
+            w.write_u16(self.fudge);
+            w.write_u16(self.mac.len() as u16);
+            w.write_bytes(&self.mac);
+            w.write_u16(self.original_id);
+            w.write_u16(self.error.to_u16());
+            w.write_u16(self.other.len() as u16);
+            w.write_bytes(&self.other);
+            Ok(())
+        }
+    }
+
+    /* skeleton for a new Dns*Record
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct DnsFOORecord {
+        // TODO
+    }
+
+    impl DnsFOORecord {
+        pub fn new() -> DnsFOORecord {
+            DnsFOORecord { }
+        }
+    }
+
+    impl fmt::Display for DnsFOORecord {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "FOO: ")
+        }
+    }
+
+    impl RData for DnsFOORecord {
+        const TYPE: u16 = 0; // TODO the iana type code
+        const MNEMONIC: &'static str = "FOO";
+
+        fn parse(buf: &[u8], offset: usize, rdlen: usize) -> Result<Self, DnsError> {
+            check_bounds(buf, offset, rdlen, "FOO record")?;
+            Ok(DnsFOORecord::new())
+        }
+
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            Ok(())
+        }
+    }
+    */
+
+    /* the record types this library has structs for, as variant(struct). each struct
+       implements RData; this makes the DnsResourceRecordEnum variant for it, and hooks it
+       into parsing, writing and printing records. */
+    macro_rules! rdata_types {
+        ($($variant:ident($rdata:ty)),* $(,)?) => {
+            #[derive(Debug, Eq, PartialEq)]
+            pub enum DnsResourceRecordEnum {
+                $($variant($rdata),)*
+                /* Generic is a string of bytes from the wire (network order), and it's meant to
+                   handle records for which the struct associated with the type
+                   has yet to be implemented in this code */
+                Generic(DnsGenericRecord),
+                // types from outside this library, added with rdata::register_rdata.
+                Registered(Box<dyn AnyRData>)
+            }
+
+            impl DnsResourceRecordEnum {
+                pub fn rdata(&self) -> &dyn AnyRData {
+                    match self {
+                        $(DnsResourceRecordEnum::$variant(rr) => rr,)*
+                        DnsResourceRecordEnum::Generic(rr) => rr,
+                        DnsResourceRecordEnum::Registered(rr) => rr.as_ref()
+                    }
+                }
+            }
+
+            fn is_builtin_rdata(rtype: u16) -> bool {
+                $(rtype == <$rdata as RData>::TYPE ||)* false
+            }
+
+            fn builtin_rdata_from_bytes(buf: &[u8], offset: usize, rtype: u16, rdlen: usize)
+                -> Option<Result<DnsResourceRecordEnum, DnsError>> {
+                $(if rtype == <$rdata as RData>::TYPE {
+                    return Some(<$rdata as RData>::parse(buf, offset, rdlen)
+                                .map(DnsResourceRecordEnum::$variant));
+                })*
+                None
+            }
+        }
+    }
+
+    rdata_types! {
+        A(DnsARecord),
+        NS(DnsNSRecord),
+        CNAME(DnsCNAMERecord),
+        SOA(DnsSOARecord),
+        PTR(DnsPTRRecord),
+        MX(DnsMXRecord),
+        TXT(DnsTXTRecord),
+        AAAA(DnsAAAARecord),
+        SRV(DnsSRVRecord),
+        OPT(DnsOPTRecord),
+        DS(DnsDSRecord),
+        RRSIG(DnsRRSIGRecord),
+        NSEC(DnsNSECRecord),
+        DNSKEY(DnsDNSKEYRecord),
+        NSEC3(DnsNSEC3Record),
+        NSEC3PARAM(DnsNSEC3PARAMRecord),
+        CAA(DnsCAARecord),
+        TSIG(DnsTSIGRecord),
+    }
+
+    impl fmt::Display for DnsResourceRecordEnum {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.rdata())
+        }
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
     pub struct DnsResourceRecord {
         name: String,
         qtype: DnsQType,
@@ -996,136 +1648,162 @@ pub mod dns_client_lib {
     impl DnsResourceRecord {
         pub fn new(n: String, t: DnsQType, c: DnsQClass, ttl: u32, r: DnsResourceRecordEnum)
             -> DnsResourceRecord {
-            DnsResourceRecord { name: n, qtype: t, class: c, ttl: ttl, record: r }
+            DnsResourceRecord { name: n, qtype: t, class: c, ttl, record: r }
         }
 
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
-            let mut ret: Vec<u8> = Vec::new();
-            ret.append(&mut string_to_dns_name(&self.name)?);
-            let qtype = self.qtype.to_u16();
-            ret.extend_from_slice(&qtype.to_be_bytes());
-            let qclass = self.class.to_u16();
-            ret.extend_from_slice(&qclass.to_be_bytes());
-            ret.extend_from_slice(&self.ttl.to_be_bytes());
-
-            let mut rdata: Vec<u8> = match &self.record {
-                DnsResourceRecordEnum::A(rr) => { rr.to_bytes()? },
-                DnsResourceRecordEnum::NS(rr) => { rr.to_bytes()? },
-                DnsResourceRecordEnum::CNAME(rr) => { rr.to_bytes()? },
-                DnsResourceRecordEnum::SOA(rr) => { rr.to_bytes()? },
-                DnsResourceRecordEnum::MX(rr) => { rr.to_bytes()? },
-                DnsResourceRecordEnum::TXT(rr) => { rr.to_bytes()? },
-                DnsResourceRecordEnum::AAAA(rr) => { rr.to_bytes()? },
-                DnsResourceRecordEnum::OPT(rr) => { rr.to_bytes()? },
-                DnsResourceRecordEnum::CAA(rr) => { rr.to_bytes()? },
-                DnsResourceRecordEnum::Generic(rr) => { rr.to_bytes()? },
-            };
-            if rdata.len() > u16::MAX as usize {
-                return Err(String::from("Got an rdlen that doesn't fit in a u16!"));
-            }
+        pub fn name(&self) -> &str {
+            &self.name
+        }
 
-            let rdlen = rdata.len() as u16;
-            ret.extend_from_slice(&rdlen.to_be_bytes());
-            ret.append(&mut rdata);
+        pub fn qtype(&self) -> DnsQType {
+            self.qtype
+        }
 
-            Ok(ret)
+        pub fn class(&self) -> DnsQClass {
+            self.class
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<(DnsResourceRecord, usize), String> {
-            let buflen = buf.len();
-            if buflen == 0 {
-                return Err(String::from("Got a buffer with length of 0."));
-            }
-            if offset >= buflen {
-                return Err(String::from("Got an offset outside the buffer parsing an RR."));
+        pub fn ttl(&self) -> u32 {
+            self.ttl
+        }
+
+        pub fn record(&self) -> &DnsResourceRecordEnum {
+            &self.record
+        }
+
+        // the EDNS fields packed into this record's class and ttl, if it's an OPT RR.
+        pub fn edns(&self) -> Option<DnsEdns<'_>> {
+            let DnsResourceRecordEnum::OPT(opt) = &self.record else { return None; };
+            Some(DnsEdns { udp_payload_size: self.class.to_u16(),
+                           extended_rcode: (self.ttl >> 24) as u8,
+                           version: (self.ttl >> 16) as u8,
+                           flags: self.ttl as u16,
+                           options: opt.options() })
+        }
+
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+            let mut w = DnsMessageWriter::uncompressed();
+            self.write_to(&mut w)?;
+            Ok(w.into_bytes())
+        }
+
+        /* like to_bytes, but written into a whole message, so names can be compressed.
+           the owner name always can be; names inside rdata only can be for the types
+           rfc1035 defined (see rfc3597, section 4), which is up to each type's RData::write. */
+        pub fn write_to(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            w.write_name(&self.name, true)?;
+            w.write_u16(self.qtype.to_u16());
+            w.write_u16(self.class.to_u16());
+            w.write_u32(self.ttl);
+
+            // rdlen isn't known until the rdata is written, so leave room and fill it in after.
+            let rdlen_offset = w.len();
+            w.write_u16(0);
+            self.record.rdata().write_rdata(w)?;
+
+            let rdlen = w.len() - rdlen_offset - 2;
+            if rdlen > u16::MAX as usize {
+                return Err(DnsError::Oversize { context: "resource record rdata", len: rdlen,
+                                                max: u16::MAX as usize });
             }
+            w.set_u16(rdlen_offset, rdlen as u16);
+            Ok(())
+        }
 
-            let mut o = offset;
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<(DnsResourceRecord, usize), DnsError> {
+            let view = DnsRecordView::parse(buf, offset)?;
+            Ok((view.to_record()?, view.wire_len()))
+        }
+    }
 
-            let (name, count) = dns_name_to_string(buf, offset)?;
-            o += count;
+    // parse the rdata of a record of type rtype, which is rdlen bytes at offset.
+    fn rdata_from_bytes(buf: &[u8], o: usize, rtype: u16, rdlen: usize)
+        -> Result<DnsResourceRecordEnum, DnsError> {
+        /* what if the count of bytes returned by some of the various from_bytes functions
+           does not equal rdlen?
+           it might be worth being pedantic about this - if count != rdlen (or rdlen !=
+           the static lengths used, like 4 bytes for A records), then we should
+           return an error.
+         */
+        if let Some(record) = builtin_rdata_from_bytes(buf, o, rtype, rdlen) {
+            return record;
+        }
+        if let Some(parse) = rdata::registered_parser(rtype) {
+            return Ok(DnsResourceRecordEnum::Registered(parse(buf, o, rdlen)?));
+        }
+        Ok(DnsResourceRecordEnum::Generic(DnsGenericRecord::from_bytes(buf, o, rdlen, rtype)?))
+    }
 
-            if o + 10 > buflen { // qtype, qclass, ttl, rdlen
-                return Err(String::from("Hit buffer end when parsing resource record."));
+    impl fmt::Display for DnsResourceRecord {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self.edns() {
+                Some(edns) => write!(f, "{edns} {}", self.record),
+                None => write!(f, "{} {} {} {}", self.name, self.class, self.ttl, self.record)
             }
+        }
+    }
 
-            let typebytes = [buf[o], buf[o+1]];
-            let typeu16 = u16::from_be_bytes(typebytes);
-            let qtype = DnsQType::from_u16(typeu16);
-            o += 2;
+    /* the OPT pseudo-RR (rfc6891, section 6.1) doesn't use its class and ttl as such: the
+       class is the largest udp payload the sender can take, and the ttl is the upper 8 bits
+       of the rcode, the EDNS version, and flags - of which only DO (rfc3225) is defined. */
+    #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+    pub struct DnsEdns<'a> {
+        udp_payload_size: u16,
+        extended_rcode: u8,
+        version: u8,
+        flags: u16,
+        options: &'a [DnsOPTRecordOption]
+    }
 
-            let classbytes = [buf[o], buf[o+1]];
-            let qclass = DnsQClass::from_u16(u16::from_be_bytes(classbytes));
-            o += 2;
+    impl<'a> DnsEdns<'a> {
+        pub fn udp_payload_size(&self) -> u16 {
+            self.udp_payload_size
+        }
 
-            let ttlbytes = [buf[o], buf[o+1], buf[o+2], buf[o+3]];
-            let ttl = u32::from_be_bytes(ttlbytes);
-            o += 4;
+        // the upper 8 bits of the 12-bit rcode. the header has the lower 4.
+        pub fn extended_rcode(&self) -> u8 {
+            self.extended_rcode
+        }
 
-            let rdlenbytes = [buf[o], buf[o+1]];
-            let rdlen = u16::from_be_bytes(rdlenbytes);
-            o += 2;
+        pub fn version(&self) -> u8 {
+            self.version
+        }
 
-            let record: DnsResourceRecordEnum = match qtype {
-                /* what if the count of bytes returned by some of the various from_bytes functions
-                   does not equal the rdlen read above?
-                   it might be worth being pedantic about this - if count != rdlen (or rdlen !=
-                   the static lengths used, like 4 bytes for A records), then we should
-                   return an error.
-                 */
-                DnsQType::A => {
-                    let record = DnsARecord::from_bytes(buf, o)?;
-                    DnsResourceRecordEnum::A(record)
-                },
-                DnsQType::NS => {
-                    let (record, _) = DnsNSRecord::from_bytes(buf, o)?;
-                    DnsResourceRecordEnum::NS(record)
-                },
-                DnsQType::CNAME => {
-                    let (record, _) = DnsCNAMERecord::from_bytes(buf, o)?;
-                    DnsResourceRecordEnum::CNAME(record)
-                },
-                DnsQType::SOA => {
-                    let (record, _) = DnsSOARecord::from_bytes(buf, o)?;
-                    DnsResourceRecordEnum::SOA(record)
-                },
-                DnsQType::MX => {
-                    let (record, _) = DnsMXRecord::from_bytes(buf, o)?;
-                    DnsResourceRecordEnum::MX(record)
-                },
-                DnsQType::TXT => {
-                    let (record, _) = DnsTXTRecord::from_bytes(buf, o)?;
-                    DnsResourceRecordEnum::TXT(record)
-                },
-                DnsQType::AAAA => {
-                    let record = DnsAAAARecord::from_bytes(buf, o)?;
-                    DnsResourceRecordEnum::AAAA(record)
-                },
-                DnsQType::OPT => {
-                    let (record, _) = DnsOPTRecord::from_bytes(buf, o, rdlen as usize)?;
-                    DnsResourceRecordEnum::OPT(record)
-                },
-                DnsQType::CAA => {
-                    let (record, _) = DnsCAARecord::from_bytes(buf, o, rdlen as usize)?;
-                    DnsResourceRecordEnum::CAA(record)
-                },
-                _ => {
-                    let record = DnsGenericRecord::from_bytes(buf, o, rdlen as usize, typeu16)?;
-                    DnsResourceRecordEnum::Generic(record)
-                }
-            };
-            o += rdlen as usize;
+        pub fn dnssec_ok(&self) -> bool {
+            self.flags & 0x8000 != 0
+        }
+
+        // all 16 flag bits, DO included.
+        pub fn flags(&self) -> u16 {
+            self.flags
+        }
+
+        pub fn options(&self) -> &'a [DnsOPTRecordOption] {
+            self.options
+        }
 
-            Ok((DnsResourceRecord::new(name, qtype, qclass, ttl, record), o - offset))
+        // the full rcode, given the 4 bits of it from the header.
+        pub fn rcode(&self, header_rcode: DnsRcode) -> DnsRcode {
+            DnsRcode::from_u16(((self.extended_rcode as u16) << 4) | (header_rcode.to_u16() & 0xF))
         }
     }
 
-    impl fmt::Display for DnsResourceRecord {
+    impl fmt::Display for DnsEdns<'_> {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            write!(f, "{} {} {} {}", self.name, self.class, self.ttl, self.record)
+            write!(f, "EDNS: version {}, flags:{}; udp: {}", self.version,
+                   if self.dnssec_ok() { " do" } else { "" }, self.udp_payload_size)?;
+            if self.extended_rcode != 0 {
+                write!(f, "; extended rcode: {}", self.extended_rcode)?;
+            }
+            Ok(())
         }
     }
 
+    // the OPT RR in additionals, if there is one. there should only ever be one.
+    fn find_edns(additionals: &[DnsResourceRecord]) -> Option<DnsEdns<'_>> {
+        additionals.iter().find_map(|rr| rr.edns())
+    }
+
     // note that this only contains the qid/options fields - RR counts aren't included,
     // b/c they're implied from the Vecs used to hold the RRs of a query/response.
     #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -1137,14 +1815,63 @@ pub mod dns_client_lib {
         tc: bool,
         rd: bool,
         ra: bool,
+        z: bool,
+        ad: bool,
+        cd: bool,
         rcode: DnsRcode
     }
 
     impl DnsHeader {
+        // z, ad and cd start out clear; see their setters.
+        #[allow(clippy::too_many_arguments)]
         pub fn new(id: u16, response: bool, opcode: DnsOpcode, aa: bool,
                    tc: bool, rd: bool, ra: bool, rcode: DnsRcode) -> DnsHeader {
-            DnsHeader { id: id, response: response, opcode: opcode, aa: aa,
-                        tc: tc, rd: rd, ra: ra, rcode: rcode }
+            DnsHeader { id, response, opcode, aa,
+                        tc, rd, ra, z: false, ad: false, cd: false, rcode }
+        }
+
+        pub fn id(&self) -> u16 {
+            self.id
+        }
+
+        pub fn response(&self) -> bool {
+            self.response
+        }
+
+        pub fn tc(&self) -> bool {
+            self.tc
+        }
+
+        // the reserved bit, which should be zero (rfc1035, section 4.1.1).
+        pub fn z(&self) -> bool {
+            self.z
+        }
+
+        pub fn set_z(&mut self, z: bool) {
+            self.z = z;
+        }
+
+        /* authentic data: in a response, the resolver validated everything in the answer and
+           authority sections. in a query, it asks to be told (rfc6840, section 5.7). */
+        pub fn ad(&self) -> bool {
+            self.ad
+        }
+
+        pub fn set_ad(&mut self, ad: bool) {
+            self.ad = ad;
+        }
+
+        // checking disabled: the resolver shouldn't do DNSSEC validation (rfc4035, section 3.2.2).
+        pub fn cd(&self) -> bool {
+            self.cd
+        }
+
+        pub fn set_cd(&mut self, cd: bool) {
+            self.cd = cd;
+        }
+
+        pub fn rcode(&self) -> DnsRcode {
+            self.rcode
         }
 
         pub fn flags_to_u16(&self) -> u16 {
@@ -1155,17 +1882,18 @@ pub mod dns_client_lib {
             let tc: u16 = if self.tc { 0x200 } else { 0 };
             let rd: u16 = if self.rd { 0x100 } else { 0 };
             let ra: u16 = if self.ra { 0x80 } else { 0 };
-            let rcode: u16 = (self.rcode as u16) & 0xf;
+            let z: u16 = if self.z { 0x40 } else { 0 };
+            let ad: u16 = if self.ad { 0x20 } else { 0 };
+            let cd: u16 = if self.cd { 0x10 } else { 0 };
+            let rcode: u16 = self.rcode.to_u16() & 0xf;
 
-            response | opcode | aa | tc | rd | ra | rcode 
+            response | opcode | aa | tc | rd | ra | z | ad | cd | rcode 
         }
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<DnsHeader, String> {
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<DnsHeader, DnsError> {
             // check that the fields read here lie inside the buf bounds
             // note that we only read the qid/flags here, so we only need 4 bytes.
-            if (offset + 4) > buf.len() {
-                return Err(String::from("Buffer does not contain enough bytes to read qid/flags!"));
-            }
+            check_bounds(buf, offset, 4, "header qid/flags")?;
 
             let mut twobytes = [0u8, 0u8];
             twobytes.clone_from_slice(&buf[offset .. offset+2]);
@@ -1174,29 +1902,40 @@ pub mod dns_client_lib {
             let flags = u16::from_be_bytes(twobytes);
 
             let response : bool = (flags & 0x8000) != 0;
-            let opcode = DnsOpcode::from_u8(((flags & 0x78) >> 11) as u8);
+            let opcode = DnsOpcode::from_u8(((flags & 0x7800) >> 11) as u8);
             let aa : bool = (flags & 0x0400) != 0;
             let tc : bool = (flags & 0x0200) != 0;
             let rd : bool = (flags & 0x0100) != 0;
             let ra : bool = (flags & 0x0080) != 0;
             let rcode = DnsRcode::from_u8((flags & 0xF) as u8);
 
-            Ok(DnsHeader::new(qid, response, opcode, aa, tc, rd, ra, rcode))
+            let mut header = DnsHeader::new(qid, response, opcode, aa, tc, rd, ra, rcode);
+            header.set_z((flags & 0x0040) != 0);
+            header.set_ad((flags & 0x0020) != 0);
+            header.set_cd((flags & 0x0010) != 0);
+            Ok(header)
         }
 
     }
 
     impl fmt::Display for DnsHeader {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            let s = format!("QID: {:x}, Opcode: {}, AA: {}, TC: {}, RD: {}, RA: {}, Rcode: {}", 
-                            self.id, self.opcode, self.aa, self.tc, self.rd, self.ra, self.rcode);
+            let s = format!("QID: {:x}, Opcode: {}, AA: {}, TC: {}, RD: {}, RA: {}, Z: {}, AD: {}, CD: {}, Rcode: {}", 
+                            self.id, self.opcode, self.aa, self.tc, self.rd, self.ra, self.z, self.ad, self.cd,
+                            self.rcode);
             write!(f, "{s}")
         }
     }
 
+    /* queries usually only carry questions (and maybe an OPT RR in the additionals), but
+       UPDATE (rfc2136) and NOTIFY (rfc1996) messages use the answer and authority sections
+       too - for UPDATE they hold the prerequisites and the updates themselves. */
+    #[derive(Debug, Eq, PartialEq)]
     pub struct DnsQuery {
         header: DnsHeader,
         questions: Vec<DnsQuestionRecord>,
+        answers: Vec<DnsResourceRecord>,
+        authorities: Vec<DnsResourceRecord>,
         additionals: Option<Vec<DnsResourceRecord>>
     }
 
@@ -1204,147 +1943,351 @@ pub mod dns_client_lib {
 
         pub fn new(h: DnsHeader, q: Vec<DnsQuestionRecord>,
                    add: Option<Vec<DnsResourceRecord>>) -> DnsQuery {
-            DnsQuery { header: h, questions: q, additionals: add }
+            DnsQuery { header: h, questions: q, answers: Vec::new(), authorities: Vec::new(),
+                       additionals: add }
+        }
+
+        pub fn new_with_sections(h: DnsHeader, q: Vec<DnsQuestionRecord>, an: Vec<DnsResourceRecord>,
+                                 auth: Vec<DnsResourceRecord>,
+                                 add: Option<Vec<DnsResourceRecord>>) -> DnsQuery {
+            DnsQuery { header: h, questions: q, answers: an, authorities: auth, additionals: add }
+        }
+
+        // start building a query. see DnsQueryBuilder.
+        pub fn builder() -> DnsQueryBuilder {
+            DnsQueryBuilder::new()
         }
         
         // output bytes are network-order, ready to be written to wire.
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
-            let mut ret: Vec<u8> = Vec::new();
-
-            // header
-            let qid: u16 = self.header.id;
-            ret.extend_from_slice(&qid.to_be_bytes());
-            let options = self.header.flags_to_u16();
-            ret.extend_from_slice(&options.to_be_bytes());
-
-            // qcount/ancount/nscount/arcount
-            let qcount = self.questions.len() as u16;
-            ret.extend_from_slice(&qcount.to_be_bytes());
-            let other_count = 0u16;
-            ret.extend_from_slice(&other_count.to_be_bytes());
-            ret.extend_from_slice(&other_count.to_be_bytes());
-            let addcount = match &self.additionals {
-                Some(a) => a.len() as u16,
-                None => 0u16
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+            let additionals = match &self.additionals {
+                Some(a) => a.as_slice(),
+                None => &[]
             };
-            ret.extend_from_slice(&addcount.to_be_bytes());
+            message_to_bytes(&self.header, &self.questions, &self.answers, &self.authorities,
+                             additionals)
+        }
 
-            for question in &self.questions {
-                let mut bytes = question.to_bytes()?;
-                ret.append(&mut bytes);
-            }
+        /* parse a query off the wire. an empty additional section comes back as None,
+           so that a query made with DnsQuery::new(h, q, None) survives a round trip. */
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<DnsQuery, DnsError> {
+            let (header, questions, answers, authorities, additionals) =
+                parse_message(buf, offset, "DNS query")?;
+            let additionals = if additionals.is_empty() { None } else { Some(additionals) };
+            Ok(DnsQuery::new_with_sections(header, questions, answers, authorities, additionals))
+        }
 
-            if let Some(additionals) = &self.additionals {
-                for rr in additionals {
-                    let mut bytes = rr.to_bytes()?;
-                    ret.append(&mut bytes);
-                }
-            }
-            
-            Ok(ret)
+        pub fn questions(&self) -> &Vec<DnsQuestionRecord> {
+            &self.questions
         }
 
-        /*
-        pub fn from_bytes(bytes: Vec<u8>) -> Result(DnsHeader, String) {
+        pub fn answers(&self) -> &Vec<DnsResourceRecord> {
+            &self.answers
+        }
 
+        pub fn authorities(&self) -> &Vec<DnsResourceRecord> {
+            &self.authorities
+        }
+
+        pub fn additionals(&self) -> Option<&Vec<DnsResourceRecord>> {
+            self.additionals.as_ref()
+        }
+
+        pub fn header(&self) -> &DnsHeader {
+            &self.header
+        }
+
+        pub fn edns(&self) -> Option<DnsEdns<'_>> {
+            find_edns(self.additionals.as_deref().unwrap_or(&[]))
+        }
+
+        /* check that buf is a response to this query, before we go trusting it: the QR bit
+           must be set, the QID must match ours, and the question section must echo ours.
+           only the header and question section are looked at, so this works on truncated
+           responses too. see rfc5452, section 9.1. */
+        pub fn check_response(&self, buf: &[u8]) -> Result<(), DnsError> {
+            let header = DnsHeader::from_bytes(buf, 0)?;
+            if !header.response {
+                return Err(DnsError::NotResponse);
+            }
+            if header.id != self.header.id {
+                return Err(DnsError::IdMismatch { got: header.id, expected: self.header.id });
+            }
+
+            check_bounds(buf, 0, 12, "DNS response")?;
+            let qcount = u16::from_be_bytes([buf[4], buf[5]]) as usize;
+            /* a server that couldn't parse or doesn't implement the query may leave the
+               question section out entirely (rfc1035, section 4.1.1). any other rcode has to
+               echo the question, or a spoofer would only need the id to fake a REFUSED or
+               NXDOMAIN. */
+            if qcount == 0 && matches!(header.rcode, DnsRcode::FORMERR | DnsRcode::NOTIMP) {
+                return Ok(());
+            }
+            if qcount != self.questions.len() {
+                return Err(DnsError::QuestionCount { got: qcount as u16, expected: self.questions.len() as u16 });
+            }
+            let mut o = 12;
+            for question in &self.questions {
+                let (record, count) = DnsQuestionRecord::from_bytes(buf, o)?;
+                if !question.matches(&record) {
+                    return Err(DnsError::QuestionMismatch { got: record.to_string(), expected: question.to_string() });
+                }
+                o += count;
+            }
+            Ok(())
         }
-        */
     }
 
     impl fmt::Display for DnsQuery {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             write!(f, "Query:\n  Header: {}\n", self.header)?;
             for qr in &self.questions {
-                write!(f, "  Question: {qr}\n")?;
+                writeln!(f, "  Question: {qr}")?;
+            }
+            for rr in &self.answers {
+                writeln!(f, "  Answer: {rr}")?;
+            }
+            for rr in &self.authorities {
+                writeln!(f, "  Authority: {rr}")?;
             }
             if let Some(additionals) = &self.additionals {
                 for rr in additionals {
-                    write!(f, "  Additional: {rr}\n")?;
+                    writeln!(f, "  Additional: {rr}")?;
                 }
             }
             Ok(())
         }
     }
 
+    /* builds a query, a setting at a time:
+         let q = DnsQuery::builder().question("example.com.", DnsQType::A, DnsQClass::IN)
+                                    .dnssec_ok(true).build()?;
+       the id is random unless one is given, and RD is set. EDNS (rfc6891) is only used if
+       something asks for it - a payload size, the DO bit or an option - and the payload size
+       defaults to client::DEFAULT_EDNS_PAYLOAD_SIZE. anything wrong with what was given (a
+       bad name, subnet or cookie) comes back from build, before anything is sent. */
+    #[derive(Debug)]
+    pub struct DnsQueryBuilder {
+        id: Option<u16>,
+        opcode: DnsOpcode,
+        rd: bool,
+        ad: bool,
+        cd: bool,
+        questions: Vec<DnsQuestionRecord>,
+        edns_payload_size: Option<u16>,
+        dnssec_ok: bool,
+        options: Vec<DnsOPTRecordOption>,
+        error: Option<DnsError>
+    }
+
+    impl DnsQueryBuilder {
+        pub fn new() -> DnsQueryBuilder {
+            DnsQueryBuilder { id: None, opcode: DnsOpcode::QUERY, rd: true, ad: false, cd: false,
+                              questions: Vec::new(),
+                              edns_payload_size: None, dnssec_ok: false, options: Vec::new(),
+                              error: None }
+        }
+
+        pub fn id(mut self, id: u16) -> DnsQueryBuilder {
+            self.id = Some(id);
+            self
+        }
+
+        pub fn opcode(mut self, opcode: DnsOpcode) -> DnsQueryBuilder {
+            self.opcode = opcode;
+            self
+        }
+
+        // recursion desired. on by default.
+        pub fn rd(mut self, rd: bool) -> DnsQueryBuilder {
+            self.rd = rd;
+            self
+        }
+
+        // ask for the AD bit to be set in the response if the answer validated. see DnsHeader::ad.
+        pub fn ad(mut self, ad: bool) -> DnsQueryBuilder {
+            self.ad = ad;
+            self
+        }
+
+        // ask the resolver not to validate. see DnsHeader::cd.
+        pub fn cd(mut self, cd: bool) -> DnsQueryBuilder {
+            self.cd = cd;
+            self
+        }
+
+        pub fn question(mut self, name: &str, qtype: DnsQType, qclass: DnsQClass) -> DnsQueryBuilder {
+            if let Err(e) = is_valid_dns_name(name) {
+                self.error.get_or_insert(e);
+            }
+            self.questions.push(DnsQuestionRecord::new(String::from(name), qtype, qclass));
+            self
+        }
+
+        // the largest udp response we can take, sent as the class of the OPT RR.
+        pub fn edns_payload_size(mut self, size: u16) -> DnsQueryBuilder {
+            self.edns_payload_size = Some(size);
+            self
+        }
+
+        // the DO bit: send DNSSEC records with the answer. see rfc3225.
+        pub fn dnssec_ok(mut self, dnssec_ok: bool) -> DnsQueryBuilder {
+            self.dnssec_ok = dnssec_ok;
+            self
+        }
+
+        pub fn edns_option(mut self, option: DnsOPTRecordOption) -> DnsQueryBuilder {
+            self.options.push(option);
+            self
+        }
+
+        // an ECS option for subnet, e.g. "192.0.2.0/24". see make_ecs_option.
+        pub fn ecs(mut self, subnet: &str) -> DnsQueryBuilder {
+            match make_ecs_option(subnet) {
+                Ok(option) => self.options.push(option),
+                Err(e) => { self.error.get_or_insert(e); }
+            }
+            self
+        }
+
+        /* a COOKIE option (rfc7873): our client cookie, and the server cookie the server gave
+           us last time, if we have one. a server cookie is 8 to 32 bytes (section 4). */
+        pub fn cookie(mut self, client: [u8; 8], server: &[u8]) -> DnsQueryBuilder {
+            if !server.is_empty() && !(8 ..= 32).contains(&server.len()) {
+                self.error.get_or_insert(DnsError::Config(format!(
+                    "Got a {} byte server cookie, but they're 8 to 32 bytes.", server.len())));
+                return self;
+            }
+            let mut data = client.to_vec();
+            data.extend_from_slice(server);
+            self.options.push(DnsOPTRecordOption::new(10u16, data)); // cookie is code 10.
+            self
+        }
+
+        pub fn build(self) -> Result<DnsQuery, DnsError> {
+            if let Some(e) = self.error {
+                return Err(e);
+            }
+            let id = self.id.unwrap_or_else(|| rand::thread_rng().gen());
+            let mut h = DnsHeader::new(id, false, self.opcode, false, false, self.rd, false,
+                                       DnsRcode::NOERROR);
+            h.set_ad(self.ad);
+            h.set_cd(self.cd);
+
+            let edns = self.edns_payload_size.is_some() || self.dnssec_ok || !self.options.is_empty();
+            let additionals = if edns {
+                let size = self.edns_payload_size.unwrap_or(client::DEFAULT_EDNS_PAYLOAD_SIZE);
+                // the OPT RR's ttl is the extended rcode, the version, then the DO bit and Z.
+                let ttl: u32 = if self.dnssec_ok { 0x8000 } else { 0 };
+                Some(vec![DnsResourceRecord::new(String::from("."), DnsQType::OPT,
+                                                 DnsQClass::RESERVED(size), ttl,
+                                                 DnsResourceRecordEnum::OPT(DnsOPTRecord::new(self.options)))])
+            } else {
+                None
+            };
+            Ok(DnsQuery::new(h, self.questions, additionals))
+        }
+    }
+
+    impl Default for DnsQueryBuilder {
+        fn default() -> DnsQueryBuilder {
+            DnsQueryBuilder::new()
+        }
+    }
+
+    /* what DNSSEC validation (rfc4035, section 4.3) made of a response. see
+       validator::DnsValidator. */
+    #[derive(Debug, Eq, PartialEq, Copy, Clone)]
+    pub enum DnssecStatus {
+        // there's a chain of signatures from a trust anchor to everything in the answer.
+        Secure,
+        // there's a chain of signatures proving the answer comes from an unsigned zone.
+        Insecure,
+        // there should be signatures, but they're missing, wrong or expired.
+        Bogus,
+        // it couldn't be worked out either way, e.g. the records needed couldn't be fetched.
+        Indeterminate
+    }
+
+    impl fmt::Display for DnssecStatus {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                DnssecStatus::Secure => write!(f, "Secure"),
+                DnssecStatus::Insecure => write!(f, "Insecure"),
+                DnssecStatus::Bogus => write!(f, "Bogus"),
+                DnssecStatus::Indeterminate => write!(f, "Indeterminate")
+            }
+        }
+    }
+
     #[derive(Debug, Eq, PartialEq)]
     pub struct DnsResponse {
         header: DnsHeader,
         questions: Vec<DnsQuestionRecord>,
         answers: Vec<DnsResourceRecord>,
         authorities: Vec<DnsResourceRecord>,
-        additionals: Vec<DnsResourceRecord>
+        additionals: Vec<DnsResourceRecord>,
+        dnssec_status: Option<DnssecStatus>
     }
 
     impl DnsResponse {
         pub fn new(h: DnsHeader, q: Vec<DnsQuestionRecord>, an: Vec<DnsResourceRecord>,
                    auth: Vec<DnsResourceRecord>, add: Vec<DnsResourceRecord>) -> DnsResponse {
-            DnsResponse { header: h, questions: q, answers: an, authorities: auth, additionals: add }
+            DnsResponse { header: h, questions: q, answers: an, authorities: auth, additionals: add,
+                          dnssec_status: None }
         }
 
-        /*
-        pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        pub fn header(&self) -> &DnsHeader {
+            &self.header
+        }
 
+        pub fn questions(&self) -> &Vec<DnsQuestionRecord> {
+            &self.questions
         }
-        */
 
-        pub fn from_bytes(buf: &Vec<u8>, offset: usize) -> Result<DnsResponse, String> {
-            let buflen = buf.len();
-            if buflen == 0 {
-                return Err(String::from("Got a zero-length buffer."));
-            }
-            if offset >= buflen {
-                return Err(String::from("Got an offset outside the buffer parsing DNS response."));
-            }
+        pub fn answers(&self) -> &Vec<DnsResourceRecord> {
+            &self.answers
+        }
 
-            let mut o = offset;
+        pub fn authorities(&self) -> &Vec<DnsResourceRecord> {
+            &self.authorities
+        }
 
-            if o + 8 > buflen { // header, qcount, ancount, authcount, addcount
-                return Err(String::from("Buf contains too few bytes to read response."));
-            }
+        pub fn additionals(&self) -> &Vec<DnsResourceRecord> {
+            &self.additionals
+        }
 
-            let header = DnsHeader::from_bytes(buf, offset)?;
-            o += 4; // qid, flags
+        pub fn edns(&self) -> Option<DnsEdns<'_>> {
+            find_edns(&self.additionals)
+        }
 
-            let qcountbytes = [buf[o], buf[o+1]];
-            let qcount = u16::from_be_bytes(qcountbytes);
-            o += 2;
-            let ancountbytes = [buf[o], buf[o+1]];
-            let ancount = u16::from_be_bytes(ancountbytes);
-            o += 2;
-            let authcountbytes = [buf[o], buf[o+1]];
-            let authcount = u16::from_be_bytes(authcountbytes);
-            o += 2;
-            let addcountbytes = [buf[o], buf[o+1]];
-            let addcount = u16::from_be_bytes(addcountbytes);
-            o += 2;
+        // None unless the response has been through DNSSEC validation.
+        pub fn dnssec_status(&self) -> Option<DnssecStatus> {
+            self.dnssec_status
+        }
 
-            let mut questions: Vec<DnsQuestionRecord> = Vec::new();
-            for _ in 0..qcount {
-                let (record, count) = DnsQuestionRecord::from_bytes(buf, o)?;
-                o += count;
-                questions.push(record);
-            }
-            let mut answers: Vec<DnsResourceRecord> = Vec::new();
-            for _ in 0..ancount {
-                let (record, count) = DnsResourceRecord::from_bytes(buf, o)?;
-                o += count;
-                answers.push(record);
-            }
-            let mut authorities: Vec<DnsResourceRecord> = Vec::new();
-            for _ in 0..authcount {
-                let (record, count) = DnsResourceRecord::from_bytes(buf, o)?;
-                o += count;
-                authorities.push(record);
-            }
-            let mut additionals: Vec<DnsResourceRecord> = Vec::new();
-            for _c in 0..addcount {
-                let (record, count) = DnsResourceRecord::from_bytes(buf, o)?;
-                o += count;
-                additionals.push(record);
+        pub fn set_dnssec_status(&mut self, status: Option<DnssecStatus>) {
+            self.dnssec_status = status;
+        }
+
+        /* the response's rcode, with the upper bits from the OPT RR if there is one. the
+           header's rcode() only has the lower 4 bits, so can't tell BADVERS from NOERROR. */
+        pub fn rcode(&self) -> DnsRcode {
+            match self.edns() {
+                Some(edns) => edns.rcode(self.header.rcode),
+                None => self.header.rcode
             }
+        }
 
-            // XXX should we check that o == buf.len() ? if o < buf.len(), we have unused bytes.
+        // output bytes are network-order, ready to be written to wire.
+        pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+            message_to_bytes(&self.header, &self.questions, &self.answers, &self.authorities,
+                             &self.additionals)
+        }
 
+        pub fn from_bytes(buf: &[u8], offset: usize) -> Result<DnsResponse, DnsError> {
+            let (header, questions, answers, authorities, additionals) =
+                parse_message(buf, offset, "DNS response")?;
             Ok(DnsResponse::new(header, questions, answers, authorities, additionals))
         }
     }
@@ -1352,34 +2295,104 @@ pub mod dns_client_lib {
     impl fmt::Display for DnsResponse {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             write!(f, "Response:\n  Header: {}\n", self.header)?;
+            if self.rcode() != self.header.rcode {
+                writeln!(f, "  Extended Rcode: {}", self.rcode())?;
+            }
+            if let Some(status) = self.dnssec_status {
+                writeln!(f, "  DNSSEC: {status}")?;
+            }
             for qr in &self.questions {
-                write!(f, "  Question: {qr}\n")?;
+                writeln!(f, "  Question: {qr}")?;
             }
             for rr in &self.answers {
-                write!(f, "  Answer: {rr}\n")?;
+                writeln!(f, "  Answer: {rr}")?;
             }
             for rr in &self.authorities {
-                write!(f, "  Authority: {rr}\n")?;
+                writeln!(f, "  Authority: {rr}")?;
             }
             for rr in &self.additionals {
-                write!(f, "  Additional: {rr}\n")?;
+                writeln!(f, "  Additional: {rr}")?;
             }
             Ok(())
         }
     }
 
+    // header and the four sections of a message, in wire order.
+    type DnsMessageSections = (DnsHeader, Vec<DnsQuestionRecord>, Vec<DnsResourceRecord>,
+                               Vec<DnsResourceRecord>, Vec<DnsResourceRecord>);
+
+    /* the inverse of parse_message: header, counts, then each section in order.
+       names are compressed wherever they're allowed to be. */
+    fn message_to_bytes(header: &DnsHeader, questions: &[DnsQuestionRecord],
+                        answers: &[DnsResourceRecord], authorities: &[DnsResourceRecord],
+                        additionals: &[DnsResourceRecord]) -> Result<Vec<u8>, DnsError> {
+        let mut w = DnsMessageWriter::new();
+
+        // header
+        w.write_u16(header.id);
+        w.write_u16(header.flags_to_u16());
+
+        // qcount/ancount/nscount/arcount
+        for count in [questions.len(), answers.len(), authorities.len(), additionals.len()] {
+            if count > u16::MAX as usize {
+                return Err(DnsError::Oversize { context: "section record count", len: count, max: u16::MAX as usize });
+            }
+            w.write_u16(count as u16);
+        }
+
+        for question in questions {
+            question.write_to(&mut w)?;
+        }
+
+        for rr in answers.iter().chain(authorities.iter()).chain(additionals.iter()) {
+            rr.write_to(&mut w)?;
+        }
+
+        Ok(w.into_bytes())
+    }
+
+    /* queries and responses share a wire format (rfc1035, section 4.1), so they share a
+       parser. what is only used to make errors say which one we were parsing. */
+    fn parse_message(buf: &[u8], offset: usize, what: &'static str) -> Result<DnsMessageSections, DnsError> {
+        let view = DnsMessageView::parse(buf, offset, what)?;
+
+        let mut questions: Vec<DnsQuestionRecord> =
+            Vec::with_capacity(view.count(DnsSection::QUESTION) as usize);
+        for question in view.questions() {
+            questions.push(question?.to_record()?);
+        }
+        let mut answers: Vec<DnsResourceRecord> = Vec::new();
+        let mut authorities: Vec<DnsResourceRecord> = Vec::new();
+        let mut additionals: Vec<DnsResourceRecord> = Vec::new();
+        for record in view.records() {
+            let (section, rr) = record?;
+            match section {
+                DnsSection::ANSWER => answers.push(rr.to_record()?),
+                DnsSection::AUTHORITY => authorities.push(rr.to_record()?),
+                _ => additionals.push(rr.to_record()?)
+            }
+        }
+
+        // XXX should we check that all of buf was used? if not, we have unused bytes.
+
+        Ok((*view.header(), questions, answers, authorities, additionals))
+    }
+
     /* given a hostname, validate it as a dns name, per the rules in
-       https://datatracker.ietf.org/doc/html/rfc1035#section-2.3.1 */
-    pub fn is_valid_dns_name(name: &String) -> Result<(), String> {
+       https://datatracker.ietf.org/doc/html/rfc1035#section-2.3.1
+       the one exception is '_', which we allow so we can ask for service names like
+       _sip._tcp.example.com (see rfc2782 and rfc8552). */
+    pub fn is_valid_dns_name(name: &str) -> Result<(), DnsError> {
 
         let stripped = String::from(name.trim());
 
         // base cases
-        if stripped.len() == 0 || stripped == "." { 
+        if stripped.is_empty() || stripped == "." { 
             return Ok(()); 
         }
 
         let parts: Vec<&str> = stripped.split('.').collect();
+        let invalid = |reason: String| Err(DnsError::InvalidName { name: name.to_string(), reason });
 
         for (idx, label) in parts.iter().enumerate() {
 
@@ -1387,39 +2400,59 @@ pub mod dns_client_lib {
 
             // empty labels are not allowed, except at end, for trailing '.'
             if len == 0 && (idx != (parts.len() - 1)) {
-                return Err(String::from("Got an empty label."));
+                return invalid(String::from("Got an empty label."));
             }
 
             if len >= 64 {
-                return Err(format!("Got a label ({}) that is more than 63 characters.", label));
+                return invalid(format!("Got a label ({}) that is more than 63 characters.", label));
             }
 
-            // labels are only allowed to contain A-Z, a-z, 0-9, and '-'.
+            // labels are only allowed to contain A-Z, a-z, 0-9, '-' and '_'.
             for c in label.chars() {
                 match c {
-                    'a'..='z' | 'A'..='Z' | '0'..='9' | '-' => {}, // who needs regexes?
-                    _ => return Err(format!("Got a label ({label}) with a bad character ({c})."))
+                    'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => {}, // who needs regexes?
+                    _ => return invalid(format!("Got a label ({label}) with a bad character ({c})."))
                 }
             }
 
             if label.starts_with("-") {
-                return Err(format!("Got a label ({}) that starts with a hyphen.", label));
+                return invalid(format!("Got a label ({}) that starts with a hyphen.", label));
             }
 
             if label.ends_with("-") {
-                return Err(format!("Got a label ({}) that ends with a hyphen.", label));
+                return invalid(format!("Got a label ({}) that ends with a hyphen.", label));
             }
         }
 
         Ok(())
     }
 
-    pub fn make_ecs_option(subnet: &String) -> Result<DnsOPTRecordOption, String> {
+    /* the name to ask for PTR records of to find the hostname for addr:
+       the octets of a v4 address reversed under in-addr.arpa (rfc1035, section 3.5), or the
+       nibbles of a v6 address reversed under ip6.arpa (rfc3596, section 2.5). */
+    pub fn reverse_lookup_name(addr: &IpAddr) -> String {
+        match addr {
+            IpAddr::V4(v4) => {
+                let o = v4.octets();
+                format!("{}.{}.{}.{}.in-addr.arpa.", o[3], o[2], o[1], o[0])
+            },
+            IpAddr::V6(v6) => {
+                let mut ret = String::new();
+                for byte in v6.octets().iter().rev() {
+                    ret.push_str(&format!("{:x}.{:x}.", byte & 0xf, byte >> 4));
+                }
+                ret.push_str("ip6.arpa.");
+                ret
+            }
+        }
+    }
+
+    pub fn make_ecs_option(subnet: &str) -> Result<DnsOPTRecordOption, DnsError> {
         // family, source prefix-len, scope prefix-len, addr. see rfc7871
 
-        let cidr = match IpCidr::from_str(subnet.as_str()) {
+        let cidr = match IpCidr::from_str(subnet) {
             Ok(i) => i,
-            Err(e) => { return Err(e.to_string()); }
+            Err(e) => { return Err(DnsError::Config(format!("Bad ECS subnet '{subnet}': {e}"))); }
         };
 
         let mut v = match cidr { // family, from IANA address family numbers.
@@ -1433,7 +2466,7 @@ pub mod dns_client_lib {
                 let mask_str = &subnet[offset+1..];
                 let mask_u8 = match u8::from_str(mask_str) {
                     Ok(u) => u,
-                    Err(e) => { return Err(e.to_string()); }
+                    Err(e) => { return Err(DnsError::Config(format!("Bad ECS subnet mask '{mask_str}': {e}"))); }
                 };
                 v.push(mask_u8);
             },
@@ -1457,18 +2490,131 @@ pub mod dns_client_lib {
         Ok(DnsOPTRecordOption::new(8u16, v)) // ecs is code 8 - rfc7871.
     }
 
+    /* builds up a message, remembering the offset of every name written (and of every
+       suffix of those names), so that a later name ending in one of them can point back
+       at it instead of being written out in full. see rfc1035, section 4.1.4.
+       suffixes are matched case-sensitively, so that a name always reads back exactly
+       as it was written. */
+    #[derive(Debug, Default)]
+    pub struct DnsMessageWriter {
+        buf: Vec<u8>,
+        names: HashMap<String, u16>,
+        compress: bool,
+        lowercase: bool
+    }
+
+    impl DnsMessageWriter {
+        pub fn new() -> DnsMessageWriter {
+            DnsMessageWriter { buf: Vec::new(), names: HashMap::new(), compress: true, lowercase: false }
+        }
+
+        // a writer that never compresses, for bytes that aren't a whole message.
+        pub fn uncompressed() -> DnsMessageWriter {
+            DnsMessageWriter { buf: Vec::new(), names: HashMap::new(), compress: false, lowercase: false }
+        }
+
+        /* a writer for DNSSEC's canonical form (rfc4034, section 6.2): never compressed, and
+           names written with write_name are lowercased. */
+        pub fn canonical() -> DnsMessageWriter {
+            DnsMessageWriter { buf: Vec::new(), names: HashMap::new(), compress: false, lowercase: true }
+        }
+
+        pub fn len(&self) -> usize {
+            self.buf.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.buf.is_empty()
+        }
+
+        pub fn into_bytes(self) -> Vec<u8> {
+            self.buf
+        }
+
+        pub fn write_bytes(&mut self, bytes: &[u8]) {
+            self.buf.extend_from_slice(bytes);
+        }
+
+        pub fn write_u16(&mut self, v: u16) {
+            self.buf.extend_from_slice(&v.to_be_bytes());
+        }
+
+        pub fn write_u32(&mut self, v: u32) {
+            self.buf.extend_from_slice(&v.to_be_bytes());
+        }
+
+        // overwrite two already-written bytes at offset, e.g. to fill in an rdlen.
+        pub fn set_u16(&mut self, offset: usize, v: u16) {
+            self.buf[offset .. offset+2].copy_from_slice(&v.to_be_bytes());
+        }
+
+        /* write name, replacing the longest suffix of it that's already in the message
+           with a pointer if compress is set. names written with compress unset are still
+           remembered, so later names can point at them. */
+        pub fn write_name(&mut self, name: &str, compress: bool) -> Result<(), DnsError> {
+            let labels = wire_labels(name)?;
+            for i in 0 .. labels.len() {
+                let suffix = labels[i..].join(".");
+                if compress && self.compress {
+                    if let Some(ptr) = self.names.get(&suffix) {
+                        self.write_u16(0xC000 | ptr);
+                        return Ok(());
+                    }
+                }
+                // pointers only have 14 bits, so anything past that can't be pointed at.
+                if self.buf.len() <= 0x3FFF {
+                    self.names.entry(suffix).or_insert(self.buf.len() as u16);
+                }
+                self.buf.push(labels[i].len() as u8);
+                if self.lowercase {
+                    self.buf.extend_from_slice(labels[i].to_ascii_lowercase().as_bytes());
+                } else {
+                    self.buf.extend_from_slice(labels[i].as_bytes());
+                }
+            }
+            self.buf.push(0u8);
+            Ok(())
+        }
+    }
+
+    /* the labels of name, to be written out as they are. this only checks what the wire
+       format needs - labels of 1 to 63 bytes, and no more than 255 bytes in all (rfc1035,
+       section 3.1) - and not the hostname rules of is_valid_dns_name, so that any name read
+       off the wire, *.example. say, can be written back out. the root has no labels. */
+    fn wire_labels(name: &str) -> Result<Vec<&str>, DnsError> {
+        let stripped = name.trim();
+        let stripped = stripped.strip_suffix('.').unwrap_or(stripped);
+        if stripped.is_empty() {
+            return Ok(vec![]);
+        }
+        let invalid = |reason: String| Err(DnsError::InvalidName { name: name.to_string(), reason });
+        let labels: Vec<&str> = stripped.split('.').collect();
+        for label in &labels {
+            if label.is_empty() {
+                return invalid(String::from("Got an empty label."));
+            }
+            if label.len() > 63 {
+                return invalid(format!("Got a label ({label}) that is more than 63 bytes."));
+            }
+        }
+        // a length byte for each label, then the root's.
+        let len = labels.iter().map(|l| l.len() + 1).sum::<usize>() + 1;
+        if len > 255 {
+            return invalid(format!("Got a name that is {len} bytes on the wire, more than 255."));
+        }
+        Ok(labels)
+    }
+
     // given a hostname s, return the equivalent domain name in raw bytes
-    pub fn string_to_dns_name(name: &String) -> Result<Vec<u8>, String> {
+    pub fn string_to_dns_name(name: &str) -> Result<Vec<u8>, DnsError> {
         // this does all the validation of the name for us, which simplifies this fn.
-        if let Err(e) = is_valid_dns_name(&name) {
-            return Err(format!("'{}' doesn't appear to be a valid DNS name: {}", name, e));
-        }
+        is_valid_dns_name(name)?;
 
         let stripped = String::from(name.trim());
 
         let mut ret : Vec<u8> = Vec::new();
 
-        if stripped.len() == 0 || stripped == "." { // handle both root cases
+        if stripped.is_empty() || stripped == "." { // handle both root cases
             ret.push(0u8);
             return Ok(ret);
         }
@@ -1495,9 +2641,7 @@ pub mod dns_client_lib {
        of the buf pointed to by the compression pointer - only the bytes in this label, and just
        the two bytes for the compression pointer itself. See dns_name_to_string_test in tests.
     */
-    pub fn dns_name_to_string(buf: &Vec<u8>, offset: usize) -> Result<(String, usize), String> {
-        let mut labels: Vec<String> = Vec::new();
-
+    pub fn dns_name_to_string(buf: &[u8], offset: usize) -> Result<(String, usize), DnsError> {
         /*
            a dns name consists of a series of labels.
            labels consist of a len byte L , followed (optionally) by other bytes.
@@ -1512,89 +2656,10 @@ pub mod dns_client_lib {
            https://datatracker.ietf.org/doc/html/draft-ietf-dnsind-local-compression-05
            according to this site, "It is important that these pointers always point backwards."
            can we declare as invalid any pointers that are >= the current offset?
+           (we do - see view::DnsNameRef, which does the actual walking.)
          */
 
-        // result usize is how many bytes were read in this call.
-        fn _helper(buf: &Vec<u8>, offset: usize, labels: &mut Vec<String>) -> Result<usize, String> {
-            let buflen = buf.len();
-
-            if buflen == 0 {
-                return Err(String::from("Can't operate on empty name buffer."));
-            }
-
-            if offset >= buflen {
-                return Err(String::from("Offset outside of buffer bounds."));
-            }
-
-            let mut o = offset; // local mutable copy for work
-
-            loop {
-                let lenbyte = &buf[o];
-                match lenbyte {
-                    0 => { // null byte. done with this name.
-                        o += 1;
-                        break;
-                    },
-
-                    1..=63 => { // label. parse it out.
-                        o += 1; // done with len byte, go onto label.
-                        let top = o + (*lenbyte as usize);
-                        if o > buflen || top > buflen {
-                            return Err(String::from("Hit buffer bounds when parsing label."));
-                        }
-                        let label = &buf[o .. top];
-                        match String::from_utf8(label.to_vec()) {
-                            Ok(s) => labels.push(s),
-                            Err(e) => return Err(e.to_string())
-                        }
-                        o += *lenbyte as usize;
-                    },
-
-                    64..=191 => { // reserved. return err (for now)
-                        return Err(String::from("Got 10/01 in top bits of dns name length byte."));
-                    },
-
-                    192..=255 => { // compression. recurse!
-                        let mut twobytes = [0u8, 0u8];
-                        let top = o + 2;
-                        if top > buflen {
-                            return Err(String::from("Hit buffer bounds when parsing compression ptr."));
-                        }
-                        twobytes.clone_from_slice(&buf[o .. top]);
-                        let new_offset = (u16::from_be_bytes(twobytes) & 0x3FFF) as usize;
-                        /* technically it's not codified anywhere that compression pointers HAVE
-                           to be prior in the packet to the current one. if we want to support
-                           this, use a HashSet<usize> to keep track of visited offsets, so as
-                           to avoid loops in compression pointers. */
-                        if new_offset > offset {
-                            return Err(String::from("Got a forward-pointing compression pointer."));
-                        }
-                        if new_offset == offset {
-                            return Err(String::from("Got a self-referencing compression pointer."));
-                        }
-                        /* don't care about count of bytes read in this case, b/c it's from
-                         elsewhere in the packet. */
-                        let _ = _helper(&buf, new_offset, labels)?;
-                        o += 2; // compression ptr takes up len byte and next one too.
-                        break; // pointers are always at the end of the labels
-                    }
-                }
-            }
-
-            Ok(o - offset)
-        }
-
-        match _helper(&buf, offset, &mut labels) {
-            Ok(count) => {
-                match labels.len() {
-                    0 => Ok((String::from("."), 1)),
-                    _ => {
-                        labels.push(String::from(""));
-                        Ok((labels.join("."), count))
-                    }
-                }
-            },
-            Err(e) => Err(e)
-        }
+        let name = DnsNameRef::parse(buf, offset)?;
+        Ok((name.to_name()?, name.wire_len()))
     }
 }
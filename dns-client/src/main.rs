@@ -1,87 +1,315 @@
 use dns_client::dns_client_lib::*;
-use std::net::UdpSocket;
+use dns_client::dns_client_lib::client::{DnsClient, DEFAULT_RETRIES};
+use dns_client::dns_client_lib::transport::*;
+use dns_client::dns_client_lib::bulk::run_bulk;
+#[cfg(feature = "dot")]
+use dns_client::dns_client_lib::tls::{DnsTlsConfig, parse_spki_pin};
+#[cfg(feature = "doh")]
+use dns_client::dns_client_lib::https::{DnsDohConfig, DohMethod, parse_doh_url};
+#[cfg(feature = "odoh")]
+use dns_client::dns_client_lib::odoh::{DnsOdohConfig, fetch_odoh_configs, odoh_configs_to_bytes, parse_odoh_configs,
+                                       select_odoh_config};
+#[cfg(feature = "dnssec")]
+use dns_client::dns_client_lib::validator::DnsValidator;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
 use clap::Parser;
-use rand::Rng;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about=None)]
 struct Arguments {
-    #[clap(short='s', long, value_parser, default_value_t = String::from("8.8.8.8:53"))]
-    server: String,
+    #[clap(short='s', long, value_parser, default_value = "8.8.8.8",
+           help = "Server to query: ip, ip:port, [ipv6] or [ipv6]:port (port defaults to 53, \
+                   or 853 for TLS and QUIC). May be given more than once; attempts rotate through the servers. \
+                   With DoH support built in, this can instead be a single https:// url \
+                   (the proxy's url, with --odoh-target)")]
+    server: Vec<String>,
     #[clap(short='n', long, value_parser, default_value_t = String::from("google.com"))]
     qname: String,
     #[clap(short='t', long, value_parser, default_value_t = String::from("A"))]
     qtype: String,
     #[clap(short='c', long, value_parser, default_value_t = String::from("IN"))]
     qclass: String,
+    #[clap(short='x', long, value_parser,
+           help = "Reverse lookup: query PTR for the in-addr.arpa/ip6.arpa name of this address")]
+    reverse: Option<IpAddr>,
+    #[clap(short='i', long, value_parser, conflicts_with = "reverse",
+           help = "Look up every 'name [type] [class]' line of this file ('-' for stdin) instead of --qname, \
+                   printing a line per result as it comes in and a summary at the end")]
+    input: Option<String>,
+    #[clap(long, value_parser, default_value_t = 10, help = "With --input, how many queries to have in flight at once")]
+    concurrency: usize,
+    #[clap(long, value_parser, default_value_t = 0,
+           help = "With --input, the most queries to send per second (0 for no limit)")]
+    rate: u32,
     #[clap(short='u', long, value_parser)]
-    subnet: Option<String>
+    subnet: Option<String>,
+    #[clap(long, value_parser, help = "Set the AD bit on the query, asking the server to say if the answer was DNSSEC validated")]
+    ad: bool,
+    #[clap(long, value_parser, help = "Set the CD bit on the query, asking the server not to do DNSSEC validation")]
+    cd: bool,
+    #[cfg(feature = "dnssec")]
+    #[clap(long, value_parser,
+           help = "Validate the response with DNSSEC, from the root zone's trust anchors, and print whether it's secure")]
+    dnssec: bool,
+    #[clap(short='T', long, value_parser, help = "Send the query over TCP instead of UDP")]
+    tcp: bool,
+    #[cfg(feature = "dot")]
+    #[clap(long, value_parser, conflicts_with = "tcp",
+           help = "Send the query over TLS (DNS-over-TLS, RFC 7858)")]
+    tls: bool,
+    #[cfg(feature = "doq")]
+    #[clap(long, value_parser, conflicts_with_all = &["tcp", "tls"],
+           help = "Send the query over QUIC (DNS-over-QUIC, RFC 9250). Certificates are checked as for --tls")]
+    quic: bool,
+    #[cfg(feature = "dot")]
+    #[clap(long, value_parser,
+           help = "Name the server's TLS certificate must be valid for (defaults to the server's ip, \
+                   or the host of an https:// url)")]
+    tls_name: Option<String>,
+    #[cfg(feature = "dot")]
+    #[clap(long, value_parser,
+           help = "Base64 SHA-256 SPKI pin the server's certificate chain must match. \
+                   May be given more than once. Without --tls-name, the pin is all that's checked")]
+    tls_pin: Vec<String>,
+    #[cfg(feature = "dot")]
+    #[clap(long, value_parser,
+           help = "PEM file of extra CA certificates to trust for TLS")]
+    tls_ca: Option<String>,
+    #[cfg(feature = "doh")]
+    #[clap(long, value_parser, help = "Send DoH queries with GET instead of POST")]
+    get: bool,
+    #[cfg(feature = "odoh")]
+    #[clap(long, value_parser, conflicts_with = "get",
+           help = "https:// url of an Oblivious DoH (RFC 9230) target. Queries are encrypted to it \
+                   and sent through the proxy given with -s, so neither sees both who asks and what")]
+    odoh_target: Option<String>,
+    #[cfg(feature = "odoh")]
+    #[clap(long, value_parser, requires = "odoh-target",
+           help = "File holding the --odoh-target's ObliviousDoHConfigs, as saved by --odoh-fetch-configs")]
+    odoh_configs: Option<String>,
+    #[cfg(feature = "odoh")]
+    #[clap(long, value_parser, requires = "odoh-target", conflicts_with = "odoh-configs",
+           help = "Fetch the --odoh-target's ObliviousDoHConfigs straight from the target, save them to this file \
+                   and exit. The target sees your address doing this, so do it apart from your queries")]
+    odoh_fetch_configs: Option<String>,
+    #[clap(short='w', long, value_parser = parse_timeout, default_value = "5",
+           help = "Seconds to wait for the first attempt; doubles on each pass through the servers")]
+    timeout: Duration,
+    #[clap(short='r', long, value_parser, default_value_t = DEFAULT_RETRIES,
+           help = "Number of extra passes through the servers before giving up")]
+    retries: u32,
+    #[clap(short='v', long, value_parser, help = "Print every attempt made, with its outcome and latency")]
+    verbose: bool
 }
 
-fn make_query(args: &Arguments) -> Result<DnsQuery, String> {
-    // header
-    let mut rng = rand::thread_rng();
-    let qid: u16 = rng.gen();
-    let h = DnsHeader::new(qid, false, DnsOpcode::QUERY, false, false,
-                           true, false, DnsRcode::NOERROR);
+fn parse_timeout(s: &str) -> Result<Duration, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(Duration::from_secs_f64(secs)),
+        _ => Err(format!("'{s}' is not a positive number of seconds."))
+    }
+}
+
+fn transport(args: &Arguments) -> DnsTransport {
+    #[cfg(feature = "odoh")]
+    if args.odoh_target.is_some() {
+        return DnsTransport::ODOH;
+    }
+    #[cfg(feature = "doh")]
+    if args.server.iter().any(|s| s.trim().to_ascii_lowercase().starts_with("https://")) {
+        return DnsTransport::HTTPS;
+    }
+    #[cfg(feature = "dot")]
+    if args.tls {
+        return DnsTransport::TLS;
+    }
+    #[cfg(feature = "doq")]
+    if args.quic {
+        return DnsTransport::QUIC;
+    }
+    if args.tcp { DnsTransport::TCP } else { DnsTransport::UDP }
+}
 
-    // question
-    let qtype = DnsQType::from_string(&args.qtype)?;
-    let qclass = DnsQClass::from_string(&args.qclass)?;
-    let qrv : Vec<DnsQuestionRecord> =
-        vec![DnsQuestionRecord::new(args.qname.clone(), qtype, qclass)];
+// for an https:// server, the url to send queries to and the addresses to connect to for it.
+#[cfg(feature = "doh")]
+fn doh_config(args: &Arguments) -> Result<(DnsDohConfig, Vec<SocketAddr>), DnsError> {
+    #[allow(unused_mut)]
+    let mut other_transport = args.tcp || args.tls;
+    #[cfg(feature = "doq")]
+    { other_transport |= args.quic; }
+    if args.server.len() != 1 || other_transport {
+        return Err(DnsError::Config(String::from("An https:// server has to be the only server, without --tcp, --tls or --quic.")));
+    }
+    let url = parse_doh_url(&args.server[0])?;
+    let addrs = url.resolve()?;
+    let method = if args.get { DohMethod::GET } else { DohMethod::POST };
+    Ok((DnsDohConfig::new(url, method), addrs))
+}
 
-    // add on the opt RR to let them know we can handle big packets (4kB)
-    // and add on the ecs subnet if specified.
-    let optr = match &args.subnet {
-        Some(subnet) => DnsOPTRecord::new(vec![make_ecs_option(&subnet)?]),
-        None => DnsOPTRecord::new(vec![])
+/* for --odoh-target, the proxy and target to send queries through, and the addresses to
+   connect to for the proxy. the target's configs come from a file: fetching them from the
+   target just before the query would show it our address, right as the query comes in. */
+#[cfg(feature = "odoh")]
+fn odoh_config(args: &Arguments) -> Result<(DnsOdohConfig, Vec<SocketAddr>), DnsError> {
+    let (proxy, addrs) = doh_config(args)?;
+    let Some(target) = &args.odoh_target else {
+        return Err(DnsError::Config(String::from("No --odoh-target given.")));
     };
-    let addv : Vec<DnsResourceRecord> =
-        vec![DnsResourceRecord::new(String::from("."), DnsQType::OPT,
-             DnsQClass::RESERVED(4096u16), 0, DnsResourceRecordEnum::OPT(optr))];
+    let target = parse_doh_url(target)?;
+    let Some(path) = &args.odoh_configs else {
+        return Err(DnsError::Config(String::from("--odoh-target needs the target's configs from --odoh-configs \
+                                                  (save them first with --odoh-fetch-configs).")));
+    };
+    let buf = match std::fs::read(path) {
+        Ok(b) => b,
+        Err(e) => return Err(DnsError::Config(format!("Couldn't read ODoH configs from {path}: {e}")))
+    };
+    let config = select_odoh_config(&parse_odoh_configs(&buf)?)?;
+    Ok((DnsOdohConfig::new(proxy.url().clone(), target, config), addrs))
+}
 
-    Ok(DnsQuery::new(h, qrv, Some(addv)))
+// for --odoh-fetch-configs: get the target's configs from the target itself, and save them for later.
+#[cfg(feature = "odoh")]
+fn save_odoh_configs(args: &Arguments, tls: &DnsTlsConfig, path: &str) -> Result<(), DnsError> {
+    let Some(target) = &args.odoh_target else {
+        return Err(DnsError::Config(String::from("No --odoh-target given.")));
+    };
+    let target = parse_doh_url(target)?;
+    let mut last_err = DnsError::Config(format!("Got no addresses for ODoH target {}.", target.host()));
+    for addr in target.resolve()? {
+        match fetch_odoh_configs(&addr, &target, tls, args.timeout) {
+            Ok(configs) => {
+                // make sure there's one we can use before saving them.
+                select_odoh_config(&configs)?;
+                if let Err(e) = std::fs::write(path, odoh_configs_to_bytes(&configs)?) {
+                    return Err(DnsError::Config(format!("Couldn't write ODoH configs to {path}: {e}")));
+                }
+                println!("Saved {} ODoH config(s) from {target} to {path}.", configs.len());
+                return Ok(());
+            },
+            Err(e) => last_err = e
+        }
+    }
+    Err(last_err)
 }
 
-fn main() {
-    let args = Arguments::parse();
+#[cfg(feature = "dot")]
+fn tls_config(args: &Arguments) -> Result<DnsTlsConfig, DnsError> {
+    let mut pins: Vec<[u8; 32]> = Vec::new();
+    for pin in &args.tls_pin {
+        pins.push(parse_spki_pin(pin)?);
+    }
+    let mut config = DnsTlsConfig::new(args.tls_name.clone(), pins);
+    if let Some(path) = &args.tls_ca {
+        match std::fs::read(path) {
+            Ok(pem) => { config.add_ca_pem(&pem)?; },
+            Err(e) => return Err(DnsError::Config(format!("Couldn't read CA file {path}: {e}")))
+        }
+    }
+    Ok(config)
+}
 
-    let q = match make_query(&args) {
-        Ok(q) => q,
-        Err(e) => { println!("{e}"); return; }
+// the --input mode: every line of the file through client, streaming out the results.
+fn run_input(client: &DnsClient, path: &str, args: &Arguments) -> Result<(), DnsError> {
+    let reader: Box<dyn Read + Send> = if path == "-" {
+        Box::new(std::io::stdin())
+    } else {
+        match File::open(path) {
+            Ok(f) => Box::new(f),
+            Err(e) => return Err(DnsError::Config(format!("Couldn't open input file {path}: {e}")))
+        }
     };
-    println!("{q}");
+    /* a line that isn't utf-8 is decoded lossily, so it comes out as a BADLINE rather than
+       ending the run. a read error does end it, but with an error rather than quietly. */
+    let mut read_error = None;
+    let lines = BufReader::new(reader).split(b'\n').map_while(|line| match line {
+        Ok(line) => Some(String::from_utf8_lossy(&line).into_owned()),
+        Err(e) => {
+            read_error = Some(e);
+            None
+        }
+    });
+    let summary = run_bulk(client, lines, args.concurrency, args.rate, |result| println!("{result}"));
+    println!("{summary}");
+    match read_error {
+        Some(e) => Err(DnsError::Config(format!("Couldn't read input file {path}: {e}"))),
+        None => Ok(())
+    }
+}
 
-    let qbytes = match q.to_bytes() {
-        Ok(b) => b,
-        Err(e) => { println!("Got an error creating query: {e}"); return }
-    };
+fn run(args: &Arguments) -> Result<(), DnsError> {
+    let transport = transport(args);
+    #[cfg(feature = "dot")]
+    let tls = tls_config(args)?;
+    let mut servers: Vec<SocketAddr> = Vec::new();
+    #[cfg(feature = "doh")]
+    let mut doh: Option<DnsDohConfig> = None;
+    #[cfg(feature = "doh")]
+    if transport == DnsTransport::HTTPS {
+        let (config, addrs) = doh_config(args)?;
+        doh = Some(config);
+        servers = addrs;
+    }
+    #[cfg(feature = "odoh")]
+    if let Some(path) = &args.odoh_fetch_configs {
+        return save_odoh_configs(args, &tls, path);
+    }
+    #[cfg(feature = "odoh")]
+    let mut odoh: Option<DnsOdohConfig> = None;
+    #[cfg(feature = "odoh")]
+    if transport == DnsTransport::ODOH {
+        let (config, addrs) = odoh_config(args)?;
+        odoh = Some(config);
+        servers = addrs;
+    }
+    if servers.is_empty() {
+        for s in &args.server {
+            servers.push(parse_server_addr_with_port(s, transport.default_port())?);
+        }
+    }
+    let mut client = DnsClient::new(servers, args.timeout, args.retries, transport);
+    client.set_ecs_subnet(args.subnet.clone());
+    client.set_ad(args.ad);
+    client.set_cd(args.cd);
+    #[cfg(feature = "dnssec")]
+    if args.dnssec {
+        client.set_validator(Some(DnsValidator::root()));
+    }
+    #[cfg(feature = "dot")]
+    client.set_tls_config(tls);
+    #[cfg(feature = "doh")]
+    client.set_doh_config(doh);
+    #[cfg(feature = "odoh")]
+    client.set_odoh_config(odoh);
 
-    let socket = UdpSocket::bind("0.0.0.0:0").expect("couldn't bind to address");
-    socket.connect(&args.server.as_str()).expect("couldn't connect to server");
+    if let Some(path) = &args.input {
+        return run_input(&client, path, args);
+    }
 
-    match socket.send(qbytes.as_slice()) {
-        Ok(_) => {
-            // TODO what if bytes sent != qbytes.len() ?
-            let mut rbuf = [0 as u8; 65535];
+    let (qname, qtype) = match &args.reverse {
+        Some(addr) => (reverse_lookup_name(addr), DnsQType::PTR),
+        None => (args.qname.clone(), DnsQType::from_string(&args.qtype)?)
+    };
+    let q = client.make_query(&qname, qtype, DnsQClass::from_string(&args.qclass)?)?;
+    println!("{q}");
 
-            match socket.recv(&mut rbuf) {
-                Ok(response_length) => {
-                    let buf = rbuf[0 .. response_length].to_vec();
+    let (result, attempts) = client.send_with_attempts(&q);
+    if args.verbose {
+        for (i, attempt) in attempts.iter().enumerate() {
+            println!("Attempt {}: {attempt}", i + 1);
+        }
+    }
+    println!("{}", result?);
+    Ok(())
+}
+
+fn main() {
+    let args = Arguments::parse();
 
-                    let response = match DnsResponse::from_bytes(&buf, 0) {
-                        Ok(r) => r,
-                        Err(e) => {
-                            println!("Error parsing response: {e}");
-                            return;
-                        }
-                    };
-                    println!("{response}");
-                },
-                Err(e) => println!("Error reading response from server: {e}")
-            }
-        },
-        Err(e) => println!("Error sending to socket: {e}")
+    if let Err(e) = run(&args) {
+        println!("{e}");
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,132 @@
+#[cfg(all(test, feature = "async"))]
+mod tests {
+
+    use dns_client::dns_client_lib::*;
+    use dns_client::dns_client_lib::async_client::*;
+    use dns_client::dns_client_lib::client::*;
+    use dns_client::dns_client_lib::transport::*;
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpListener, UdpSocket};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap()
+    }
+
+    fn make_test_query(id: u16, name: &str) -> DnsQuery {
+        let h = DnsHeader::new(id, false, DnsOpcode::QUERY, false, false,
+                               true, false, DnsRcode::NOERROR);
+        let qrv = vec![DnsQuestionRecord::new(String::from(name), DnsQType::A, DnsQClass::IN)];
+        DnsQuery::new(h, qrv, None)
+    }
+
+    /* a stand-in server that waits for count queries, then answers them in reverse order -
+       after a stray answer with the same id as the first, to a question nobody asked. the
+       thread hands back the addresses the queries came from. */
+    fn spawn_reversing_server(count: usize) -> (SocketAddr, thread::JoinHandle<Vec<SocketAddr>>) {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = udp.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut queries: Vec<(Vec<u8>, SocketAddr)> = Vec::new();
+            for _ in 0 .. count {
+                let mut buf = [0u8; 512];
+                let (len, from) = udp.recv_from(&mut buf).unwrap();
+                buf[2] |= 0x80;
+                queries.push((buf[.. len].to_vec(), from));
+            }
+
+            let id = u16::from_be_bytes([queries[0].0[0], queries[0].0[1]]);
+            let mut stray = make_test_query(id, "stray.test.").to_bytes().unwrap();
+            stray[2] |= 0x80;
+            udp.send_to(&stray, queries[0].1).unwrap();
+
+            for (response, from) in queries.iter().rev() {
+                udp.send_to(response, from).unwrap();
+            }
+            queries.into_iter().map(|(_, from)| from).collect()
+        });
+        (addr, handle)
+    }
+
+    #[test]
+    fn async_demux_test() {
+        let (server, handle) = spawn_reversing_server(3);
+        let client = Arc::new(AsyncDnsClient::new(vec![server], TIMEOUT, 0, DnsTransport::UDP));
+
+        let results = runtime().block_on(async {
+            // the same id for every query, so only their questions tell the answers apart.
+            let mut tasks = Vec::new();
+            for name in ["a.test.", "b.test.", "c.test."] {
+                let client = client.clone();
+                tasks.push(tokio::spawn(async move {
+                    client.send_with_attempts(&make_test_query(0x1234, name)).await
+                }));
+            }
+            let mut results = Vec::new();
+            for task in tasks {
+                results.push(task.await.unwrap());
+            }
+            results
+        });
+
+        let froms = handle.join().unwrap();
+        assert!(froms.iter().all(|f| *f == froms[0]), "queries went out more than one socket: {froms:?}");
+        for ((result, attempts), name) in results.into_iter().zip(["a.test.", "b.test.", "c.test."]) {
+            let response = result.unwrap();
+            assert!(response.header().response());
+            assert_eq!(response.questions()[0], DnsQuestionRecord::new(String::from(name), DnsQType::A, DnsQClass::IN));
+            assert_eq!(attempts.len(), 1);
+            assert_eq!(attempts[0].outcome(), &DnsAttemptOutcome::Answered);
+            // every query with that id saw the stray answer, and turned it down.
+            assert_eq!(attempts[0].rejected().len(), 1, "{:?}", attempts[0].rejected());
+            assert!(attempts[0].rejected()[0].contains("stray.test."));
+        }
+    }
+
+    #[test]
+    fn async_timeout_test() {
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let silent_addr = silent.local_addr().unwrap();
+        let client = AsyncDnsClient::new(vec![silent_addr], Duration::from_millis(50), 1, DnsTransport::UDP);
+
+        let (result, attempts) = runtime().block_on(client.send_with_attempts(&make_test_query(1, "a.test.")));
+        assert_eq!(result.err(), Some(DnsError::Server { server: silent_addr,
+                                                         error: Box::new(DnsError::Timeout(Duration::from_millis(100))) }));
+        assert_eq!(attempts.len(), 2);
+        assert_eq!(attempts[0].timeout(), Duration::from_millis(50));
+
+        let client = AsyncDnsClient::new(vec![], TIMEOUT, 0, DnsTransport::UDP);
+        assert_eq!(runtime().block_on(client.query("google.com.", DnsQType::A, DnsQClass::IN)),
+                   Err(DnsError::NoServers));
+    }
+
+    #[test]
+    fn async_tcp_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let query = read_tcp_frame(&mut stream).unwrap();
+            let mut response = query.clone();
+            response[2] |= 0x80;
+            // something for some other query first, which should be passed over.
+            let mut other = response.clone();
+            other[0] ^= 0xFF;
+            stream.write_all(&tcp_frame(&other).unwrap()).unwrap();
+            stream.write_all(&tcp_frame(&response).unwrap()).unwrap();
+            let _ = stream.read(&mut [0u8; 1]);
+        });
+
+        let client = AsyncDnsClient::new(vec![server], TIMEOUT, 0, DnsTransport::TCP);
+        let q = client.make_query("google.com.", DnsQType::A, DnsQClass::IN).unwrap();
+        let (result, attempts) = runtime().block_on(client.send_with_attempts(&q));
+        handle.join().unwrap();
+        assert_eq!(result.unwrap().header().id(), q.header().id());
+        assert_eq!(attempts[0].transport(), DnsTransport::TCP);
+        assert_eq!(attempts[0].rejected().len(), 1);
+    }
+}
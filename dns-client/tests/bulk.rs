@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+
+    use dns_client::dns_client_lib::*;
+    use dns_client::dns_client_lib::bulk::*;
+    use dns_client::dns_client_lib::client::*;
+    use dns_client::dns_client_lib::transport::*;
+    use std::net::{SocketAddr, UdpSocket};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /* a stand-in server that echoes back every query with QR set, until it's been quiet for
+       a second - except for names with "silent" in them, which it never answers, and names
+       with "nx" in them, which get NXDOMAIN. */
+    fn spawn_bulk_server() -> (SocketAddr, thread::JoinHandle<usize>) {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        udp.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        let addr = udp.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut count = 0;
+            let mut buf = [0u8; 512];
+            while let Ok((len, from)) = udp.recv_from(&mut buf) {
+                count += 1;
+                let query = &buf[.. len];
+                if query.windows(6).any(|w| w == b"silent") {
+                    continue;
+                }
+                let mut response = query.to_vec();
+                response[2] |= 0x80;
+                if query.windows(2).any(|w| w == b"nx") {
+                    response[3] |= 0x03;
+                }
+                udp.send_to(&response, from).unwrap();
+            }
+            count
+        });
+        (addr, handle)
+    }
+
+    #[test]
+    fn parse_bulk_line_test() {
+        assert_eq!(parse_bulk_line("example.com"),
+                   Ok(Some(BulkLookup::new(String::from("example.com"), DnsQType::A, DnsQClass::IN))));
+        assert_eq!(parse_bulk_line("  example.com  MX  # the mail servers"),
+                   Ok(Some(BulkLookup::new(String::from("example.com"), DnsQType::MX, DnsQClass::IN))));
+        assert_eq!(parse_bulk_line("example.com AAAA CH"),
+                   Ok(Some(BulkLookup::new(String::from("example.com"), DnsQType::AAAA, DnsQClass::CH))));
+        assert_eq!(parse_bulk_line(""), Ok(None));
+        assert_eq!(parse_bulk_line("# just a comment"), Ok(None));
+
+        assert!(parse_bulk_line("example.com NOPE").is_err());
+        assert!(parse_bulk_line("exa@mple.com").is_err());
+        assert_eq!(parse_bulk_line("example.com A IN extra"),
+                   Err(DnsError::Config(String::from("Expected 'name [type] [class]', got 'example.com A IN extra'."))));
+    }
+
+    #[test]
+    fn rate_limiter_test() {
+        let limiter = RateLimiter::new(20);
+        let start = Instant::now();
+        for _ in 0 .. 5 {
+            limiter.wait();
+        }
+        // the first goes right away, then one every 50ms.
+        assert!(start.elapsed() >= Duration::from_millis(200), "{:?}", start.elapsed());
+
+        let limiter = RateLimiter::new(0);
+        let start = Instant::now();
+        for _ in 0 .. 1000 {
+            limiter.wait();
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn run_bulk_test() {
+        let (server, handle) = spawn_bulk_server();
+        let client = DnsClient::new(vec![server], Duration::from_millis(200), 0, DnsTransport::UDP);
+
+        let mut input: Vec<String> = (0 .. 20).map(|i| format!("host{i}.example.com")).collect();
+        input.push(String::from("nx.example.com TXT"));
+        input.push(String::from("silent.example.com"));
+        input.push(String::from(""));
+        input.push(String::from("bad@name.example.com"));
+
+        let mut lines: Vec<String> = Vec::new();
+        let start = Instant::now();
+        let summary = run_bulk(&client, input.into_iter(), 8, 0, |r| lines.push(r.to_string()));
+        // with 8 in flight, waiting out the silent one doesn't hold up everything else.
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(handle.join().unwrap(), 22);
+
+        assert_eq!(summary.total(), 23);
+        assert_eq!(summary.rcodes().get("NOERROR"), Some(&20));
+        assert_eq!(summary.rcodes().get("NAMERR"), Some(&1));
+        assert_eq!(summary.timeouts(), 1);
+        assert_eq!(summary.errors(), 0);
+        assert_eq!(summary.bad_lines(), 1);
+        assert_eq!(summary.to_string(), "Summary: 23 lookups; NAMERR 1; NOERROR 20; TIMEOUT 1; ERROR 0; BADLINE 1");
+
+        assert_eq!(lines.len(), 23);
+        assert!(lines.iter().any(|l| l.starts_with("host7.example.com A IN NOERROR ")), "{lines:?}");
+        assert!(lines.iter().any(|l| l.starts_with("nx.example.com TXT IN NAMERR ")), "{lines:?}");
+        assert!(lines.iter().any(|l| l.starts_with("silent.example.com A IN TIMEOUT ")), "{lines:?}");
+        assert!(lines.iter().any(|l| l.starts_with("line 24: BADLINE ")), "{lines:?}");
+    }
+}
@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests {
+
+    use dns_client::dns_client_lib::*;
+    use dns_client::dns_client_lib::client::*;
+    use dns_client::dns_client_lib::transport::*;
+    use std::net::{SocketAddr, UdpSocket};
+    use std::thread;
+    use std::time::Duration;
+
+    // a stand-in server that answers one query by echoing it back with QR set.
+    fn spawn_echo_server() -> (SocketAddr, thread::JoinHandle<()>) {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = udp.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, from) = udp.recv_from(&mut buf).unwrap();
+            buf[2] |= 0x80;
+            udp.send_to(&buf[..len], from).unwrap();
+        });
+        (addr, handle)
+    }
+
+    #[test]
+    fn dnsclient_make_query_test() {
+        let client = DnsClient::new(vec![], DEFAULT_TIMEOUT, DEFAULT_RETRIES, DnsTransport::UDP);
+        let q = client.make_query("google.com.", DnsQType::A, DnsQClass::IN).unwrap();
+        let bytes = q.to_bytes().unwrap();
+        assert_eq!(&bytes[2..12], &[0x01, 0x00,   // RD set
+                                    0x00, 0x01,   // qcount
+                                    0x00, 0x00,   // ancount
+                                    0x00, 0x00,   // authcount
+                                    0x00, 0x01]); // addcount - the OPT RR
+        assert_eq!(&bytes[bytes.len() - 11 ..],
+                   &[0x00, 0x00, 0x29, 0x10, 0x00, // root, OPT, payload 4096
+                     0x00, 0x00, 0x00, 0x00,       // ttl (ext. rcode/flags)
+                     0x00, 0x00]);                 // rdlen
+
+        assert!(client.make_query("goo@gle.com", DnsQType::A, DnsQClass::IN).is_err());
+
+        let mut client = client;
+        client.set_ad(true);
+        client.set_cd(true);
+        let q = client.make_query("google.com.", DnsQType::A, DnsQClass::IN).unwrap();
+        assert_eq!(&q.to_bytes().unwrap()[2..4], &[0x01, 0x30]); // RD, AD and CD set
+    }
+
+    #[test]
+    fn dnsclient_query_test() {
+        let (server, handle) = spawn_echo_server();
+        let client = DnsClient::new(vec![server], DEFAULT_TIMEOUT, 0, DnsTransport::UDP);
+        let response = client.query("google.com.", DnsQType::A, DnsQClass::IN).unwrap();
+        assert!(response.header().response());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn dnsclient_failover_test() {
+        // nothing will ever answer on this one.
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (server, handle) = spawn_echo_server();
+
+        let silent_addr = silent.local_addr().unwrap();
+        let client = DnsClient::new(vec![silent_addr, server],
+                                    Duration::from_millis(100), 0, DnsTransport::UDP);
+        let q = client.make_query("google.com.", DnsQType::A, DnsQClass::IN).unwrap();
+        let (result, attempts) = client.send_with_attempts(&q);
+        assert!(result.is_ok());
+        handle.join().unwrap();
+
+        assert_eq!(attempts.len(), 2);
+        assert_eq!(attempts[0].server(), silent_addr);
+        assert_eq!(attempts[0].outcome(), &DnsAttemptOutcome::Failed(
+            DnsError::Timeout(Duration::from_millis(100))));
+        assert!(attempts[0].latency() >= Duration::from_millis(100));
+        assert_eq!(attempts[1].server(), server);
+        assert_eq!(attempts[1].outcome(), &DnsAttemptOutcome::Answered);
+        assert_eq!(attempts[1].transport(), DnsTransport::UDP);
+    }
+
+    #[test]
+    fn dnsclient_attempt_timeout_test() {
+        let a: SocketAddr = "127.0.0.1:53".parse().unwrap();
+        let b: SocketAddr = "[::1]:53".parse().unwrap();
+
+        // one server: every attempt is a new pass, so the timeout doubles each time.
+        let client = DnsClient::new(vec![a], Duration::from_secs(1), 3, DnsTransport::UDP);
+        assert_eq!(client.attempt_timeout(0), Duration::from_secs(1));
+        assert_eq!(client.attempt_timeout(1), Duration::from_secs(2));
+        assert_eq!(client.attempt_timeout(2), Duration::from_secs(4));
+        assert_eq!(client.attempt_timeout(3), Duration::from_secs(8));
+
+        // two servers: timeout doubles every other attempt.
+        let client = DnsClient::new(vec![a, b], Duration::from_secs(1), 3, DnsTransport::UDP);
+        assert_eq!(client.attempt_timeout(0), Duration::from_secs(1));
+        assert_eq!(client.attempt_timeout(1), Duration::from_secs(1));
+        assert_eq!(client.attempt_timeout(2), Duration::from_secs(2));
+        assert_eq!(client.attempt_timeout(3), Duration::from_secs(2));
+
+        // and it never goes past the cap.
+        assert_eq!(client.attempt_timeout(40), MAX_ATTEMPT_TIMEOUT);
+    }
+
+    #[test]
+    fn dnsclient_timeout_test() {
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let silent_addr = silent.local_addr().unwrap();
+        let client = DnsClient::new(vec![silent_addr], Duration::from_millis(50), 1, DnsTransport::UDP);
+        let q = client.make_query("google.com.", DnsQType::A, DnsQClass::IN).unwrap();
+        let (result, attempts) = client.send_with_attempts(&q);
+        // the error is the last attempt's, and says which server it came from.
+        let e = result.unwrap_err();
+        assert!(e.is_timeout());
+        assert_eq!(e, DnsError::Server { server: silent_addr,
+                                         error: Box::new(DnsError::Timeout(Duration::from_millis(100))) });
+        assert_eq!(e.to_string(), format!("{silent_addr}: Timed out after 100ms waiting for response."));
+        assert_eq!(std::error::Error::source(&e).map(|s| s.to_string()),
+                   Some(String::from("Timed out after 100ms waiting for response.")));
+        assert_eq!(attempts.len(), 2);
+        assert_eq!(attempts[0].timeout(), Duration::from_millis(50));
+        assert_eq!(attempts[1].timeout(), Duration::from_millis(100));
+
+        let client = DnsClient::new(vec![], DEFAULT_TIMEOUT, DEFAULT_RETRIES, DnsTransport::UDP);
+        assert_eq!(client.query("google.com.", DnsQType::A, DnsQClass::IN),
+                   Err(DnsError::NoServers));
+    }
+}
@@ -0,0 +1,17 @@
+// helpers shared by the transport tests. the tls ones are only built with a tls transport.
+#![allow(dead_code, unused_imports)]
+
+#[cfg(feature = "dot")]
+mod tls;
+#[cfg(feature = "dot")]
+pub use tls::*;
+
+use dns_client::dns_client_lib::*;
+
+// a recursive query for google.com A, with the given id.
+pub fn make_test_query(id: u16) -> DnsQuery {
+    let h = DnsHeader::new(id, false, DnsOpcode::QUERY, false, false,
+                           true, false, DnsRcode::NOERROR);
+    let qrv = vec![DnsQuestionRecord::new(String::from("google.com."), DnsQType::A, DnsQClass::IN)];
+    DnsQuery::new(h, qrv, None)
+}
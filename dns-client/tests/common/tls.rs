@@ -0,0 +1,50 @@
+// helpers shared by the tls stand-in servers.
+
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+use rustls::ServerConfig;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use std::sync::Arc;
+
+// a CA, and a certificate for dns.test signed by it.
+pub struct TestPki {
+    pub ca_pem: String,
+    pub chain: Vec<CertificateDer<'static>>,
+    pub key: PrivateKeyDer<'static>,
+    pub spki: Vec<u8>
+}
+
+pub fn make_test_pki() -> TestPki {
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params.distinguished_name.push(DnType::CommonName, "dns-client test CA");
+    let ca_key = KeyPair::generate().unwrap();
+    let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+
+    let params = CertificateParams::new(vec![String::from("dns.test")]).unwrap();
+    let key = KeyPair::generate().unwrap();
+    let cert = params.signed_by(&key, &ca_cert, &ca_key).unwrap();
+
+    TestPki {
+        ca_pem: ca_cert.pem(),
+        chain: vec![cert.der().clone(), ca_cert.der().clone()],
+        key: PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der())),
+        spki: key.public_key_der()
+    }
+}
+
+pub fn pin_of(spki: &[u8]) -> [u8; 32] {
+    let mut ret = [0u8; 32];
+    ret.copy_from_slice(ring::digest::digest(&ring::digest::SHA256, spki).as_ref());
+    ret
+}
+
+// a server config presenting the dns.test certificate, offering alpn.
+pub fn server_config(pki: &TestPki, alpn: &[&[u8]]) -> Arc<ServerConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions().unwrap()
+        .with_no_client_auth()
+        .with_single_cert(pki.chain.clone(), pki.key.clone_key()).unwrap();
+    config.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+    Arc::new(config)
+}
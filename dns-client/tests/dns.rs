@@ -5,7 +5,10 @@ mod tests {
        when writing tests using raw bytes. */
 
     use dns_client::dns_client_lib::*;
-    use std::net::{Ipv4Addr,Ipv6Addr};
+    use dns_client::dns_client_lib::rdata::RData;
+    use std::net::{IpAddr,Ipv4Addr,Ipv6Addr};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
     use std::str::FromStr;
 
     #[test]
@@ -25,26 +28,35 @@ mod tests {
     fn dnsrcode_from_u8_test() {
         assert_eq!(DnsRcode::from_u8(0), DnsRcode::NOERROR);
         assert_eq!(DnsRcode::from_u8(1), DnsRcode::FORMERR);
-        assert_eq!(DnsRcode::from_u8(42), DnsRcode::RESERVED);
+        assert_eq!(DnsRcode::from_u8(11), DnsRcode::DSOTYPENI);
+        assert_eq!(DnsRcode::from_u8(42), DnsRcode::RESERVED(42));
+        assert_eq!(DnsRcode::from_u16(16), DnsRcode::BADSIG);
+        assert_eq!(DnsRcode::from_u16(22), DnsRcode::BADTRUNC);
+        assert_eq!(DnsRcode::from_u16(3841), DnsRcode::RESERVED(3841));
+        for value in 0 .. 30 {
+            assert_eq!(DnsRcode::from_u16(value).to_u16(), value);
+        }
     }
 
     #[test]
     fn dnsrcode_fmt_test() {
         assert_eq!(format!("{}", DnsRcode::NOERROR), String::from("NOERROR"));
-        assert_eq!(format!("{}", DnsRcode::RESERVED), String::from("RESERVED"));
+        assert_eq!(format!("{}", DnsRcode::BADKEY), String::from("BADKEY"));
+        assert_eq!(format!("{}", DnsRcode::RESERVED(42)), String::from("RESERVED(42)"));
+        assert_eq!(DnsRcode::RESERVED(3841).to_string(), "RESERVED(3841)");
     }
 
     #[test]
     fn dnsqtype_from_u16_test() {
         assert_eq!(DnsQType::from_u16(1), DnsQType::A);
         assert_eq!(DnsQType::from_u16(28), DnsQType::AAAA);
-        assert_eq!(DnsQType::from_u16(253), DnsQType::RESERVED);
+        assert_eq!(DnsQType::from_u16(253), DnsQType::RESERVED(253));
     }
 
     #[test]
     fn dnsqtype_fmt_test() {
         assert_eq!(format!("{}", DnsQType::A), String::from("A"));
-        assert_eq!(format!("{}", DnsQType::RESERVED), String::from("RESERVED"));
+        assert_eq!(format!("{}", DnsQType::RESERVED(253)), String::from("RESERVED"));
     }
 
     #[test]
@@ -65,6 +77,14 @@ mod tests {
         let header = DnsHeader::new(
             0xFF, false, DnsOpcode::QUERY, true, false, false, false, DnsRcode::NOERROR);
         assert_eq!(header.flags_to_u16(), 0x400u16);
+        let mut header = DnsHeader::new(
+            0xFF, false, DnsOpcode::QUERY, false, false, false, false, DnsRcode::NOERROR);
+        header.set_cd(true);
+        assert_eq!(header.flags_to_u16(), 0x10u16);
+        header.set_ad(true);
+        assert_eq!(header.flags_to_u16(), 0x30u16);
+        header.set_z(true);
+        assert_eq!(header.flags_to_u16(), 0x70u16);
 
         // opcodes
         let header = DnsHeader::new(
@@ -90,6 +110,13 @@ mod tests {
         let header = DnsHeader::new(
             0xFF, false, DnsOpcode::QUERY, false, false, false, false, DnsRcode::REFUSED);
         assert_eq!(header.flags_to_u16(), 0x5u16);
+        let header = DnsHeader::new(
+            0xFF, false, DnsOpcode::QUERY, false, false, false, false, DnsRcode::RESERVED(12));
+        assert_eq!(header.flags_to_u16(), 0xCu16);
+        // only the lower 4 bits fit in the header; the rest go in the OPT RR.
+        let header = DnsHeader::new(
+            0xFF, false, DnsOpcode::QUERY, false, false, false, false, DnsRcode::BADCOOKIE);
+        assert_eq!(header.flags_to_u16(), 0x7u16);
 
         // and in combination, or with response bit set
         let header = DnsHeader::new(
@@ -125,7 +152,7 @@ mod tests {
 
         // root (".")
         let buf: Vec<u8> = vec![0x00];
-        assert_eq!(string_to_dns_name(&String::from("")), Ok(buf.clone()));
+        assert_eq!(string_to_dns_name(""), Ok(buf.clone()));
         assert_eq!(string_to_dns_name(&String::from(".")), Ok(buf.clone()));
         assert_eq!(string_to_dns_name(&String::from(" ")), Ok(buf.clone()));
         
@@ -141,25 +168,29 @@ mod tests {
 
         // empty label 
         assert_eq!(is_valid_dns_name(&String::from("foo..bar")),
-                   Err(String::from("Got an empty label.")));
+                   Err(DnsError::InvalidName { name: String::from("foo..bar"), reason: String::from("Got an empty label.") }));
         assert_eq!(is_valid_dns_name(&String::from("..")),
-                   Err(String::from("Got an empty label.")));
+                   Err(DnsError::InvalidName { name: String::from(".."), reason: String::from("Got an empty label.") }));
 
         // label too long
         let name = String::from(
             "a1234567890123456789012345678901234567890123456789012345678901234567890");
         assert_eq!(is_valid_dns_name(&name), 
-                   Err(format!("Got a label ({}) that is more than 63 characters.", name)));
+                   Err(DnsError::InvalidName { name: name.clone(),
+                                              reason: format!("Got a label ({}) that is more than 63 characters.", name) }));
 
         // label starts/ends with a hyphen
         assert_eq!(is_valid_dns_name(&String::from("-foo.com")), 
-                   Err(String::from("Got a label (-foo) that starts with a hyphen.")));
+                   Err(DnsError::InvalidName { name: String::from("-foo.com"), reason: String::from("Got a label (-foo) that starts with a hyphen.") }));
         assert_eq!(is_valid_dns_name(&String::from("foo-.com")), 
-                   Err(String::from("Got a label (foo-) that ends with a hyphen.")));
+                   Err(DnsError::InvalidName { name: String::from("foo-.com"), reason: String::from("Got a label (foo-) that ends with a hyphen.") }));
+
+        // underscores are fine, for service names
+        assert_eq!(is_valid_dns_name("_sip._tcp.example.com."), Ok(()));
 
         // label contains non-alphanumeric/hyphen characters
         assert_eq!(is_valid_dns_name(&String::from("a@b.com.")),
-                   Err(String::from("Got a label (a@b) with a bad character (@).")));
+                   Err(DnsError::InvalidName { name: String::from("a@b.com."), reason: String::from("Got a label (a@b) with a bad character (@).") }));
 
         // TODO more tests! lots of corner/error cases for this one.
     }
@@ -189,7 +220,7 @@ mod tests {
 
         let qr = DnsQuestionRecord::new(String::from("goo@gle.com."), DnsQType::A, DnsQClass::IN);
         assert_eq!(qr.to_bytes(), 
-                   Err(String::from("'goo@gle.com.' doesn't appear to be a valid DNS name: Got a label (goo@gle) with a bad character (@).")));
+                   Err(DnsError::InvalidName { name: String::from("goo@gle.com."), reason: String::from("Got a label (goo@gle) with a bad character (@).") }));
     }
 
     #[test]
@@ -205,14 +236,14 @@ mod tests {
                                 0x67, 0x6c, 0x65, 0x03, // g, l, e, length 3
                                 0x63, 0x6f, 0x6d, 0x00];// c, o, m, null - but no qtype/qclass!
         assert_eq!(DnsQuestionRecord::from_bytes(&buf, 0),
-                   Err(String::from("Hit buffer bounds reading qtype/class in QuestionRecord.")));
+                   Err(DnsError::Truncated { context: "question record qtype/class", offset: 12, needed: 4, len: 12 }));
 
         let buf: Vec<u8> = vec![0x06, 0x67, 0x6f, 0x6f, // length 6, g, o, o
                                 0x67, 0x6c, 0x65, 0x03, // g, l, e, length 3
                                 0x63, 0x6f, 0x6d, 0x00, // c, o, m, null
                                 0x00, 0x01];            // qtype=A, but no qclass!
         assert_eq!(DnsQuestionRecord::from_bytes(&buf, 0),
-                   Err(String::from("Hit buffer bounds reading qtype/class in QuestionRecord.")));
+                   Err(DnsError::Truncated { context: "question record qtype/class", offset: 12, needed: 4, len: 14 }));
 
         // TODO more tests!
     }
@@ -236,12 +267,194 @@ mod tests {
         // TODO more tests! diff qtypes/qclasses, header flags/codes
     }
 
+    #[test]
+    fn dnsquestionrecord_matches_test() {
+        let qr = DnsQuestionRecord::new(String::from("google.com."), DnsQType::A, DnsQClass::IN);
+        assert!(qr.matches(&DnsQuestionRecord::new(String::from("google.com."), DnsQType::A, DnsQClass::IN)));
+        assert!(qr.matches(&DnsQuestionRecord::new(String::from("GooGLE.cOm"), DnsQType::A, DnsQClass::IN)));
+        assert!(!qr.matches(&DnsQuestionRecord::new(String::from("google.com."), DnsQType::AAAA, DnsQClass::IN)));
+        assert!(!qr.matches(&DnsQuestionRecord::new(String::from("google.com."), DnsQType::A, DnsQClass::CH)));
+        assert!(!qr.matches(&DnsQuestionRecord::new(String::from("www.google.com."), DnsQType::A, DnsQClass::IN)));
+    }
+
+    #[test]
+    fn dnsquery_check_response_test() {
+        let h = DnsHeader::new(0xABCDu16, false, DnsOpcode::QUERY, false, false,
+                               true, false, DnsRcode::NOERROR);
+        let qrv : Vec<DnsQuestionRecord> =
+            vec![DnsQuestionRecord::new(String::from("google.com."), DnsQType::A, DnsQClass::IN)];
+        let q = DnsQuery::new(h, qrv, None);
+
+        let good: Vec<u8> = vec![0xAB, 0xCD, 0x81, 0x80,      // qid, flags (QR set)
+                                 0x00, 0x01, 0x00, 0x00,      // qcount, ancount
+                                 0x00, 0x00, 0x00, 0x00,      // authcount, addcount
+                                 0x06, 0x47, 0x4f, 0x6f,      // len 6, G, O, o
+                                 0x67, 0x6c, 0x65, 0x03,      // g, l, e, len 3
+                                 0x63, 0x6f, 0x6d, 0x00,      // c, o, m, null
+                                 0x00, 0x01, 0x00, 0x01];     // qtype=A, qclass=IN
+        assert_eq!(q.check_response(&good), Ok(()));
+
+        let mut buf = good.clone();
+        buf[2] = 0x01; // QR cleared
+        assert_eq!(q.check_response(&buf), Err(DnsError::NotResponse));
+
+        let mut buf = good.clone();
+        buf[1] = 0xCE;
+        assert_eq!(q.check_response(&buf),
+                   Err(DnsError::IdMismatch { got: 0xabce, expected: 0xabcd }));
+
+        let mut buf = good.clone();
+        buf[25] = 0x1c; // qtype=AAAA
+        assert_eq!(q.check_response(&buf),
+                   Err(DnsError::QuestionMismatch { got: String::from("GOogle.com. AAAA IN"),
+                                                    expected: String::from("google.com. A IN") }));
+
+        let buf: Vec<u8> = vec![0xAB, 0xCD, 0x81, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(q.check_response(&buf),
+                   Err(DnsError::QuestionCount { got: 0, expected: 1 }));
+        // ... unless the server couldn't make sense of the query (FORMERR, NOTIMP).
+        let buf: Vec<u8> = vec![0xAB, 0xCD, 0x81, 0x81, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(q.check_response(&buf), Ok(()));
+        let buf: Vec<u8> = vec![0xAB, 0xCD, 0x81, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(q.check_response(&buf), Ok(()));
+        // other errors still have to echo the question.
+        let buf: Vec<u8> = vec![0xAB, 0xCD, 0x81, 0x83, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(q.check_response(&buf),
+                   Err(DnsError::QuestionCount { got: 0, expected: 1 }));
+        let buf: Vec<u8> = vec![0xAB, 0xCD, 0x81, 0x85, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(q.check_response(&buf),
+                   Err(DnsError::QuestionCount { got: 0, expected: 1 }));
+    }
+
+    #[test]
+    fn dnsquery_from_bytes_test() {
+        // plain query, no additionals
+        let buf: Vec<u8> = vec![0xAB, 0xCD, 0x01, 0x00,      // qid, options
+                                0x00, 0x01, 0x00, 0x00,      // qcount, ancount
+                                0x00, 0x00, 0x00, 0x00,      // authcount, addcount
+                                0x06, 0x67, 0x6f, 0x6f,      // len 6, g, o, o
+                                0x67, 0x6c, 0x65, 0x03,      // g, l, e, len 3
+                                0x63, 0x6f, 0x6d, 0x00,      // c, o, m, null
+                                0x00, 0x01, 0x00, 0x01];     // qtype=A, qclass=IN
+        let h = DnsHeader::new(0xABCDu16, false, DnsOpcode::QUERY, false, false,
+                               true, false, DnsRcode::NOERROR);
+        let qrv = vec![DnsQuestionRecord::new(String::from("google.com."), DnsQType::A, DnsQClass::IN)];
+        assert_eq!(DnsQuery::from_bytes(&buf, 0), Ok(DnsQuery::new(h, qrv, None)));
+
+        // with an OPT pseudo-RR carrying an ECS option in the additionals
+        let buf: Vec<u8> = vec![0x12, 0x34, 0x01, 0x00,      // qid, options
+                                0x00, 0x01, 0x00, 0x00,      // qcount, ancount
+                                0x00, 0x00, 0x00, 0x01,      // authcount, addcount
+                                0x03, 0x63, 0x6f, 0x6d,      // len 3, c, o, m
+                                0x00, 0x00, 0x1c, 0x00,      // null, qtype=AAAA,
+                                0x01,                        // qclass=IN
+                                0x00, 0x00, 0x29, 0x10,      // root, OPT, payload size 4096
+                                0x00, 0x00, 0x00, 0x00,      // (payload cont.), ttl (ext rcode/flags)
+                                0x00, 0x00, 0x0b,            // (ttl cont.), rdlen 11
+                                0x00, 0x08, 0x00, 0x07,      // option 8 (ECS), len 7
+                                0x00, 0x01, 0x18, 0x00,      // family v4, source /24, scope 0
+                                0x0a, 0x0b, 0x0c];           // 10.11.12
+        let h = DnsHeader::new(0x1234u16, false, DnsOpcode::QUERY, false, false,
+                               true, false, DnsRcode::NOERROR);
+        let qrv = vec![DnsQuestionRecord::new(String::from("com."), DnsQType::AAAA, DnsQClass::IN)];
+        let optr = DnsOPTRecord::new(vec![DnsOPTRecordOption::new(8, vec![0x00, 0x01, 0x18, 0x00,
+                                                                         0x0a, 0x0b, 0x0c])]);
+        let addv = vec![DnsResourceRecord::new(String::from("."), DnsQType::OPT,
+                        DnsQClass::RESERVED(4096), 0, DnsResourceRecordEnum::OPT(optr))];
+        let q = DnsQuery::new(h, qrv, Some(addv));
+        assert_eq!(DnsQuery::from_bytes(&buf, 0), Ok(q));
+
+        // a NOTIFY, which carries the new SOA in the answer section (rfc1996, section 3.7)
+        let h = DnsHeader::new(0x4242u16, false, DnsOpcode::NOTIFY, true, false,
+                               false, false, DnsRcode::NOERROR);
+        let qrv = vec![DnsQuestionRecord::new(String::from("example.com."), DnsQType::SOA, DnsQClass::IN)];
+        let soa = DnsSOARecord::new(String::from("ns1.example.com."), String::from("admin.example.com."),
+                                    2024010101, 3600, 600, 86400, 300);
+        let anv = vec![DnsResourceRecord::new(String::from("example.com."), DnsQType::SOA,
+                       DnsQClass::IN, 3600, DnsResourceRecordEnum::SOA(soa))];
+        let notify = DnsQuery::new_with_sections(h, qrv, anv, vec![], None);
+        let buf = notify.to_bytes().unwrap();
+        assert_eq!(&buf[2..12], &[0x24, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(DnsQuery::from_bytes(&buf, 0), Ok(notify));
+
+        let buf: Vec<u8> = vec![0xAB, 0xCD, 0x01, 0x00, 0x00, 0x01]; // header cut short
+        assert_eq!(DnsQuery::from_bytes(&buf, 0),
+                   Err(DnsError::Truncated { context: "DNS query", offset: 0, needed: 12, len: 6 }));
+        assert_eq!(DnsQuery::from_bytes(&buf, 6),
+                   Err(DnsError::Truncated { context: "DNS query", offset: 6, needed: 12, len: 6 }));
+    }
+
+    #[test]
+    fn dnsquery_builder_test() {
+        // like the ECS one in dnsquery_from_bytes_test, though make_ecs_option sends all 4 address bytes
+        let q = DnsQuery::builder().id(0x1234)
+                                   .question("com.", DnsQType::AAAA, DnsQClass::IN)
+                                   .ecs("10.11.12.0/24")
+                                   .build().unwrap();
+        assert_eq!(q.to_bytes().unwrap(),
+                   vec![0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+                        0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x1c, 0x00, 0x01,
+                        0x00, 0x00, 0x29, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0c,
+                        0x00, 0x08, 0x00, 0x08, 0x00, 0x01, 0x18, 0x00, 0x0a, 0x0b, 0x0c, 0x00]);
+
+        // no EDNS unless something asks for it
+        let q = DnsQuery::builder().id(1).rd(false)
+                                   .question("example.com.", DnsQType::A, DnsQClass::IN)
+                                   .build().unwrap();
+        let h = DnsHeader::new(1, false, DnsOpcode::QUERY, false, false, false, false, DnsRcode::NOERROR);
+        let qrv = vec![DnsQuestionRecord::new(String::from("example.com."), DnsQType::A, DnsQClass::IN)];
+        assert_eq!(q, DnsQuery::new(h, qrv, None));
+
+        let q = DnsQuery::builder().id(1).ad(true).cd(true)
+                                   .question("example.com.", DnsQType::A, DnsQClass::IN)
+                                   .build().unwrap();
+        assert_eq!((q.header().ad(), q.header().cd()), (true, true));
+        assert_eq!(&q.to_bytes().unwrap()[2..4], &[0x01, 0x30]);
+
+        // the DO bit is the top bit of the OPT RR's ttl, and cookies are option 10
+        let q = DnsQuery::builder().id(2)
+                                   .question("example.com.", DnsQType::A, DnsQClass::IN)
+                                   .edns_payload_size(1232)
+                                   .dnssec_ok(true)
+                                   .cookie([1, 2, 3, 4, 5, 6, 7, 8], &[])
+                                   .build().unwrap();
+        let optr = DnsOPTRecord::new(vec![DnsOPTRecordOption::new(10, vec![1, 2, 3, 4, 5, 6, 7, 8])]);
+        assert_eq!(q.additionals(),
+                   Some(&vec![DnsResourceRecord::new(String::from("."), DnsQType::OPT, DnsQClass::RESERVED(1232),
+                                                     0x8000, DnsResourceRecordEnum::OPT(optr))]));
+
+        assert_eq!(DnsQuery::builder().question("a@b.com.", DnsQType::A, DnsQClass::IN).build(),
+                   Err(DnsError::InvalidName { name: String::from("a@b.com."),
+                                               reason: String::from("Got a label (a@b) with a bad character (@).") }));
+        assert!(matches!(DnsQuery::builder().ecs("10.11.12.0/99").build(), Err(DnsError::Config(_))));
+        assert_eq!(DnsQuery::builder().cookie([0; 8], &[0; 4]).build(),
+                   Err(DnsError::Config(String::from("Got a 4 byte server cookie, but they're 8 to 32 bytes."))));
+    }
+
     #[test]
     fn dnsheader_from_bytes_test() {
         let h = DnsHeader::new(0xABCDu16, true, DnsOpcode::QUERY, false,
                                false, true, true, DnsRcode::NOERROR);
         let v : Vec<u8> = vec![0xAB, 0xCD, 0x81, 0x80]; // qid, flags
         assert_eq!(Ok(h), DnsHeader::from_bytes(&v, 0));
+
+        // opcodes live in bits 11-14
+        let h = DnsHeader::new(0x0001u16, false, DnsOpcode::UPDATE, false,
+                               false, false, false, DnsRcode::NOERROR);
+        let v : Vec<u8> = vec![0x00, 0x01, 0x28, 0x00]; // qid, flags
+        assert_eq!(Ok(h), DnsHeader::from_bytes(&v, 0));
+
+        // z, ad and cd are bits 6, 5 and 4.
+        let v : Vec<u8> = vec![0x00, 0x02, 0x81, 0xb0]; // qid, flags
+        let h = DnsHeader::from_bytes(&v, 0).unwrap();
+        assert_eq!((h.z(), h.ad(), h.cd()), (false, true, true));
+        assert_eq!(h.flags_to_u16(), 0x81b0);
+        let v : Vec<u8> = vec![0x00, 0x02, 0x00, 0x40]; // qid, flags
+        let h = DnsHeader::from_bytes(&v, 0).unwrap();
+        assert_eq!((h.z(), h.ad(), h.cd()), (true, false, false));
+        assert_eq!(h.to_string(),
+                   "QID: 2, Opcode: QUERY, AA: false, TC: false, RD: false, RA: false, Z: true, AD: false, \
+                    CD: false, Rcode: NOERROR");
         // TODO more tests! diff header options, etc.
     }
 
@@ -269,39 +482,39 @@ mod tests {
         assert_eq!(Ok((String::from("www.google.com."), 6)), dns_name_to_string(&buf, 12));
 
         let buf: Vec<u8> = vec![0xC0, 0x00]; // ptr to 0 at 0
-        assert_eq!(Err(String::from("Got a self-referencing compression pointer.")),
+        assert_eq!(Err(DnsError::BadPointer { offset: 0, target: 0 }),
                    dns_name_to_string(&buf, 0));
 
         let buf: Vec<u8> = vec![0xC0, 0x02]; // ptr to 2 at 0
-        assert_eq!(Err(String::from("Got a forward-pointing compression pointer.")),
+        assert_eq!(Err(DnsError::BadPointer { offset: 0, target: 2 }),
                    dns_name_to_string(&buf, 0));
 
         let buf: Vec<u8> = vec![0x80, 0x02]; // 10 in top bits of len byte
-        assert_eq!(Err(String::from("Got 10/01 in top bits of dns name length byte.")),
+        assert_eq!(Err(DnsError::BadLabel { offset: 0, lenbyte: 0x80 }),
                    dns_name_to_string(&buf, 0));
 
         let buf: Vec<u8> = vec![0x40, 0x02]; // 10 in top bits of len byte
-        assert_eq!(Err(String::from("Got 10/01 in top bits of dns name length byte.")),
+        assert_eq!(Err(DnsError::BadLabel { offset: 0, lenbyte: 0x40 }),
                    dns_name_to_string(&buf, 0));
 
         let buf: Vec<u8> = vec![0xC0]; // ptr, but without followup byte in buf
-        assert_eq!(Err(String::from("Hit buffer bounds when parsing compression ptr.")),
+        assert_eq!(Err(DnsError::Truncated { context: "name compression pointer", offset: 0, needed: 2, len: 1 }),
                    dns_name_to_string(&buf, 0));
 
         let buf: Vec<u8> = vec![0x02, 0x67]; // len 2, g, then nothing - not enough for label!
-        assert_eq!(Err(String::from("Hit buffer bounds when parsing label.")),
+        assert_eq!(Err(DnsError::Truncated { context: "name label", offset: 1, needed: 2, len: 2 }),
                    dns_name_to_string(&buf, 0));
 
         let buf: Vec<u8> = vec![0x01]; // len 1 then nothing - not enough for label!
-        assert_eq!(Err(String::from("Hit buffer bounds when parsing label.")),
+        assert_eq!(Err(DnsError::Truncated { context: "name label", offset: 1, needed: 1, len: 1 }),
                    dns_name_to_string(&buf, 0));
 
         let buf: Vec<u8> = vec![0x00]; // root, but starting at offset outside buffer
-        assert_eq!(Err(String::from("Offset outside of buffer bounds.")),
+        assert_eq!(Err(DnsError::Truncated { context: "name length byte", offset: 1, needed: 1, len: 1 }),
                    dns_name_to_string(&buf, 1));
 
         let buf: Vec<u8> = vec![]; // nothing - obvious error
-        assert_eq!(Err(String::from("Can't operate on empty name buffer.")),
+        assert_eq!(Err(DnsError::Truncated { context: "name length byte", offset: 0, needed: 1, len: 0 }),
                    dns_name_to_string(&buf, 0));
 
         // TODO more tests!
@@ -311,13 +524,13 @@ mod tests {
     fn dnsarecord_from_bytes_test() {
         let buf: Vec<u8> = vec![];
         assert_eq!(DnsARecord::from_bytes(&buf, 0),
-                   Err(String::from("Got a zero-length buffer.")));
+                   Err(DnsError::Truncated { context: "A record", offset: 0, needed: 4, len: 0 }));
         let buf: Vec<u8> = vec![0x00];
         assert_eq!(DnsARecord::from_bytes(&buf, 1),
-                   Err(String::from("Got an offset outside of the buffer parsing A record.")));
+                   Err(DnsError::Truncated { context: "A record", offset: 1, needed: 4, len: 1 }));
         let buf: Vec<u8> = vec![0xAB, 0xCD, 0xEF];
         assert_eq!(DnsARecord::from_bytes(&buf, 0),
-                   Err(String::from("Got a buffer with too few bytes to read.")));
+                   Err(DnsError::Truncated { context: "A record", offset: 0, needed: 4, len: 3 }));
 
         let arecord = DnsARecord::new(Ipv4Addr::new(0xAB, 0xCD, 0xEF, 0x01));
         let buf: Vec<u8> = vec![0xAB, 0xCD, 0xEF, 0x01];
@@ -328,13 +541,13 @@ mod tests {
     fn dnsaaaarecord_from_bytes_test() {
         let buf: Vec<u8> = vec![];
         assert_eq!(DnsAAAARecord::from_bytes(&buf, 0),
-                   Err(String::from("Got a zero-length buffer.")));
+                   Err(DnsError::Truncated { context: "AAAA record", offset: 0, needed: 16, len: 0 }));
         let buf: Vec<u8> = vec![0x00];
         assert_eq!(DnsAAAARecord::from_bytes(&buf, 1),
-                   Err(String::from("Got an offset outside of the buffer parsing AAAA record.")));
+                   Err(DnsError::Truncated { context: "AAAA record", offset: 1, needed: 16, len: 1 }));
         let buf: Vec<u8> = vec![0xAB, 0xCD, 0xEF];
         assert_eq!(DnsAAAARecord::from_bytes(&buf, 0),
-                   Err(String::from("Got a buffer with too few bytes to read.")));
+                   Err(DnsError::Truncated { context: "AAAA record", offset: 0, needed: 16, len: 3 }));
 
         let aaaarecord = DnsAAAARecord::new(Ipv6Addr::from_str("CAFE::F00D").unwrap());
         let buf: Vec<u8> = vec![0xCA, 0xFE, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 
@@ -346,18 +559,19 @@ mod tests {
     fn dnstxtrecord_from_bytes_test() {
         let buf: Vec<u8> = vec![];
         assert_eq!(DnsTXTRecord::from_bytes(&buf, 0),
-                   Err(String::from("Got a zero-length buffer.")));
+                   Err(DnsError::Truncated { context: "TXT record", offset: 0, needed: 1, len: 0 }));
         let buf: Vec<u8> = vec![0x00];
         assert_eq!(DnsTXTRecord::from_bytes(&buf, 1),
-                   Err(String::from("Got an offset outside of the buffer parsing TXT record.")));
+                   Err(DnsError::Truncated { context: "TXT record", offset: 1, needed: 1, len: 1 }));
         let buf: Vec<u8> = vec![0x02, 0x74]; // len 2, t, then nothing!
         assert_eq!(DnsTXTRecord::from_bytes(&buf, 0),
-                   Err(String::from("Got a TXT record with a len byte pointing outside buffer.")));
+                   Err(DnsError::Truncated { context: "TXT record string", offset: 1, needed: 2, len: 2 }));
 
         let txtrecord = DnsTXTRecord::new(String::from("test"));
         let buf: Vec<u8> = vec![0x04, 0x74, 0x65, 0x73, 0x74];
         let (parsed_record, count) = DnsTXTRecord::from_bytes(&buf, 0).unwrap();
         assert_eq!(parsed_record, txtrecord);
+        assert_eq!(count, 5);
     }
 
     #[test]
@@ -369,13 +583,13 @@ mod tests {
     fn dnsmxrecord_from_bytes_test() {
         let buf: Vec<u8> = vec![];
         assert_eq!(DnsMXRecord::from_bytes(&buf, 0),
-                   Err(String::from("Got a zero-length buffer.")));
+                   Err(DnsError::Truncated { context: "MX record", offset: 0, needed: 3, len: 0 }));
         let buf: Vec<u8> = vec![0x00];
         assert_eq!(DnsMXRecord::from_bytes(&buf, 1),
-                   Err(String::from("Got an offset outside of the buffer parsing MX record.")));
+                   Err(DnsError::Truncated { context: "MX record", offset: 1, needed: 3, len: 1 }));
         let buf: Vec<u8> = vec![0x02, 0x74]; // prefs, but no exchange!
         assert_eq!(DnsMXRecord::from_bytes(&buf, 0),
-                   Err(String::from("Got an offset with not enough buf for prefs/exchange.")));
+                   Err(DnsError::Truncated { context: "MX record", offset: 0, needed: 3, len: 2 }));
 
         let mxrecord = DnsMXRecord::new(0xabcd, String::from("exchange."));
         let buf: Vec<u8> = vec![0xab, 0xcd, 0x08, 0x65, 0x78, 0x63,     // prefs, len 8, e, x, c
@@ -388,6 +602,92 @@ mod tests {
         // TODO add tests for this. like DnsCNAMERecord::from_bytes, this function is trivial.
     }
 
+    #[test]
+    fn dnsptrrecord_from_bytes_test() {
+        let buf: Vec<u8> = vec![];
+        assert_eq!(DnsPTRRecord::from_bytes(&buf, 0),
+                   Err(DnsError::Truncated { context: "PTR record", offset: 0, needed: 1, len: 0 }));
+        let buf: Vec<u8> = vec![0x00];
+        assert_eq!(DnsPTRRecord::from_bytes(&buf, 1),
+                   Err(DnsError::Truncated { context: "PTR record", offset: 1, needed: 1, len: 1 }));
+
+        let ptrrecord = DnsPTRRecord::new(String::from("dns.google."));
+        let buf: Vec<u8> = vec![0x03, 0x64, 0x6e, 0x73,     // len 3, d, n, s
+                                0x06, 0x67, 0x6f, 0x6f,     // len 6, g, o, o
+                                0x67, 0x6c, 0x65, 0x00];    // g, l, e, null
+        assert_eq!(ptrrecord.to_bytes(), Ok(buf.clone()));
+        assert_eq!(DnsPTRRecord::from_bytes(&buf, 0), Ok((ptrrecord, 12)));
+        assert_eq!(DnsQType::from_string("ptr"), Ok(DnsQType::PTR));
+        assert_eq!(DnsQType::from_u16(12), DnsQType::PTR);
+    }
+
+    #[test]
+    fn dnssrvrecord_from_bytes_test() {
+        let buf: Vec<u8> = vec![];
+        assert_eq!(DnsSRVRecord::from_bytes(&buf, 0),
+                   Err(DnsError::Truncated { context: "SRV record", offset: 0, needed: 7, len: 0 }));
+        let buf: Vec<u8> = vec![0x00, 0x0a, 0x00, 0x3c, 0x13];
+        assert_eq!(DnsSRVRecord::from_bytes(&buf, 0),
+                   Err(DnsError::Truncated { context: "SRV record", offset: 0, needed: 7, len: 5 }));
+
+        let srvrecord = DnsSRVRecord::new(10, 60, 5060, String::from("sip.example."));
+        let buf: Vec<u8> = vec![0x00, 0x0a, 0x00, 0x3c,     // priority 10, weight 60
+                                0x13, 0xc4, 0x03, 0x73,     // port 5060, len 3, s
+                                0x69, 0x70, 0x07, 0x65,     // i, p, len 7, e
+                                0x78, 0x61, 0x6d, 0x70,     // x, a, m, p
+                                0x6c, 0x65, 0x00];          // l, e, null
+        assert_eq!(srvrecord.to_bytes(), Ok(buf.clone()));
+        assert_eq!(DnsSRVRecord::from_bytes(&buf, 0), Ok((srvrecord, 19)));
+
+        // in canonical form, the target is lowercased (rfc4034, section 6.2).
+        let mut w = DnsMessageWriter::canonical();
+        RData::write(&DnsSRVRecord::new(10, 60, 5060, String::from("SIP.Example.")), &mut w).unwrap();
+        assert_eq!(w.into_bytes(), buf);
+        assert_eq!(DnsQType::from_string("srv"), Ok(DnsQType::SRV));
+        assert_eq!(DnsQType::from_u16(33), DnsQType::SRV);
+    }
+
+    #[test]
+    fn order_srv_records_test() {
+        let records = vec![
+            DnsSRVRecord::new(20, 0, 5060, String::from("backup.example.")),
+            DnsSRVRecord::new(10, 0, 5060, String::from("zero.example.")),
+            DnsSRVRecord::new(10, 100, 5060, String::from("heavy.example.")),
+            DnsSRVRecord::new(10, 1, 5060, String::from("light.example.")),
+            DnsSRVRecord::new(30, 0, 0, String::from("."))];
+
+        let mut heavy_first = 0;
+        let mut rng = StdRng::seed_from_u64(2782);
+        for _ in 0 .. 1000 {
+            let ordered = order_srv_records(&records, &mut rng);
+            // the "." record is dropped, and priority 10 always comes before priority 20.
+            assert_eq!(ordered.len(), 4);
+            assert!(ordered[.. 3].iter().all(|r| r.priority() == 10));
+            assert_eq!(ordered[3].target(), "backup.example.");
+            if ordered[0].target() == "heavy.example." {
+                heavy_first += 1;
+            }
+        }
+        // heavy has 100 of the 101 weight in its priority, so it should almost always win.
+        assert!(heavy_first > 950, "heavy.example. was only first {heavy_first} times");
+
+        assert!(order_srv_records(&[], &mut rng).is_empty());
+    }
+
+    #[test]
+    fn reverse_lookup_name_test() {
+        let addr = IpAddr::from_str("8.8.4.4").unwrap();
+        assert_eq!(reverse_lookup_name(&addr), String::from("4.4.8.8.in-addr.arpa."));
+        let addr = IpAddr::from_str("192.0.2.1").unwrap();
+        assert_eq!(reverse_lookup_name(&addr), String::from("1.2.0.192.in-addr.arpa."));
+
+        // see rfc3596, section 2.5
+        let addr = IpAddr::from_str("2001:db8::567:89ab").unwrap();
+        assert_eq!(reverse_lookup_name(&addr),
+                   String::from("b.a.9.8.7.6.5.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa."));
+        assert!(is_valid_dns_name(&reverse_lookup_name(&addr)).is_ok());
+    }
+
     #[test]
     fn dnsresourcerecord_from_bytes_test() {
         // TODO
@@ -433,4 +733,243 @@ mod tests {
         assert_eq!(DnsResponse::from_bytes(&buf, 0), Ok(response));
     }
 
+    #[test]
+    fn dnscaarecord_to_bytes_test() {
+        let caa = DnsCAARecord::new(true, String::from("issue"), String::from("ca.example"));
+        let buf: Vec<u8> = vec![0x80, 0x05, 0x69, 0x73,     // critical, tag len 5, i, s
+                                0x73, 0x75, 0x65, 0x63,     // s, u, e, c
+                                0x61, 0x2e, 0x65, 0x78,     // a, ., e, x
+                                0x61, 0x6d, 0x70, 0x6c,     // a, m, p, l
+                                0x65];                      // e
+        assert_eq!(caa.to_bytes(), Ok(buf.clone()));
+        assert_eq!(DnsCAARecord::from_bytes(&buf, 0, buf.len()), Ok((caa, 17)));
+
+        let caa = DnsCAARecord::new(false, String::from(""), String::from("ca.example"));
+        assert_eq!(caa.to_bytes(),
+                   Err(DnsError::BadRecord { context: "CAA record", offset: 0, reason: "the tag is empty." }));
+    }
+
+    #[test]
+    fn dnstsigrecord_from_bytes_test() {
+        let buf: Vec<u8> = vec![0x03, 0x6b, 0x65, 0x79,     // len 3, k, e, y
+                                0x00, 0x00, 0x00, 0x65,     // null, time signed...
+                                0x4a, 0x8d, 0xb0, 0x01,     // ..., fudge
+                                0x2c, 0x00, 0x02, 0xab,     // ..., mac len 2, mac
+                                0xcd, 0x12, 0x34, 0x00,     // ..., original id, error
+                                0x10, 0x00, 0x00];          // ... (BADSIG), other len 0
+        let tsig = DnsTSIGRecord::new(String::from("key."), 0x654a8db0, 300, vec![0xab, 0xcd], 0x1234,
+                                      DnsRcode::BADSIG, vec![]);
+        assert_eq!(DnsTSIGRecord::from_bytes(&buf, 0), Ok((tsig, 23)));
+        let (tsig, _) = DnsTSIGRecord::from_bytes(&buf, 0).unwrap();
+        assert_eq!(tsig.error(), DnsRcode::BADVERS);
+        assert_eq!(tsig.to_string(), "TSIG: algorithm: key., time signed: 1699384752, fudge: 300, \
+                                      mac: [ab, cd], original id: 4660, error: BADSIG, other: []");
+
+        let rr = DnsResourceRecord::new(String::from("key."), DnsQType::RESERVED(250), DnsQClass::ANY, 0,
+                                        DnsResourceRecordEnum::TSIG(tsig));
+        let bytes = rr.to_bytes().unwrap();
+        assert_eq!(&bytes[15 ..], &buf[..]);
+        assert_eq!(DnsResourceRecord::from_bytes(&bytes, 0), Ok((rr, bytes.len())));
+
+        // errors that aren't rcodes we know keep their value.
+        let mut buf = buf.clone();
+        buf[19] = 0xff;
+        let (tsig, _) = DnsTSIGRecord::from_bytes(&buf, 0).unwrap();
+        assert_eq!(tsig.error(), DnsRcode::RESERVED(0xff10));
+        assert_eq!(DnsTSIGRecord::from_bytes(&buf[.. 20], 0),
+                   Err(DnsError::Truncated { context: "TSIG record id/error/other len", offset: 17, needed: 6,
+                                             len: 20 }));
+    }
+
+    #[test]
+    fn dnsresponse_to_bytes_test() {
+        let header = DnsHeader::new(0xabcd, true, DnsOpcode::QUERY, false, false, true,
+                                    true, DnsRcode::NOERROR);
+        let qvec = vec![DnsQuestionRecord::new(String::from("a.net."), DnsQType::A, DnsQClass::IN)];
+        let anvec = vec![DnsResourceRecord::new(
+            String::from("a.net."), DnsQType::A, DnsQClass::IN, 300, DnsResourceRecordEnum::A(
+                DnsARecord::new(Ipv4Addr::new(72,246,2,76))))];
+        let response = DnsResponse::new(header, qvec, anvec, vec![], vec![]);
+        assert_eq!(response.to_bytes(),
+                   Ok(vec![0xab, 0xcd, 0x81, 0x80,     // qid, flags
+                           0x00, 0x01, 0x00, 0x01,     // qcount, ancount
+                           0x00, 0x00, 0x00, 0x00,     // authcount, addcount
+                           0x01, 0x61, 0x03, 0x6e,     // len 1, a, len 3, n
+                           0x65, 0x74, 0x00,           // e, t, null
+                           0x00, 0x01, 0x00, 0x01,     // A IN
+                           0xc0, 0x0c,                 // ptr to name in question
+                           0x00, 0x01, 0x00, 0x01,     // A IN
+                           0x00, 0x00, 0x01, 0x2c,     // ttl
+                           0x00, 0x04,                 // rdlen
+                           0x48, 0xf6, 0x02, 0x4c]));  // 72.246.2.76
+    }
+
+    // one of every record type in DnsResourceRecordEnum, spread across the sections.
+    fn make_every_rr_type_response() -> DnsResponse {
+        let header = DnsHeader::new(0x1234, true, DnsOpcode::QUERY, true, false, true,
+                                    true, DnsRcode::NXRRSET);
+        let name = String::from("example.com.");
+        let qvec = vec![DnsQuestionRecord::new(name.clone(), DnsQType::ANY, DnsQClass::IN)];
+        let anvec = vec![
+            DnsResourceRecord::new(name.clone(), DnsQType::A, DnsQClass::IN, 60,
+                DnsResourceRecordEnum::A(DnsARecord::new(Ipv4Addr::new(192, 0, 2, 1)))),
+            DnsResourceRecord::new(name.clone(), DnsQType::AAAA, DnsQClass::IN, 60,
+                DnsResourceRecordEnum::AAAA(DnsAAAARecord::new(Ipv6Addr::from_str("2001:db8::1").unwrap()))),
+            DnsResourceRecord::new(String::from("www.example.com."), DnsQType::CNAME, DnsQClass::IN, 60,
+                DnsResourceRecordEnum::CNAME(DnsCNAMERecord::new(name.clone()))),
+            DnsResourceRecord::new(String::from("1.2.0.192.in-addr.arpa."), DnsQType::PTR, DnsQClass::IN, 60,
+                DnsResourceRecordEnum::PTR(DnsPTRRecord::new(name.clone()))),
+            DnsResourceRecord::new(String::from("_sip._udp.google.com."), DnsQType::SRV, DnsQClass::IN, 60,
+                DnsResourceRecordEnum::SRV(DnsSRVRecord::new(10, 60, 5060, name.clone()))),
+            DnsResourceRecord::new(name.clone(), DnsQType::MX, DnsQClass::IN, 60,
+                DnsResourceRecordEnum::MX(DnsMXRecord::new(10, String::from("mail.example.com.")))),
+            DnsResourceRecord::new(name.clone(), DnsQType::TXT, DnsQClass::IN, 60,
+                DnsResourceRecordEnum::TXT(DnsTXTRecord::new(String::from("v=spf1 -all")))),
+            DnsResourceRecord::new(name.clone(), DnsQType::CAA, DnsQClass::IN, 60,
+                DnsResourceRecordEnum::CAA(DnsCAARecord::new(false, String::from("issue"),
+                                                             String::from("ca.example")))),
+            DnsResourceRecord::new(name.clone(), DnsQType::RESERVED(99), DnsQClass::IN, 60,
+                DnsResourceRecordEnum::Generic(DnsGenericRecord::new(99, vec![0xde, 0xad, 0xbe, 0xef])))];
+        let auvec = vec![
+            DnsResourceRecord::new(name.clone(), DnsQType::NS, DnsQClass::IN, 3600,
+                DnsResourceRecordEnum::NS(DnsNSRecord::new(String::from("ns1.example.com.")))),
+            DnsResourceRecord::new(name.clone(), DnsQType::SOA, DnsQClass::IN, 3600,
+                DnsResourceRecordEnum::SOA(DnsSOARecord::new(String::from("ns1.example.com."),
+                    String::from("hostmaster.example.com."), 1, 7200, 3600, 1209600, 300)))];
+        let optr = DnsOPTRecord::new(vec![DnsOPTRecordOption::new(10, vec![1, 2, 3, 4, 5, 6, 7, 8])]);
+        let addvec = vec![
+            DnsResourceRecord::new(String::from("ns1.example.com."), DnsQType::A, DnsQClass::IN, 3600,
+                DnsResourceRecordEnum::A(DnsARecord::new(Ipv4Addr::new(192, 0, 2, 53)))),
+            DnsResourceRecord::new(String::from("."), DnsQType::OPT, DnsQClass::RESERVED(1232), 0,
+                DnsResourceRecordEnum::OPT(optr))];
+        DnsResponse::new(header, qvec, anvec, auvec, addvec)
+    }
+
+    #[test]
+    fn dnsresponse_round_trip_test() {
+        let response = make_every_rr_type_response();
+        let bytes = response.to_bytes().unwrap();
+        assert_eq!(DnsResponse::from_bytes(&bytes, 0), Ok(response));
+    }
+
+    #[test]
+    fn dnsresponse_wildcard_round_trip_test() {
+        // a wildcard owner isn't a valid hostname, but it has to come back out the way it went in.
+        let header = DnsHeader::new(0x1234, true, DnsOpcode::QUERY, true, false, true, true, DnsRcode::NOERROR);
+        let qvec = vec![DnsQuestionRecord::new(String::from("*.example."), DnsQType::A, DnsQClass::IN)];
+        let anvec = vec![DnsResourceRecord::new(String::from("*.example."), DnsQType::A, DnsQClass::IN, 300,
+                                                DnsResourceRecordEnum::A(DnsARecord::new(Ipv4Addr::new(192, 0, 2, 1))))];
+        let bytes = DnsResponse::new(header, qvec, anvec, vec![], vec![]).to_bytes().unwrap();
+        let response = DnsResponse::from_bytes(&bytes, 0).unwrap();
+        assert_eq!(response.answers()[0].name(), "*.example.");
+        assert_eq!(response.to_bytes(), Ok(bytes));
+    }
+
+    #[test]
+    fn dnsresponse_edns_test() {
+        let response = make_every_rr_type_response();
+        let edns = response.edns().unwrap();
+        assert_eq!((edns.udp_payload_size(), edns.extended_rcode(), edns.version(), edns.dnssec_ok()),
+                   (1232, 0, 0, false));
+        assert_eq!(edns.options(), &[DnsOPTRecordOption::new(10, vec![1, 2, 3, 4, 5, 6, 7, 8])]);
+        assert_eq!(response.rcode(), DnsRcode::NXRRSET);
+
+        // BADVERS is 16: 1 in the OPT RR's extended rcode, and 0 (NOERROR) in the header.
+        let h = DnsHeader::new(0x1234, true, DnsOpcode::QUERY, false, false, true, true, DnsRcode::NOERROR);
+        let opt = |ttl: u32| vec![DnsResourceRecord::new(String::from("."), DnsQType::OPT, DnsQClass::RESERVED(512),
+                                                         ttl, DnsResourceRecordEnum::OPT(DnsOPTRecord::new(vec![])))];
+        let response = DnsResponse::new(h, vec![], vec![], vec![], opt(0x0100_8000));
+        let bytes = response.to_bytes().unwrap();
+        let response = DnsResponse::from_bytes(&bytes, 0).unwrap();
+        let edns = response.edns().unwrap();
+        assert_eq!((edns.udp_payload_size(), edns.extended_rcode(), edns.dnssec_ok()), (512, 1, true));
+        assert_eq!(response.header().rcode(), DnsRcode::NOERROR);
+        assert_eq!(response.rcode(), DnsRcode::BADVERS);
+        assert_eq!(response.additionals()[0].to_string(), "EDNS: version 0, flags: do; udp: 512; extended rcode: 1 OPT: ");
+
+        // BADCOOKIE is 23: 1 in the OPT RR, and 7 in the header.
+        let h = DnsHeader::new(0x1234, true, DnsOpcode::QUERY, false, false, true, true, DnsRcode::YXRRSET);
+        let response = DnsResponse::new(h, vec![], vec![], vec![], opt(0x0100_0000));
+        assert_eq!(response.rcode(), DnsRcode::BADCOOKIE);
+        assert!(response.to_string().contains("Extended Rcode: BADCOOKIE"));
+
+        // without an OPT RR, the header is all there is.
+        let response = DnsResponse::new(h, vec![], vec![], vec![], vec![]);
+        assert_eq!(response.edns(), None);
+        assert_eq!(response.rcode(), DnsRcode::YXRRSET);
+    }
+
+    #[test]
+    fn dnsmessagewriter_write_name_test() {
+        let mut w = DnsMessageWriter::new();
+        w.write_name("google.com.", true).unwrap();
+        w.write_name("www.google.com", true).unwrap();   // suffix matches, from offset 0
+        w.write_name("mail.com.", true).unwrap();        // only com. matches, from offset 7
+        w.write_name("google.com.", false).unwrap();     // written in full
+        w.write_name("GOOGLE.com.", true).unwrap();      // case differs, so only com. matches
+        w.write_name(".", true).unwrap();
+        assert_eq!(w.into_bytes(),
+                   vec![0x06, 0x67, 0x6f, 0x6f,      // len 6, g, o, o
+                        0x67, 0x6c, 0x65, 0x03,      // g, l, e, len 3
+                        0x63, 0x6f, 0x6d, 0x00,      // c, o, m, null
+                        0x03, 0x77, 0x77, 0x77,      // len 3, w, w, w
+                        0xc0, 0x00,                  // ptr to 0
+                        0x04, 0x6d, 0x61, 0x69,      // len 4, m, a, i
+                        0x6c, 0xc0, 0x07,            // l, ptr to 7
+                        0x06, 0x67, 0x6f, 0x6f,      // len 6, g, o, o
+                        0x67, 0x6c, 0x65, 0x03,      // g, l, e, len 3
+                        0x63, 0x6f, 0x6d, 0x00,      // c, o, m, null
+                        0x06, 0x47, 0x4f, 0x4f,      // len 6, G, O, O
+                        0x47, 0x4c, 0x45, 0xc0,      // G, L, E, ptr...
+                        0x07,                        // ...to 7
+                        0x00]);                      // root
+
+        // only the wire format's limits apply, not the rules for hostnames.
+        let mut w = DnsMessageWriter::new();
+        w.write_name("*._x@y.com", true).unwrap();
+        assert_eq!(w.into_bytes(), vec![0x01, 0x2a, 0x04, 0x5f, 0x78, 0x40, 0x79, 0x03, 0x63, 0x6f, 0x6d, 0x00]);
+        let mut w = DnsMessageWriter::new();
+        assert!(w.write_name("goo..gle.com", true).is_err());
+        assert!(w.write_name(&format!("{}.com", "a".repeat(64)), true).is_err());
+        assert!(w.write_name(&"abcdefg.".repeat(32), true).is_err());
+        w.write_name(&"abcdefg.".repeat(31), true).unwrap();
+    }
+
+    #[test]
+    fn dnsresponse_to_bytes_compression_test() {
+        // rdata names in MX records (and NS, CNAME, SOA) get compressed too.
+        let header = DnsHeader::new(0xabcd, true, DnsOpcode::QUERY, false, false, true,
+                                    true, DnsRcode::NOERROR);
+        let qvec = vec![DnsQuestionRecord::new(String::from("a.net."), DnsQType::MX, DnsQClass::IN)];
+        let anvec = vec![DnsResourceRecord::new(
+            String::from("a.net."), DnsQType::MX, DnsQClass::IN, 300, DnsResourceRecordEnum::MX(
+                DnsMXRecord::new(10, String::from("mx.a.net."))))];
+        let response = DnsResponse::new(header, qvec, anvec, vec![], vec![]);
+        assert_eq!(response.to_bytes(),
+                   Ok(vec![0xab, 0xcd, 0x81, 0x80,     // qid, flags
+                           0x00, 0x01, 0x00, 0x01,     // qcount, ancount
+                           0x00, 0x00, 0x00, 0x00,     // authcount, addcount
+                           0x01, 0x61, 0x03, 0x6e,     // len 1, a, len 3, n
+                           0x65, 0x74, 0x00,           // e, t, null
+                           0x00, 0x0f, 0x00, 0x01,     // MX IN
+                           0xc0, 0x0c,                 // ptr to name in question
+                           0x00, 0x0f, 0x00, 0x01,     // MX IN
+                           0x00, 0x00, 0x01, 0x2c,     // ttl
+                           0x00, 0x07,                 // rdlen
+                           0x00, 0x0a, 0x02, 0x6d,     // preference 10, len 2, m
+                           0x78, 0xc0, 0x0c]));        // x, ptr to name in question
+
+        // and a message with every type in it comes out smaller than the sum of its parts.
+        let response = make_every_rr_type_response();
+        let bytes = response.to_bytes().unwrap();
+        let mut uncompressed = 12;
+        for q in response.questions() {
+            uncompressed += q.to_bytes().unwrap().len();
+        }
+        for rr in response.answers().iter().chain(response.authorities()).chain(response.additionals()) {
+            uncompressed += rr.to_bytes().unwrap().len();
+        }
+        assert!(bytes.len() < uncompressed);
+        assert_eq!(DnsResponse::from_bytes(&bytes, 0), Ok(response));
+    }
 }
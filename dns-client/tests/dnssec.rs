@@ -0,0 +1,158 @@
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use dns_client::dns_client_lib::*;
+    use dns_client::dns_client_lib::dnssec::*;
+
+    // the example key from rfc4034, section 5.4.
+    fn dskey() -> DnsDNSKEYRecord {
+        let key = BASE64.decode("AQOeiiR0GOMYkDshWoSKz9XzfwJr1AYtsmx3TGkJaNXVbfi/2pHm822aJ5iI9BMzNXxeYCmZ\
+                                 DRD99WYwYqUSdjMmmAphXdvxegXd/M5+X7OrzKBaMbCVdFLUUh6DhweJBjEVv5f2wwjM9Xzc\
+                                 nOf+EPbtG9DMBmADjFDc2w/rljwvFw==").unwrap();
+        DnsDNSKEYRecord::new(256, 3, 5, key)
+    }
+
+    #[test]
+    fn dnskey_test() {
+        let key = dskey();
+        assert_eq!(key.key_tag(), 60485);
+        assert!(key.zone_key());
+        assert!(!key.sep());
+        assert!(key.to_string().starts_with("DNSKEY: 256 3 5 AQOeiiR0GOMYkDshWoSKz9Xz"));
+
+        let buf = key.to_bytes();
+        assert_eq!(&buf[.. 4], &[0x01, 0x00, 0x03, 0x05]);
+        assert_eq!(DnsDNSKEYRecord::from_bytes(&buf, 0, buf.len()), Ok((key, 134)));
+        assert_eq!(DnsDNSKEYRecord::from_bytes(&buf, 0, 3),
+                   Err(DnsError::BadRecord { context: "DNSKEY record", offset: 0, reason: "the rdata is too short." }));
+
+        // RSA/MD5 keys take their tag from the modulus instead.
+        assert_eq!(DnsDNSKEYRecord::new(256, 3, 1, vec![0xaa, 0x12, 0x34, 0xbb]).key_tag(), 0x1234);
+    }
+
+    #[test]
+    fn ds_test() {
+        let digest = vec![0x2b, 0xb1, 0x83, 0xaf, 0x5f, 0x22, 0x58, 0x81, 0x79, 0xa5,
+                          0x3b, 0x0a, 0x98, 0x63, 0x1f, 0xad, 0x1a, 0x29, 0x21, 0x18];
+        let ds = DnsDSRecord::new(60485, 5, 1, digest);
+        assert_eq!(ds.to_string(), "DS: 60485 5 1 2BB183AF5F22588179A53B0A98631FAD1A292118");
+        let buf = ds.to_bytes();
+        assert_eq!(&buf[.. 4], &[0xec, 0x45, 0x05, 0x01]);
+        assert_eq!(DnsDSRecord::from_bytes(&buf, 0, buf.len()), Ok((ds, 24)));
+        assert!(matches!(DnsDSRecord::from_bytes(&buf, 0, 30),
+                         Err(DnsError::Truncated { context: "DS record digest", .. })));
+    }
+
+    #[test]
+    fn rrsig_test() {
+        // from rfc4034, section 3.3.
+        let sig = BASE64.decode("oJB1W6WNGv+ldvQ3WDG0MQkg5IEhjRip8WTrPYGv07h108dUKGMeDPKijVCHX3DDKdfb+v6o\
+                                 B9wfuh3DTJXUAfI/M0zmO/zz8bW0Rznl8O3tGNazPwQKkRN20XPXV6nwwfoXmJQbsLNrLfkG\
+                                 J5D6fwFm8nN+6pBzeDQfsS3Ap3o=").unwrap();
+        let rrsig = DnsRRSIGRecord::new(DnsQType::A, 5, 3, 86400, 1048354263, 1045762263, 2642,
+                                        String::from("example.com."), sig);
+        assert_eq!(rrsig.to_string(),
+                   "RRSIG: A 5 3 86400 20030322173103 20030220173103 2642 example.com. \
+                    oJB1W6WNGv+ldvQ3WDG0MQkg5IEhjRip8WTrPYGv07h108dUKGMeDPKijVCHX3DDKdfb+v6o\
+                    B9wfuh3DTJXUAfI/M0zmO/zz8bW0Rznl8O3tGNazPwQKkRN20XPXV6nwwfoXmJQbsLNrLfkG\
+                    J5D6fwFm8nN+6pBzeDQfsS3Ap3o=");
+
+        let buf = rrsig.to_bytes().unwrap();
+        assert_eq!(&buf[.. 4], &[0x00, 0x01, 0x05, 0x03]);
+        assert_eq!(DnsRRSIGRecord::from_bytes(&buf, 0, buf.len()), Ok((rrsig, buf.len())));
+        assert_eq!(DnsRRSIGRecord::from_bytes(&buf, 0, 20),
+                   Err(DnsError::BadRecord { context: "RRSIG record", offset: 0,
+                                             reason: "the signer's name runs past the rdata." }));
+    }
+
+    #[test]
+    fn nsec_test() {
+        // from rfc4034, section 4.3.
+        let mut buf: Vec<u8> = vec![0x04, 0x68, 0x6f, 0x73,     // len 4, h, o, s
+                                    0x74, 0x07, 0x65, 0x78,     // t, len 7, e, x
+                                    0x61, 0x6d, 0x70, 0x6c,     // a, m, p, l
+                                    0x65, 0x03, 0x63, 0x6f,     // e, len 3, c, o
+                                    0x6d, 0x00,                 // m, null
+                                    0x00, 0x06, 0x40, 0x01,     // window 0, len 6, A, MX
+                                    0x00, 0x00, 0x00, 0x03,     // ..., RRSIG, NSEC
+                                    0x04, 0x1b];                // window 4, len 27
+        buf.extend_from_slice(&[0x00; 26]);
+        buf.push(0x20);                                         // TYPE1234
+        let nsec = DnsNSECRecord::new(String::from("host.example.com."),
+                                      vec![DnsQType::A, DnsQType::MX, DnsQType::RRSIG, DnsQType::NSEC,
+                                           DnsQType::RESERVED(1234)]);
+        assert_eq!(nsec.to_string(), "NSEC: host.example.com. A MX RRSIG NSEC TYPE1234");
+        assert_eq!(nsec.to_bytes(), Ok(buf.clone()));
+        assert_eq!(DnsNSECRecord::from_bytes(&buf, 0, buf.len()), Ok((nsec, buf.len())));
+
+        // a zone with a wildcard has an NSEC pointing at it, and that has to be written out too.
+        let nsec = DnsNSECRecord::new(String::from("*.Example."), vec![DnsQType::A]);
+        let buf = nsec.to_bytes().unwrap();
+        assert_eq!(buf, vec![0x01, 0x2a, 0x07, 0x45, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x00, 0x00, 0x01, 0x40]);
+        assert_eq!(DnsNSECRecord::from_bytes(&buf, 0, buf.len()), Ok((nsec, buf.len())));
+
+        // the bitmap's windows have to go up, and be 1 to 32 bytes.
+        let bad = [0x00, 0x00, 0x01, 0x40, 0x00, 0x01, 0x40]; // root, then window 0 twice
+        assert_eq!(DnsNSECRecord::from_bytes(&bad, 0, bad.len()),
+                   Err(DnsError::BadRecord { context: "NSEC record type bitmap", offset: 4,
+                                             reason: "the windows are out of order." }));
+        let bad = [0x00, 0x00, 0x00]; // root, then an empty window
+        assert_eq!(DnsNSECRecord::from_bytes(&bad, 0, bad.len()),
+                   Err(DnsError::BadRecord { context: "NSEC record type bitmap", offset: 1,
+                                             reason: "a window has a bad length." }));
+    }
+
+    #[test]
+    fn nsec3_test() {
+        // from rfc5155, appendix A.
+        let hash = vec![0x17, 0x4e, 0xb2, 0x40, 0x9f, 0xe2, 0x8b, 0xcb, 0x48, 0x87,
+                        0xa1, 0x83, 0x6f, 0x95, 0x7f, 0x0a, 0x84, 0x25, 0xe2, 0x7b];
+        let nsec3 = DnsNSEC3Record::new(1, 1, 12, vec![0xaa, 0xbb, 0xcc, 0xdd], hash,
+                                        vec![DnsQType::NS, DnsQType::SOA, DnsQType::MX, DnsQType::RRSIG,
+                                             DnsQType::DNSKEY, DnsQType::NSEC3PARAM]);
+        assert!(nsec3.opt_out());
+        assert_eq!(nsec3.to_string(),
+                   "NSEC3: 1 1 12 AABBCCDD 2T7B4G4VSA5SMI47K61MV5BV1A22BOJR NS SOA MX RRSIG DNSKEY NSEC3PARAM");
+        let buf = nsec3.to_bytes().unwrap();
+        assert_eq!(&buf[.. 10], &[0x01, 0x01, 0x00, 0x0c, 0x04, 0xaa, 0xbb, 0xcc, 0xdd, 0x14]);
+        assert_eq!(DnsNSEC3Record::from_bytes(&buf, 0, buf.len()), Ok((nsec3, buf.len())));
+
+        let param = DnsNSEC3PARAMRecord::new(1, 0, 0, vec![]);
+        assert_eq!(param.to_string(), "NSEC3PARAM: 1 0 0 -");
+        let buf = param.to_bytes().unwrap();
+        assert_eq!(buf, vec![0x01, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(DnsNSEC3PARAMRecord::from_bytes(&buf, 0, buf.len()), Ok((param, 5)));
+
+        // a salt length that doesn't agree with the rdata length, either way.
+        let buf = vec![0x01, 0x00, 0x00, 0x00, 0x02, 0xaa, 0xbb, 0x00];
+        assert_eq!(DnsNSEC3PARAMRecord::from_bytes(&buf, 0, 6),
+                   Err(DnsError::BadRecord { context: "NSEC3PARAM record", offset: 0,
+                                             reason: "the salt runs past the rdata." }));
+        assert_eq!(DnsNSEC3PARAMRecord::from_bytes(&buf, 0, buf.len()),
+                   Err(DnsError::BadRecord { context: "NSEC3PARAM record", offset: 0,
+                                             reason: "the rdata runs on past the salt." }));
+    }
+
+    #[test]
+    fn dnssec_response_round_trip_test() {
+        let h = DnsHeader::new(0x1234, true, DnsOpcode::QUERY, false, false, true, true, DnsRcode::NOERROR);
+        let q = vec![DnsQuestionRecord::new(String::from("dskey.example.com."), DnsQType::DNSKEY, DnsQClass::IN)];
+        let rr = |qtype: DnsQType, record: DnsResourceRecordEnum|
+            DnsResourceRecord::new(String::from("dskey.example.com."), qtype, DnsQClass::IN, 86400, record);
+        let an = vec![rr(DnsQType::DNSKEY, DnsResourceRecordEnum::DNSKEY(dskey())),
+                      rr(DnsQType::RRSIG, DnsResourceRecordEnum::RRSIG(DnsRRSIGRecord::new(
+                          DnsQType::DNSKEY, 5, 3, 86400, 1048354263, 1045762263, 60485,
+                          String::from("dskey.example.com."), vec![1, 2, 3]))),
+                      rr(DnsQType::NSEC, DnsResourceRecordEnum::NSEC(DnsNSECRecord::new(
+                          String::from("dskey.example.com."), vec![DnsQType::RRSIG, DnsQType::DNSKEY]))),
+                      rr(DnsQType::DS, DnsResourceRecordEnum::DS(DnsDSRecord::new(60485, 5, 2, vec![0xab; 32]))),
+                      rr(DnsQType::NSEC3PARAM, DnsResourceRecordEnum::NSEC3PARAM(DnsNSEC3PARAMRecord::new(
+                          1, 0, 10, vec![0x12, 0x34])))];
+        let r = DnsResponse::new(h, q, an, vec![], vec![]);
+        let buf = r.to_bytes().unwrap();
+        assert_eq!(DnsResponse::from_bytes(&buf, 0), Ok(r));
+        assert_eq!(DnsQType::from_string("dnskey"), Ok(DnsQType::DNSKEY));
+        assert_eq!(DnsQType::from_u16(50), DnsQType::NSEC3);
+    }
+}
@@ -0,0 +1,51 @@
+#[cfg(all(test, feature = "odoh"))]
+mod tests {
+
+    use dns_client::dns_client_lib::DnsError;
+    use dns_client::dns_client_lib::hpke::*;
+
+    fn unhex(s: &str) -> Vec<u8> {
+        (0 .. s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i .. i + 2], 16).unwrap()).collect()
+    }
+
+    fn unhex32(s: &str) -> [u8; 32] {
+        unhex(s).try_into().unwrap()
+    }
+
+    // the test vector for DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, AES-128-GCM in base mode. rfc9180, appendix A.1.1.
+    #[test]
+    fn hpke_rfc9180_vector_test() {
+        let sk_e = unhex32("52c4a758a802cd8b936eceea314432798d5baf2d7e9235dc084ab1b9cfa2f736");
+        let sk_r = unhex32("4612c550263fc8ad58375df3f557aac531d26850903e55a9f23f21d8534e8ac8");
+        let info = unhex("4f6465206f6e2061204772656369616e2055726e");
+        let pk_r = public_key(&sk_r);
+        assert_eq!(pk_r.to_vec(), unhex("3948cfe0ad1ddb695d780e59077195da6c56506b027329794ab02bca80815c4d"));
+
+        let (enc, sender) = setup_base_s(&pk_r, &info, sk_e).unwrap();
+        assert_eq!(enc, unhex("37fda3567bdbd628e88668c3c8d7e97d1d1253b6d4ea6d44c150f741f1bf4431"));
+        assert_eq!(sender.key(), &unhex("4531685d41d65f03dc48f6b8302c05b0")[..]);
+        assert_eq!(sender.base_nonce(), &unhex("56d890e5accaaf011cff4b7d")[..]);
+        assert_eq!(sender.exporter_secret(),
+                   &unhex("45ff1c2e220db587171952c0592d5f5ebe103f1561a2614e38f2ffd47e99e3f8")[..]);
+
+        let pt = b"Beauty is truth, truth beauty";
+        let ct = sender.seal(b"Count-0", pt).unwrap();
+        assert_eq!(ct, unhex("f938558b5d72f1a23810b4be2ab4f84331acc02fc97babc53a52ae8218a355a96d8770ac83d07bea87e13c512a"));
+        assert_eq!(sender.export(b"", 32).unwrap(),
+                   unhex("3853fe2b4035195a573ffc53856e77058e15d9ea064de3e59f4961d0095250ee"));
+
+        let receiver = setup_base_r(&enc, &sk_r, &info).unwrap();
+        assert_eq!(receiver.open(b"Count-0", &ct).unwrap(), pt.to_vec());
+        assert!(receiver.open(b"Count-1", &ct).is_err());
+    }
+
+    #[test]
+    fn hpke_bad_key_test() {
+        let sk_e = [0x11u8; 32];
+        assert_eq!(setup_base_s(&[0u8; 31], b"", sk_e).err(),
+                   Some(DnsError::Crypto(String::from("Got a 31 byte X25519 public key, expected 32."))));
+        // the all zero point is of low order, so any key gives an all zero shared secret.
+        assert_eq!(setup_base_s(&[0u8; 32], b"", sk_e).err(),
+                   Some(DnsError::Crypto(String::from("Got an X25519 public key that gives an all zero shared secret."))));
+    }
+}
@@ -0,0 +1,235 @@
+#[cfg(all(test, feature = "doh"))]
+mod common;
+
+#[cfg(all(test, feature = "doh"))]
+mod tests {
+
+    use crate::common::*;
+    use dns_client::dns_client_lib::*;
+    use dns_client::dns_client_lib::client::*;
+    use dns_client::dns_client_lib::https;
+    use dns_client::dns_client_lib::https::*;
+    use dns_client::dns_client_lib::tls::DnsTlsConfig;
+    use dns_client::dns_client_lib::transport::DnsTransport;
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL;
+    use rustls::{ServerConnection, StreamOwned};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{SocketAddr, TcpListener};
+    use std::thread;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // how the stand-in server should answer.
+    struct Answer {
+        status: u16,
+        content_type: &'static str,
+        chunked: bool
+    }
+
+    const GOOD_ANSWER: Answer = Answer { status: 200, content_type: "application/dns-message", chunked: false };
+
+    /* a stand-in DoH server, answering one request by echoing the query back with QR set.
+       the thread hands back the request line and the query it got, for checking. */
+    fn spawn_https_server(pki: &TestPki, answer: Answer) -> (SocketAddr, thread::JoinHandle<(String, Vec<u8>)>) {
+        let config = server_config(pki, &[b"http/1.1"]);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let (sock, _) = listener.accept().unwrap();
+            let conn = ServerConnection::new(config).unwrap();
+            let mut stream = BufReader::new(StreamOwned::new(conn, sock));
+
+            let mut request_line = String::new();
+            stream.read_line(&mut request_line).unwrap();
+            let request_line = request_line.trim_end().to_string();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                stream.read_line(&mut line).unwrap();
+                if line.trim_end().is_empty() {
+                    break;
+                }
+                if let Some(l) = line.strip_prefix("Content-Length: ") {
+                    content_length = l.trim().parse().unwrap();
+                }
+            }
+
+            let mut query = vec![0u8; content_length];
+            stream.read_exact(&mut query).unwrap();
+            if let Some((_, param)) = request_line.split_once("dns=") {
+                query = BASE64URL.decode(param.split(' ').next().unwrap()).unwrap();
+            }
+
+            let mut body = query.clone();
+            body[2] |= 0x80;
+            let mut response = format!("HTTP/1.1 {} Whatever\r\nContent-Type: {}\r\n",
+                                       answer.status, answer.content_type).into_bytes();
+            if answer.chunked {
+                response.extend_from_slice(b"Transfer-Encoding: chunked\r\n\r\n");
+                for chunk in body.chunks(7) {
+                    response.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+                    response.extend_from_slice(chunk);
+                    response.extend_from_slice(b"\r\n");
+                }
+                response.extend_from_slice(b"0\r\n\r\n");
+            } else {
+                response.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+                response.extend_from_slice(&body);
+            }
+            let stream = stream.get_mut();
+            stream.write_all(&response).unwrap();
+            stream.flush().unwrap();
+            (request_line, query)
+        });
+        (addr, handle)
+    }
+
+    fn test_tls_config(pki: &TestPki) -> DnsTlsConfig {
+        let mut config = DnsTlsConfig::default();
+        config.add_ca_pem(pki.ca_pem.as_bytes()).unwrap();
+        config
+    }
+
+    #[test]
+    fn parse_doh_url_test() {
+        assert_eq!(parse_doh_url("https://dns.google/dns-query"),
+                   Ok(DohUrl::new(String::from("dns.google"), 443, String::from("/dns-query"))));
+        assert_eq!(parse_doh_url("HTTPS://dns.google"),
+                   Ok(DohUrl::new(String::from("dns.google"), 443, String::from(DEFAULT_DOH_PATH))));
+        assert_eq!(parse_doh_url("https://1.1.1.1:8443/q?x=1#frag"),
+                   Ok(DohUrl::new(String::from("1.1.1.1"), 8443, String::from("/q?x=1"))));
+        assert_eq!(parse_doh_url("https://[2001:db8::1]:8443/dns-query"),
+                   Ok(DohUrl::new(String::from("2001:db8::1"), 8443, String::from("/dns-query"))));
+        assert_eq!(parse_doh_url("https://[2001:db8::1]"),
+                   Ok(DohUrl::new(String::from("2001:db8::1"), 443, String::from(DEFAULT_DOH_PATH))));
+
+        assert!(parse_doh_url("http://dns.google/dns-query").is_err());
+        assert!(parse_doh_url("https:///dns-query").is_err());
+        assert!(parse_doh_url("https://dns.google:99999/").is_err());
+        assert!(parse_doh_url("https://user@dns.google/").is_err());
+        assert!(parse_doh_url("https://[2001:db8::1/").is_err());
+
+        let url = parse_doh_url("https://[2001:db8::1]:8443/dns-query").unwrap();
+        assert_eq!(url.authority(), "[2001:db8::1]:8443");
+        assert_eq!(url.to_string(), "https://[2001:db8::1]:8443/dns-query");
+        assert_eq!(parse_doh_url("https://dns.google").unwrap().authority(), "dns.google");
+    }
+
+    #[test]
+    fn doh_request_test() {
+        let url = parse_doh_url("https://dns.example/dns-query").unwrap();
+        // the example query from rfc8484, section 4.1.1: www.example.com A, id 0, RD.
+        let qbytes: Vec<u8> = vec![0x00, 0x00, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00,
+                                   0x00, 0x00, 0x00, 0x00, 0x03, 0x77, 0x77, 0x77,
+                                   0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65,
+                                   0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00, 0x01];
+        let get = String::from_utf8(doh_request(&url, DohMethod::GET, &qbytes)).unwrap();
+        assert_eq!(get, "GET /dns-query?dns=AAABAAABAAAAAAAAA3d3dwdleGFtcGxlA2NvbQAAAQAB HTTP/1.1\r\n\
+                         Host: dns.example\r\n\
+                         Accept: application/dns-message\r\n\
+                         Connection: close\r\n\r\n");
+
+        let post = doh_request(&url, DohMethod::POST, &qbytes);
+        let headers = "POST /dns-query HTTP/1.1\r\n\
+                       Host: dns.example\r\n\
+                       Accept: application/dns-message\r\n\
+                       Content-Type: application/dns-message\r\n\
+                       Content-Length: 33\r\n\
+                       Connection: close\r\n\r\n";
+        assert_eq!(&post[.. headers.len()], headers.as_bytes());
+        assert_eq!(&post[headers.len() ..], &qbytes[..]);
+
+        // an existing query string gets added to, not replaced.
+        let url = parse_doh_url("https://dns.example/q?ct").unwrap();
+        let get = String::from_utf8(doh_request(&url, DohMethod::GET, &[0x00])).unwrap();
+        assert!(get.starts_with("GET /q?ct&dns=AA HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn read_http_response_test() {
+        let buf = b"HTTP/1.1 200 OK\r\nContent-Type: application/dns-message\r\nContent-Length: 3\r\n\r\nabcdef";
+        assert_eq!(read_http_response(&mut &buf[..]),
+                   Ok((200, Some(String::from("application/dns-message")), b"abc".to_vec())));
+
+        let buf = b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n2;ext=1\r\nab\r\n1\r\nc\r\n0\r\nTrailer: x\r\n\r\n";
+        assert_eq!(read_http_response(&mut &buf[..]), Ok((200, None, b"abc".to_vec())));
+
+        // no length at all means the body runs to the end of the connection.
+        let buf = b"HTTP/1.0 404 Not Found\n\nnope";
+        assert_eq!(read_http_response(&mut &buf[..]), Ok((404, None, b"nope".to_vec())));
+
+        assert!(read_http_response(&mut &b"SMTP/1.1 200 OK\r\n\r\n"[..]).is_err());
+        assert!(read_http_response(&mut &b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nabc"[..]).is_err());
+        assert!(read_http_response(&mut &b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n"[..]).is_err());
+        assert!(read_http_response(&mut &b"HTTP/1.1 200 OK\r\nContent-Length: 99999\r\n\r\n"[..]).is_err());
+    }
+
+    #[test]
+    fn https_send_query_test() {
+        let pki = make_test_pki();
+        let tls = test_tls_config(&pki);
+
+        for (method, chunked) in [(DohMethod::POST, false), (DohMethod::GET, false), (DohMethod::GET, true)] {
+            let (server, handle) = spawn_https_server(&pki, Answer { chunked, ..GOOD_ANSWER });
+            let url = DohUrl::new(String::from("dns.test"), server.port(), String::from("/dns-query"));
+            let doh = DnsDohConfig::new(url, method);
+
+            let response = https::send_query(&server, &make_test_query(0x8484), &doh, &tls, TIMEOUT).unwrap();
+            let (request_line, query) = handle.join().unwrap();
+
+            assert!(request_line.starts_with(&format!("{method} /dns-query")), "{request_line}");
+            // the id goes out as 0, whatever the query had.
+            assert_eq!(&query[.. 2], &[0x00, 0x00]);
+            assert_eq!(response.header().id(), 0);
+            assert!(response.header().response());
+            assert_eq!(response.questions(), make_test_query(0x8484).questions());
+        }
+    }
+
+    #[test]
+    fn https_bad_answer_test() {
+        let pki = make_test_pki();
+        let tls = test_tls_config(&pki);
+
+        let (server, handle) = spawn_https_server(&pki, Answer { status: 404, ..GOOD_ANSWER });
+        let url = DohUrl::new(String::from("dns.test"), server.port(), String::from("/dns-query"));
+        let doh = DnsDohConfig::new(url, DohMethod::POST);
+        assert_eq!(https::send_query(&server, &make_test_query(0x8484), &doh, &tls, TIMEOUT).err(),
+                   Some(DnsError::Transport(format!("https://dns.test:{}/dns-query answered with HTTP status 404.",
+                                                    server.port()))));
+        handle.join().unwrap();
+
+        let (server, handle) = spawn_https_server(&pki, Answer { content_type: "text/html", ..GOOD_ANSWER });
+        let url = DohUrl::new(String::from("dns.test"), server.port(), String::from("/dns-query"));
+        let doh = DnsDohConfig::new(url, DohMethod::POST);
+        let e = https::send_query(&server, &make_test_query(0x8484), &doh, &tls, TIMEOUT).unwrap_err();
+        assert!(e.to_string().contains("answered with Content-Type 'text/html'"), "{e}");
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn dnsclient_https_test() {
+        let pki = make_test_pki();
+        let (server, handle) = spawn_https_server(&pki, GOOD_ANSWER);
+
+        let mut client = DnsClient::new(vec![server], TIMEOUT, 0, DnsTransport::HTTPS);
+        client.set_tls_config(test_tls_config(&pki));
+        let url = parse_doh_url(&format!("https://dns.test:{}/dns-query", server.port())).unwrap();
+        client.set_doh_config(Some(DnsDohConfig::new(url, DohMethod::POST)));
+
+        let (result, attempts) = client.send_with_attempts(
+            &client.make_query("google.com.", DnsQType::A, DnsQClass::IN).unwrap());
+        handle.join().unwrap();
+        assert!(result.is_ok());
+        assert_eq!(attempts[0].transport(), DnsTransport::HTTPS);
+        assert_eq!(DnsTransport::HTTPS.default_port(), DOH_PORT);
+
+        client.set_doh_config(None);
+        let (result, _) = client.send_with_attempts(
+            &client.make_query("google.com.", DnsQType::A, DnsQClass::IN).unwrap());
+        assert!(result.is_err());
+    }
+}
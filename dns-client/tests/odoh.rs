@@ -0,0 +1,200 @@
+#[cfg(all(test, feature = "odoh"))]
+mod common;
+
+#[cfg(all(test, feature = "odoh"))]
+mod tests {
+
+    use crate::common::*;
+    use dns_client::dns_client_lib::*;
+    use dns_client::dns_client_lib::client::*;
+    use dns_client::dns_client_lib::https::DohUrl;
+    use dns_client::dns_client_lib::odoh;
+    use dns_client::dns_client_lib::odoh::*;
+    use dns_client::dns_client_lib::tls::DnsTlsConfig;
+    use dns_client::dns_client_lib::transport::DnsTransport;
+    use rustls::{ServerConnection, StreamOwned};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{SocketAddr, TcpListener};
+    use std::thread;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /* a stand-in that's both proxy and target, for connections requests. it serves the
+       configs for keys, and answers queries by echoing them back with QR set. the thread
+       hands back the request lines it got. */
+    fn spawn_odoh_server(pki: &TestPki, keys: ObliviousDoHKeyPair,
+                         connections: usize) -> (SocketAddr, thread::JoinHandle<Vec<String>>) {
+        let config = server_config(pki, &[b"http/1.1"]);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut request_lines: Vec<String> = Vec::new();
+            for _ in 0 .. connections {
+                let (sock, _) = listener.accept().unwrap();
+                let conn = ServerConnection::new(config.clone()).unwrap();
+                let mut stream = BufReader::new(StreamOwned::new(conn, sock));
+
+                let mut request_line = String::new();
+                stream.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                let mut content_type = String::new();
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).unwrap();
+                    if line.trim_end().is_empty() {
+                        break;
+                    }
+                    if let Some(l) = line.strip_prefix("Content-Length: ") {
+                        content_length = l.trim().parse().unwrap();
+                    }
+                    if let Some(t) = line.strip_prefix("Content-Type: ") {
+                        content_type = t.trim().to_string();
+                    }
+                }
+                let mut body = vec![0u8; content_length];
+                stream.read_exact(&mut body).unwrap();
+
+                let (media_type, response) = if request_line.starts_with("GET ") {
+                    ("application/octet-stream", odoh_configs_to_bytes(&[keys.config().clone()]).unwrap())
+                } else {
+                    assert_eq!(content_type, ODOH_MEDIA_TYPE);
+                    let message = ObliviousDoHMessage::from_bytes(&body).unwrap();
+                    let (mut query, context) = keys.decrypt_query(&message).unwrap();
+                    query[2] |= 0x80;
+                    (ODOH_MEDIA_TYPE, context.encrypt_response(&query).unwrap().to_bytes().unwrap())
+                };
+                let mut bytes = format!("HTTP/1.1 200 OK\r\nContent-Type: {media_type}\r\nContent-Length: {}\r\n\r\n",
+                                        response.len()).into_bytes();
+                bytes.extend_from_slice(&response);
+                let stream = stream.get_mut();
+                stream.write_all(&bytes).unwrap();
+                stream.flush().unwrap();
+                request_lines.push(request_line.trim_end().to_string());
+            }
+            request_lines
+        });
+        (addr, handle)
+    }
+
+    fn test_tls_config(pki: &TestPki) -> DnsTlsConfig {
+        let mut config = DnsTlsConfig::default();
+        config.add_ca_pem(pki.ca_pem.as_bytes()).unwrap();
+        config
+    }
+
+    fn test_url(addr: &SocketAddr, path: &str) -> DohUrl {
+        DohUrl::new(String::from("dns.test"), addr.port(), String::from(path))
+    }
+
+    #[test]
+    fn odoh_configs_test() {
+        let keys = ObliviousDoHKeyPair::new([0x42u8; 32]);
+        let unsupported = ObliviousDoHConfig::new(0x0010, 0x0001, 0x0001, vec![0x04; 65]);
+        let configs = vec![unsupported.clone(), keys.config().clone()];
+        let bytes = odoh_configs_to_bytes(&configs).unwrap();
+        assert_eq!(parse_odoh_configs(&bytes), Ok(configs.clone()));
+        assert_eq!(select_odoh_config(&configs), Ok(keys.config().clone()));
+        assert!(select_odoh_config(&[unsupported]).is_err());
+        assert_eq!(keys.config().key_id().unwrap().len(), 32);
+
+        // a config of some other version is skipped over.
+        let mut other = vec![0xFF, 0x01, 0x00, 0x02, 0xAB, 0xCD];
+        other.extend_from_slice(&keys.config().to_bytes().unwrap());
+        let mut bytes = (other.len() as u16).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&other);
+        assert_eq!(parse_odoh_configs(&bytes), Ok(vec![keys.config().clone()]));
+
+        bytes.push(0);
+        assert_eq!(parse_odoh_configs(&bytes),
+                   Err(DnsError::Crypto(String::from("Got 1 bytes after the end of the ODoH configs."))));
+        assert!(parse_odoh_configs(&bytes[.. 10]).is_err());
+    }
+
+    #[test]
+    fn odoh_encrypt_decrypt_test() {
+        let keys = ObliviousDoHKeyPair::generate();
+        let qbytes = vec![0x00, 0x00, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let (message, context) = encrypt_query(keys.config(), &qbytes).unwrap();
+        assert_eq!(message.message_type(), ODOH_QUERY);
+        assert_eq!(message.key_id(), &keys.config().key_id().unwrap()[..]);
+        // the ephemeral key, then the query padded to 128 bytes, then the tag.
+        assert_eq!(message.encrypted_message().len(), 32 + 128 + 16);
+        assert_eq!(ObliviousDoHMessage::from_bytes(&message.to_bytes().unwrap()), Ok(message.clone()));
+
+        let (got, target_context) = keys.decrypt_query(&message).unwrap();
+        assert_eq!(got, qbytes);
+        assert!(ObliviousDoHKeyPair::generate().decrypt_query(&message).is_err());
+
+        let response = target_context.encrypt_response(b"the answer").unwrap();
+        assert_eq!(response.message_type(), ODOH_RESPONSE);
+        assert_eq!(context.decrypt_response(&response), Ok(b"the answer".to_vec()));
+
+        let mut tampered = response.encrypted_message().to_vec();
+        tampered[0] ^= 0x01;
+        let tampered = ObliviousDoHMessage::new(ODOH_RESPONSE, response.key_id().to_vec(), tampered);
+        assert!(context.decrypt_response(&tampered).is_err());
+        assert!(context.decrypt_response(&message).is_err());
+    }
+
+    #[test]
+    fn odoh_proxy_path_test() {
+        let proxy = DohUrl::new(String::from("proxy.example"), 443, String::from("/proxy"));
+        let target = DohUrl::new(String::from("target.example"), 8443, String::from("/dns-query"));
+        assert_eq!(odoh_proxy_path(&proxy, &target),
+                   "/proxy?targethost=target.example%3A8443&targetpath=/dns-query");
+        let proxy = DohUrl::new(String::from("proxy.example"), 443, String::from("/proxy?a=b"));
+        let target = DohUrl::new(String::from("target.example"), 443, String::from("/dns-query"));
+        assert_eq!(odoh_proxy_path(&proxy, &target),
+                   "/proxy?a=b&targethost=target.example&targetpath=/dns-query");
+    }
+
+    #[test]
+    fn odoh_send_query_test() {
+        let pki = make_test_pki();
+        let keys = ObliviousDoHKeyPair::generate();
+        let (server, handle) = spawn_odoh_server(&pki, keys.clone(), 2);
+        let tls = test_tls_config(&pki);
+
+        let target = test_url(&server, "/dns-query");
+        let configs = fetch_odoh_configs(&server, &target, &tls, TIMEOUT).unwrap();
+        assert_eq!(configs, vec![keys.config().clone()]);
+
+        let odoh_config = DnsOdohConfig::new(test_url(&server, "/proxy"), target, select_odoh_config(&configs).unwrap());
+        let h = DnsHeader::new(0x9230u16, false, DnsOpcode::QUERY, false, false,
+                               true, false, DnsRcode::NOERROR);
+        let qrv = vec![DnsQuestionRecord::new(String::from("www.example.com."), DnsQType::A, DnsQClass::IN)];
+        let query = DnsQuery::new(h, qrv, None);
+        let response = odoh::send_query(&server, &query, &odoh_config, &tls, TIMEOUT).unwrap();
+        assert_eq!(response.header().id(), 0);
+        assert!(response.header().response());
+
+        let request_lines = handle.join().unwrap();
+        assert_eq!(request_lines[0], "GET /.well-known/odohconfigs HTTP/1.1");
+        assert_eq!(request_lines[1], format!("POST /proxy?targethost=dns.test%3A{}&targetpath=/dns-query HTTP/1.1",
+                                             server.port()));
+    }
+
+    #[test]
+    fn dnsclient_odoh_test() {
+        let pki = make_test_pki();
+        let keys = ObliviousDoHKeyPair::generate();
+        let (server, handle) = spawn_odoh_server(&pki, keys.clone(), 1);
+
+        let mut client = DnsClient::new(vec![server], TIMEOUT, 0, DnsTransport::ODOH);
+        client.set_tls_config(test_tls_config(&pki));
+        let q = client.make_query("google.com.", DnsQType::A, DnsQClass::IN).unwrap();
+        let (result, _) = client.send_with_attempts(&q);
+        assert_eq!(result.err().map(|e| e.to_string()),
+                   Some(format!("{server}: No ODoH proxy and target configured to send an ODOH query through.")));
+
+        client.set_odoh_config(Some(DnsOdohConfig::new(test_url(&server, "/proxy"), test_url(&server, "/dns-query"),
+                                                       keys.config().clone())));
+        let (result, attempts) = client.send_with_attempts(&q);
+        handle.join().unwrap();
+        assert!(result.is_ok());
+        assert_eq!(attempts[0].transport(), DnsTransport::ODOH);
+        assert_eq!(DnsTransport::ODOH.default_port(), 443);
+    }
+}
@@ -0,0 +1,129 @@
+#[cfg(all(test, feature = "doq"))]
+mod common;
+
+#[cfg(all(test, feature = "doq"))]
+mod tests {
+
+    use crate::common::*;
+    use dns_client::dns_client_lib::*;
+    use dns_client::dns_client_lib::client::*;
+    use dns_client::dns_client_lib::quic;
+    use dns_client::dns_client_lib::quic::*;
+    use dns_client::dns_client_lib::tls::DnsTlsConfig;
+    use dns_client::dns_client_lib::transport::DnsTransport;
+    use quinn::Endpoint;
+    use quinn::crypto::rustls::QuicServerConfig;
+    use std::net::{SocketAddr, UdpSocket};
+    use std::sync::{Arc, mpsc};
+    use std::thread;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /* a stand-in DoQ server, answering one query by echoing it back with QR set. the thread
+       hands back the stream it got (length prefix and all), or nothing if the handshake failed. */
+    fn spawn_quic_server(pki: &TestPki, alpn: &[u8]) -> (SocketAddr, thread::JoinHandle<Vec<u8>>) {
+        let crypto = QuicServerConfig::try_from(server_config(pki, &[alpn])).unwrap();
+        let config = quinn::ServerConfig::with_crypto(Arc::new(crypto));
+        let (tx, rx) = mpsc::channel();
+
+        let handle = thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(async move {
+                let endpoint = Endpoint::server(config, "127.0.0.1:0".parse().unwrap()).unwrap();
+                tx.send(endpoint.local_addr().unwrap()).unwrap();
+
+                let incoming = endpoint.accept().await.unwrap();
+                let Ok(conn) = incoming.await else { return vec![] };
+                let (mut send, mut recv) = conn.accept_bi().await.unwrap();
+                let stream = recv.read_to_end(1024).await.unwrap();
+
+                let mut response = stream.clone();
+                response[4] |= 0x80; // the flags byte, past the length prefix and id.
+                send.write_all(&response).await.unwrap();
+                send.finish().unwrap();
+                // hang around until the client hangs up, so the response gets to it - cleanly, with NO_ERROR.
+                match conn.closed().await {
+                    quinn::ConnectionError::ApplicationClosed(close) =>
+                        assert_eq!(close.error_code, quinn::VarInt::from_u32(DOQ_NO_ERROR)),
+                    e => panic!("the client didn't close the connection: {e}")
+                }
+                stream
+            })
+        });
+        (rx.recv().unwrap(), handle)
+    }
+
+    #[test]
+    fn unframe_doq_message_test() {
+        assert_eq!(unframe_doq_message(&[0x00, 0x02, 0xAB, 0xCD]), Ok(vec![0xAB, 0xCD]));
+        assert_eq!(unframe_doq_message(&[0x00, 0x00]), Ok(vec![]));
+        assert_eq!(unframe_doq_message(&[0x00]),
+                   Err(DnsError::Transport(String::from("Got a 1 byte QUIC stream, too short for a length prefix."))));
+        assert_eq!(unframe_doq_message(&[0x00, 0x03, 0xAB, 0xCD]),
+                   Err(DnsError::Transport(String::from("Got a QUIC stream with a length prefix of 3, but 2 bytes of message."))));
+    }
+
+    #[test]
+    fn quic_send_query_test() {
+        let pki = make_test_pki();
+        let (server, handle) = spawn_quic_server(&pki, DOQ_ALPN);
+
+        let mut config = DnsTlsConfig::new(Some(String::from("dns.test")), vec![]);
+        config.add_ca_pem(pki.ca_pem.as_bytes()).unwrap();
+        let query = make_test_query(0x9250);
+        let response = quic::send_query(&server, &query, &config, TIMEOUT).unwrap();
+        let stream = handle.join().unwrap();
+
+        // length prefix, then the query - with its id zeroed.
+        let mut expected = query.to_bytes().unwrap();
+        expected[0] = 0;
+        expected[1] = 0;
+        assert_eq!(&stream[.. 2], &(expected.len() as u16).to_be_bytes());
+        assert_eq!(&stream[2 ..], &expected[..]);
+
+        assert_eq!(response.header().id(), 0);
+        assert!(response.header().response());
+    }
+
+    #[test]
+    fn quic_bad_server_test() {
+        let pki = make_test_pki();
+
+        // the server doesn't speak doq.
+        let (server, handle) = spawn_quic_server(&pki, b"h3");
+        let config = DnsTlsConfig::new(None, vec![pin_of(&pki.spki)]);
+        let e = quic::send_query(&server, &make_test_query(0x9250), &config, TIMEOUT).unwrap_err();
+        assert!(e.to_string().starts_with("QUIC handshake with "), "{e}");
+        assert!(handle.join().unwrap().is_empty());
+
+        // the server's key isn't the one we pinned.
+        let (server, handle) = spawn_quic_server(&pki, DOQ_ALPN);
+        let config = DnsTlsConfig::new(None, vec![[0x42u8; 32]]);
+        assert!(quic::send_query(&server, &make_test_query(0x9250), &config, TIMEOUT).is_err());
+        assert!(handle.join().unwrap().is_empty());
+
+        // nothing answers at all.
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = silent.local_addr().unwrap();
+        assert_eq!(quic::send_query(&server, &make_test_query(0x9250), &config, Duration::from_millis(200)).err(),
+                   Some(DnsError::Timeout(Duration::from_millis(200))));
+    }
+
+    #[test]
+    fn dnsclient_quic_test() {
+        let pki = make_test_pki();
+        let (server, handle) = spawn_quic_server(&pki, DOQ_ALPN);
+
+        let mut client = DnsClient::new(vec![server], TIMEOUT, 0, DnsTransport::QUIC);
+        client.set_tls_config(DnsTlsConfig::new(None, vec![pin_of(&pki.spki)]));
+        let q = client.make_query("google.com.", DnsQType::A, DnsQClass::IN).unwrap();
+        let (result, attempts) = client.send_with_attempts(&q);
+        handle.join().unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(attempts[0].transport(), DnsTransport::QUIC);
+        assert_eq!(attempts[0].outcome(), &DnsAttemptOutcome::Answered);
+        assert_eq!(DnsTransport::QUIC.default_port(), DOQ_PORT);
+    }
+}
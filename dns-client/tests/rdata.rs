@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use dns_client::dns_client_lib::*;
+    use dns_client::dns_client_lib::rdata::*;
+    use std::fmt;
+    use std::net::Ipv4Addr;
+    use std::sync::Once;
+
+    // a made up type, in the private use range (rfc6895, section 3.1): a host name and a note.
+    #[derive(Debug, Eq, PartialEq)]
+    struct DnsNOTERecord {
+        host: String,
+        note: String
+    }
+
+    impl fmt::Display for DnsNOTERecord {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "NOTE: {} says {}", self.host, self.note)
+        }
+    }
+
+    impl RData for DnsNOTERecord {
+        const TYPE: u16 = 65280;
+        const MNEMONIC: &'static str = "NOTE";
+
+        fn parse(buf: &[u8], offset: usize, rdlen: usize) -> Result<Self, DnsError> {
+            let (host, count) = dns_name_to_string(buf, offset)?;
+            let note = String::from_utf8_lossy(&buf[offset + count .. offset + rdlen]).into_owned();
+            Ok(DnsNOTERecord { host, note })
+        }
+
+        fn write(&self, w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            w.write_name(&self.host, false)?;
+            w.write_bytes(self.note.as_bytes());
+            Ok(())
+        }
+    }
+
+    // pretends to be an A record, which can't be registered over.
+    #[derive(Debug, Eq, PartialEq)]
+    struct FakeA;
+
+    impl fmt::Display for FakeA {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "fake")
+        }
+    }
+
+    impl RData for FakeA {
+        const TYPE: u16 = 1;
+        const MNEMONIC: &'static str = "A";
+
+        fn parse(_buf: &[u8], _offset: usize, _rdlen: usize) -> Result<Self, DnsError> {
+            Ok(FakeA)
+        }
+
+        fn write(&self, _w: &mut DnsMessageWriter) -> Result<(), DnsError> {
+            Ok(())
+        }
+    }
+
+    // the registry is for the whole process, so every test shares the one registration.
+    fn register_note() {
+        static REGISTER: Once = Once::new();
+        REGISTER.call_once(|| register_rdata::<DnsNOTERecord>().unwrap());
+    }
+
+    #[test]
+    fn register_rdata_test() {
+        register_note();
+        assert_eq!(register_rdata::<DnsNOTERecord>(),
+                   Err(DnsError::Config(String::from("Record type 65280 is already registered, as NOTE."))));
+        assert_eq!(register_rdata::<FakeA>(),
+                   Err(DnsError::Config(String::from("Record type 1 is already handled by this library."))));
+
+        assert_eq!(DnsQType::from_string("note"), Ok(DnsQType::RESERVED(65280)));
+        assert_eq!(DnsQType::RESERVED(65280).to_string(), "NOTE");
+        assert_eq!(registered_mnemonic(65281), None);
+    }
+
+    #[test]
+    fn registered_rdata_round_trip_test() {
+        register_note();
+        let note = DnsNOTERecord { host: String::from("www.example.com."), note: String::from("hello") };
+        let h = DnsHeader::new(0x1234, true, DnsOpcode::QUERY, false, false, true, true,
+                               DnsRcode::NOERROR);
+        let q = vec![DnsQuestionRecord::new(String::from("www.example.com."), DnsQType::RESERVED(65280),
+                                            DnsQClass::IN)];
+        let an = vec![DnsResourceRecord::new(String::from("www.example.com."), DnsQType::RESERVED(65280),
+                                             DnsQClass::IN, 60, DnsResourceRecordEnum::Registered(Box::new(note))),
+                      DnsResourceRecord::new(String::from("www.example.com."), DnsQType::A, DnsQClass::IN, 60,
+                                             DnsResourceRecordEnum::A(DnsARecord::new(Ipv4Addr::new(192, 0, 2, 1))))];
+        let r = DnsResponse::new(h, q, an, vec![], vec![]);
+        let buf = r.to_bytes().unwrap();
+        let parsed = DnsResponse::from_bytes(&buf, 0).unwrap();
+        assert_eq!(parsed, r);
+
+        let rdata = parsed.answers()[0].record().rdata();
+        assert_eq!(rdata.rtype(), 65280);
+        assert_eq!(rdata.downcast_ref::<DnsNOTERecord>().unwrap().note, "hello");
+        assert_eq!(parsed.answers()[0].to_string(), "www.example.com. IN 60 NOTE: www.example.com. says hello");
+
+        let rdata = parsed.answers()[1].record().rdata();
+        assert_eq!((rdata.rtype(), rdata.mnemonic()), (1, "A"));
+        assert!(rdata.downcast_ref::<DnsNOTERecord>().is_none());
+    }
+}
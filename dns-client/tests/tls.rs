@@ -0,0 +1,167 @@
+#[cfg(all(test, feature = "dot"))]
+mod common;
+
+#[cfg(all(test, feature = "dot"))]
+mod tests {
+
+    use crate::common::*;
+    use dns_client::dns_client_lib::*;
+    use dns_client::dns_client_lib::client::*;
+    use dns_client::dns_client_lib::tls;
+    use dns_client::dns_client_lib::tls::{DnsTlsConfig, DOT_PORT, parse_spki_pin, spki_pin};
+    use dns_client::dns_client_lib::transport::*;
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use rcgen::{CertificateParams, KeyPair};
+    use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+    use rustls::{ServerConnection, StreamOwned};
+    use std::io::Write;
+    use std::net::{SocketAddr, TcpListener};
+    use std::thread;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /* a stand-in DoT server, answering one query by echoing it back with QR set. the client
+       may well hang up mid-handshake, so errors here just end the thread. */
+    fn spawn_tls_server(pki: &TestPki) -> (SocketAddr, thread::JoinHandle<()>) {
+        let config = server_config(pki, &[]);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let (sock, _) = listener.accept().unwrap();
+            let conn = ServerConnection::new(config).unwrap();
+            let mut stream = StreamOwned::new(conn, sock);
+            let Ok(mut query) = read_tcp_frame(&mut stream) else { return };
+            query[2] |= 0x80;
+            let _ = stream.write_all(&tcp_frame(&query).unwrap()).and_then(|_| stream.flush());
+        });
+        (addr, handle)
+    }
+
+    fn query_with(pki: &TestPki, config: &DnsTlsConfig) -> Result<DnsResponse, DnsError> {
+        let (server, handle) = spawn_tls_server(pki);
+        let mut rejected: Vec<String> = Vec::new();
+        let result = tls::send_query(&server, &make_test_query(0x7858), config, TIMEOUT, &mut rejected);
+        handle.join().unwrap();
+        assert!(rejected.is_empty());
+        result
+    }
+
+    #[test]
+    fn tls_auth_name_test() {
+        let pki = make_test_pki();
+
+        let mut config = DnsTlsConfig::new(Some(String::from("dns.test.")), vec![]);
+        assert_eq!(config.add_ca_pem(pki.ca_pem.as_bytes()), Ok(1));
+        let response = query_with(&pki, &config).unwrap();
+        assert!(response.header().response());
+        assert_eq!(response.header().id(), 0x7858);
+
+        // right CA, wrong name.
+        let mut config = DnsTlsConfig::new(Some(String::from("other.test")), vec![]);
+        config.add_ca_pem(pki.ca_pem.as_bytes()).unwrap();
+        let e = query_with(&pki, &config).unwrap_err();
+        assert!(e.to_string().starts_with("TLS handshake with "), "{e}");
+
+        // right name, but we don't trust the CA.
+        let config = DnsTlsConfig::new(Some(String::from("dns.test")), vec![]);
+        let e = query_with(&pki, &config).unwrap_err();
+        assert!(e.to_string().contains("UnknownIssuer"), "{e}");
+
+        // no name means checking the cert against the server's ip, which it doesn't have.
+        let mut config = DnsTlsConfig::new(None, vec![]);
+        config.add_ca_pem(pki.ca_pem.as_bytes()).unwrap();
+        assert!(query_with(&pki, &config).is_err());
+    }
+
+    #[test]
+    fn tls_spki_pin_test() {
+        let pki = make_test_pki();
+        let pin = pin_of(&pki.spki);
+        let wrong_pin = [0x42u8; 32];
+
+        // a pin on its own is enough, even without trusting the CA.
+        let config = DnsTlsConfig::new(None, vec![wrong_pin, pin]);
+        let response = query_with(&pki, &config).unwrap();
+        assert_eq!(response.header().id(), 0x7858);
+
+        let config = DnsTlsConfig::new(None, vec![wrong_pin]);
+        let e = query_with(&pki, &config).unwrap_err();
+        assert!(e.to_string().contains("None of the server's certificates matched a configured SPKI pin."), "{e}");
+
+        // with a name as well, the chain has to check out too.
+        let config = DnsTlsConfig::new(Some(String::from("dns.test")), vec![pin]);
+        assert!(query_with(&pki, &config).is_err());
+        let mut config = DnsTlsConfig::new(Some(String::from("dns.test")), vec![pin]);
+        config.add_ca_pem(pki.ca_pem.as_bytes()).unwrap();
+        assert!(query_with(&pki, &config).is_ok());
+
+        // pinning the CA works once the chain up to it has been checked, and not before.
+        let ca_pin = spki_pin(&pki.chain[1]).unwrap();
+        let mut config = DnsTlsConfig::new(Some(String::from("dns.test")), vec![ca_pin]);
+        config.add_ca_pem(pki.ca_pem.as_bytes()).unwrap();
+        assert!(query_with(&pki, &config).is_ok());
+        let config = DnsTlsConfig::new(None, vec![ca_pin]);
+        assert!(query_with(&pki, &config).is_err());
+    }
+
+    #[test]
+    fn tls_spki_pin_intermediate_test() {
+        let pki = make_test_pki();
+
+        // someone else's certificate, sent along with a copy of the pinned one as an "intermediate".
+        let params = CertificateParams::new(vec![String::from("evil.test")]).unwrap();
+        let key = KeyPair::generate().unwrap();
+        let cert = params.self_signed(&key).unwrap();
+        let mitm = TestPki {
+            ca_pem: cert.pem(),
+            chain: vec![cert.der().clone(), pki.chain[0].clone()],
+            key: PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der())),
+            spki: key.public_key_der()
+        };
+
+        let config = DnsTlsConfig::new(None, vec![pin_of(&pki.spki)]);
+        let e = query_with(&mitm, &config).unwrap_err();
+        assert!(e.to_string().contains("None of the server's certificates matched a configured SPKI pin."), "{e}");
+    }
+
+    #[test]
+    fn spki_pin_test() {
+        let pki = make_test_pki();
+        assert_eq!(spki_pin(&pki.chain[0]), Ok(pin_of(&pki.spki)));
+        assert!(spki_pin(&[0x30, 0x03, 0x02, 0x01]).is_err());
+        assert!(spki_pin(&[]).is_err());
+
+        let pin = pin_of(&pki.spki);
+        let encoded = BASE64.encode(pin);
+        assert_eq!(parse_spki_pin(&encoded), Ok(pin));
+        assert_eq!(parse_spki_pin(&format!("pin-sha256=\"{encoded}\"")), Ok(pin));
+        assert!(parse_spki_pin("not base64!").is_err());
+        assert_eq!(parse_spki_pin("AAAA"),
+                   Err(DnsError::Config(String::from("SPKI pin 'AAAA' is 3 bytes, expected a 32 byte sha256."))));
+    }
+
+    #[test]
+    fn dnsclient_tls_test() {
+        let pki = make_test_pki();
+        let (server, handle) = spawn_tls_server(&pki);
+
+        let mut client = DnsClient::new(vec![server], TIMEOUT, 0, DnsTransport::TLS);
+        client.set_tls_config(DnsTlsConfig::new(None, vec![pin_of(&pki.spki)]));
+        let q = client.make_query("google.com.", DnsQType::A, DnsQClass::IN).unwrap();
+        let (result, attempts) = client.send_with_attempts(&q);
+        handle.join().unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(attempts.len(), 1);
+        assert_eq!(attempts[0].transport(), DnsTransport::TLS);
+        assert_eq!(attempts[0].outcome(), &DnsAttemptOutcome::Answered);
+
+        // plain send_query doesn't know how to do tls.
+        let mut rejected: Vec<String> = Vec::new();
+        assert!(send_query(&server, &q, DnsTransport::TLS, TIMEOUT, &mut rejected).is_err());
+        assert_eq!(DnsTransport::TLS.default_port(), DOT_PORT);
+    }
+}
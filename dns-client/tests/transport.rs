@@ -0,0 +1,224 @@
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+mod tests {
+
+    use crate::common::*;
+    use dns_client::dns_client_lib::*;
+    use dns_client::dns_client_lib::transport::*;
+    use std::io::{ErrorKind, Write};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, UdpSocket};
+    use std::str::FromStr;
+    use std::thread;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // turn a query into a response by setting QR (and, optionally, TC) in the flags.
+    fn echo_as_response(query: &[u8], truncated: bool) -> Vec<u8> {
+        let mut r = query.to_vec();
+        r[2] |= 0x80;
+        if truncated {
+            r[2] |= 0x02;
+        }
+        r
+    }
+
+    #[test]
+    fn tcp_frame_test() {
+        assert_eq!(tcp_frame(&[0xAB, 0xCD]), Ok(vec![0x00, 0x02, 0xAB, 0xCD]));
+        assert_eq!(tcp_frame(&[]), Ok(vec![0x00, 0x00]));
+        let big = vec![0u8; 65536];
+        assert_eq!(tcp_frame(&big),
+                   Err(DnsError::Oversize { context: "message to send over TCP", len: 65536, max: 65535 }));
+    }
+
+    #[test]
+    fn read_tcp_frame_test() {
+        let buf: Vec<u8> = vec![0x00, 0x03, 0x01, 0x02, 0x03, 0xFF]; // extra byte is left unread
+        assert_eq!(read_tcp_frame(&mut buf.as_slice()), Ok(vec![0x01, 0x02, 0x03]));
+
+        let buf: Vec<u8> = vec![0x00, 0x03, 0x01]; // prefix says 3 bytes, only 1 there
+        assert!(read_tcp_frame(&mut buf.as_slice()).is_err());
+        let buf: Vec<u8> = vec![0x00]; // not even a whole prefix
+        let e = read_tcp_frame(&mut buf.as_slice()).unwrap_err();
+        assert_eq!(e.io_kind(), Some(ErrorKind::UnexpectedEof));
+        assert!(e.to_string().starts_with("Error reading TCP length prefix: "), "{e}");
+    }
+
+    #[test]
+    fn tcp_refused_test() {
+        // nothing's listening on a port we've just had and let go of.
+        let server = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let e = send_query(&server, &make_test_query(0xABCD), DnsTransport::TCP, TIMEOUT, &mut vec![]).unwrap_err();
+        assert_eq!(e.io_kind(), Some(ErrorKind::ConnectionRefused));
+        assert!(!e.is_timeout());
+    }
+
+    #[test]
+    fn parse_server_addr_test() {
+        let v4 = IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8));
+        let v6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888));
+
+        assert_eq!(parse_server_addr("8.8.8.8"), Ok(SocketAddr::new(v4, 53)));
+        assert_eq!(parse_server_addr("8.8.8.8:5353"), Ok(SocketAddr::new(v4, 5353)));
+        assert_eq!(parse_server_addr("2001:4860:4860::8888"), Ok(SocketAddr::new(v6, 53)));
+        assert_eq!(parse_server_addr("[2001:4860:4860::8888]"), Ok(SocketAddr::new(v6, 53)));
+        assert_eq!(parse_server_addr("[2001:4860:4860::8888]:5353"), Ok(SocketAddr::new(v6, 5353)));
+        assert_eq!(parse_server_addr(" 8.8.8.8 "), Ok(SocketAddr::new(v4, 53)));
+
+        assert!(parse_server_addr("dns.google").is_err());
+        assert!(parse_server_addr("8.8.8.8:").is_err());
+        assert!(parse_server_addr("8.8.8.8:99999").is_err());
+        assert!(parse_server_addr("[8.8.8.8]").is_err());
+        assert!(parse_server_addr("2001:4860:4860:0:0:0:0:8888:53").is_err());
+    }
+
+    #[test]
+    fn parse_server_addr_with_port_test() {
+        let v4 = IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1));
+        assert_eq!(parse_server_addr_with_port("1.1.1.1", 853), Ok(SocketAddr::new(v4, 853)));
+        assert_eq!(parse_server_addr_with_port("1.1.1.1:53", 853), Ok(SocketAddr::new(v4, 53)));
+        assert_eq!(parse_server_addr_with_port("[::1]", 853),
+                   Ok(SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 853)));
+        assert_eq!(DnsTransport::UDP.default_port(), DNS_PORT);
+        assert_eq!(DnsTransport::TCP.default_port(), DNS_PORT);
+    }
+
+    #[test]
+    fn local_bind_addr_test() {
+        assert_eq!(local_bind_addr(&parse_server_addr("8.8.8.8").unwrap()),
+                   SocketAddr::from_str("0.0.0.0:0").unwrap());
+        assert_eq!(local_bind_addr(&parse_server_addr("::1").unwrap()),
+                   SocketAddr::from_str("[::]:0").unwrap());
+    }
+
+    #[test]
+    fn send_query_tcp_fallback_test() {
+        // a stand-in server: truncates everything over udp, answers in full over tcp.
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = tcp.local_addr().unwrap();
+        let udp = UdpSocket::bind(server).unwrap();
+
+        let udp_thread = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, from) = udp.recv_from(&mut buf).unwrap();
+            udp.send_to(&echo_as_response(&buf[..len], true), from).unwrap();
+        });
+        let tcp_thread = thread::spawn(move || {
+            let (mut stream, _) = tcp.accept().unwrap();
+            let query = read_tcp_frame(&mut stream).unwrap();
+            let response = tcp_frame(&echo_as_response(&query, false)).unwrap();
+            stream.write_all(&response).unwrap();
+        });
+
+        let mut rejected: Vec<String> = Vec::new();
+        let (response, used) = send_query(&server, &make_test_query(0xABCD),
+                                          DnsTransport::UDP, TIMEOUT, &mut rejected).unwrap();
+        assert!(rejected.is_empty());
+        assert_eq!(used, DnsTransport::TCP);
+        assert!(!response.header().tc());
+        assert!(response.header().response());
+        assert_eq!(response.header().id(), 0xABCD);
+
+        udp_thread.join().unwrap();
+        tcp_thread.join().unwrap();
+    }
+
+    #[test]
+    fn send_query_udp_test() {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        udp_echo_test(udp);
+    }
+
+    #[test]
+    fn send_query_udp_v6_test() {
+        let udp = UdpSocket::bind("[::1]:0").unwrap();
+        udp_echo_test(udp);
+    }
+
+    fn udp_echo_test(udp: UdpSocket) {
+        let server = udp.local_addr().unwrap();
+
+        let udp_thread = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, from) = udp.recv_from(&mut buf).unwrap();
+            udp.send_to(&echo_as_response(&buf[..len], false), from).unwrap();
+        });
+
+        let mut rejected: Vec<String> = Vec::new();
+        let (response, used) = send_query(&server, &make_test_query(0xABCD),
+                                          DnsTransport::UDP, TIMEOUT, &mut rejected).unwrap();
+        assert!(rejected.is_empty());
+        assert_eq!(used, DnsTransport::UDP);
+        assert_eq!(response.header().id(), 0xABCD);
+
+        udp_thread.join().unwrap();
+    }
+
+    #[test]
+    fn send_query_drops_mismatched_test() {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = udp.local_addr().unwrap();
+        let spoofer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let spoofer_addr = spoofer.local_addr().unwrap();
+
+        let udp_thread = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, from) = udp.recv_from(&mut buf).unwrap();
+            let good = echo_as_response(&buf[..len], false);
+
+            // right everything, wrong source address.
+            spoofer.send_to(&good, from).unwrap();
+            // wrong qid.
+            let mut bad = good.clone();
+            bad[1] ^= 0xFF;
+            udp.send_to(&bad, from).unwrap();
+            // not a response.
+            udp.send_to(&buf[..len], from).unwrap();
+            // wrong question (qtype AAAA instead of A).
+            let mut bad = good.clone();
+            bad[len - 3] = 0x1c;
+            udp.send_to(&bad, from).unwrap();
+            // and finally, the real answer.
+            udp.send_to(&good, from).unwrap();
+        });
+
+        let mut rejected: Vec<String> = Vec::new();
+        let (response, used) = send_query(&server, &make_test_query(0xABCD),
+                                          DnsTransport::UDP, TIMEOUT, &mut rejected).unwrap();
+        assert_eq!(used, DnsTransport::UDP);
+        assert_eq!(response.header().id(), 0xABCD);
+        assert_eq!(rejected, vec![
+            format!("Dropped a packet from {spoofer_addr}, which we didn't send a query to."),
+            format!("Dropped a packet from {server}: Got a response with QID ab32, expected abcd."),
+            format!("Dropped a packet from {server}: Got a packet without the QR bit set."),
+            format!("Dropped a packet from {server}: Got a response to question 'google.com. AAAA IN', expected 'google.com. A IN'.")]);
+
+        udp_thread.join().unwrap();
+    }
+
+    #[test]
+    fn send_query_mismatched_timeout_test() {
+        // a server that only ever answers with the wrong qid - we should give up at the timeout.
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = udp.local_addr().unwrap();
+
+        let udp_thread = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, from) = udp.recv_from(&mut buf).unwrap();
+            let mut bad = echo_as_response(&buf[..len], false);
+            bad[0] ^= 0xFF;
+            udp.send_to(&bad, from).unwrap();
+        });
+
+        let mut rejected: Vec<String> = Vec::new();
+        let result = send_query(&server, &make_test_query(0xABCD), DnsTransport::UDP,
+                                Duration::from_millis(100), &mut rejected);
+        assert_eq!(result.err(), Some(DnsError::Timeout(Duration::from_millis(100))));
+        assert_eq!(rejected.len(), 1);
+
+        udp_thread.join().unwrap();
+    }
+}
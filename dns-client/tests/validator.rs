@@ -0,0 +1,498 @@
+#[cfg(all(test, feature = "dnssec"))]
+mod tests {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use dns_client::dns_client_lib::*;
+    use dns_client::dns_client_lib::dnssec::*;
+    use dns_client::dns_client_lib::validator::*;
+    use ring::rand::SystemRandom;
+    use ring::{digest, rsa, signature};
+    use std::collections::HashMap;
+    use std::net::Ipv4Addr;
+
+    const INCEPTION: u32 = 1700000000;
+    const EXPIRATION: u32 = 1900000000;
+    const NSEC3_SALT: [u8; 2] = [0xab, 0xcd];
+    const NSEC3_ITERATIONS: u16 = 1;
+
+    enum KeyPair {
+        Ed25519(signature::Ed25519KeyPair),
+        Ecdsa(signature::EcdsaKeyPair),
+        Rsa(signature::RsaKeyPair)
+    }
+
+    // a zone of the test tree, with the one key (a KSK, for simplicity) that signs everything in it.
+    struct Zone {
+        name: &'static str,
+        key: KeyPair,
+        dnskey: DnsDNSKEYRecord
+    }
+
+    impl Zone {
+        fn new(name: &'static str, algorithm: u8) -> Zone {
+            let rng = SystemRandom::new();
+            let (key, public_key) = match algorithm {
+                8 => {
+                    let key = signature::RsaKeyPair::from_pkcs8(include_bytes!("data/rsa-2048.pk8")).unwrap();
+                    let public = rsa::PublicKeyComponents::<Vec<u8>>::from(key.public());
+                    // rfc3110: the exponent's length, the exponent, then the modulus.
+                    let mut public_key = vec![public.e.len() as u8];
+                    public_key.extend_from_slice(&public.e);
+                    public_key.extend_from_slice(&public.n);
+                    (KeyPair::Rsa(key), public_key)
+                },
+                13 | 14 => {
+                    let alg = if algorithm == 13 {
+                        &signature::ECDSA_P256_SHA256_FIXED_SIGNING
+                    } else {
+                        &signature::ECDSA_P384_SHA384_FIXED_SIGNING
+                    };
+                    let pkcs8 = signature::EcdsaKeyPair::generate_pkcs8(alg, &rng).unwrap();
+                    let key = signature::EcdsaKeyPair::from_pkcs8(alg, pkcs8.as_ref(), &rng).unwrap();
+                    // without the 0x04 that marks an uncompressed point.
+                    let public_key = signature::KeyPair::public_key(&key).as_ref()[1 ..].to_vec();
+                    (KeyPair::Ecdsa(key), public_key)
+                },
+                _ => {
+                    let key = signature::Ed25519KeyPair::from_seed_unchecked(&[0x42; 32]).unwrap();
+                    let public_key = signature::KeyPair::public_key(&key).as_ref().to_vec();
+                    (KeyPair::Ed25519(key), public_key)
+                }
+            };
+            Zone { name, key, dnskey: DnsDNSKEYRecord::new(257, 3, algorithm, public_key) }
+        }
+
+        fn ds(&self) -> DnsDSRecord {
+            make_ds(self.name, &self.dnskey, 2).unwrap()
+        }
+
+        fn sign(&self, data: &[u8]) -> Vec<u8> {
+            let rng = SystemRandom::new();
+            match &self.key {
+                KeyPair::Ed25519(key) => key.sign(data).as_ref().to_vec(),
+                KeyPair::Ecdsa(key) => key.sign(&rng, data).unwrap().as_ref().to_vec(),
+                KeyPair::Rsa(key) => {
+                    let mut sig = vec![0; key.public().modulus_len()];
+                    key.sign(&signature::RSA_PKCS1_SHA256, &rng, data, &mut sig).unwrap();
+                    sig
+                }
+            }
+        }
+
+        // the RRSIG over records, which all have the same name and type.
+        fn rrsig(&self, records: &[DnsResourceRecord]) -> DnsResourceRecord {
+            let owner = records[0].name();
+            let labels = owner.trim_end_matches('.').split('.')
+                .filter(|l| !l.is_empty() && *l != "*").count() as u8;
+            let unsigned = DnsRRSIGRecord::new(records[0].qtype(), self.dnskey.algorithm(), labels, 3600,
+                                               EXPIRATION, INCEPTION, self.dnskey.key_tag(),
+                                               String::from(self.name), vec![]);
+            let refs: Vec<&DnsResourceRecord> = records.iter().collect();
+            let sig = self.sign(&signed_data(&unsigned, &refs).unwrap());
+            let rrsig = DnsRRSIGRecord::new(records[0].qtype(), self.dnskey.algorithm(), labels, 3600,
+                                            EXPIRATION, INCEPTION, self.dnskey.key_tag(),
+                                            String::from(self.name), sig);
+            rr(owner, DnsQType::RRSIG, DnsResourceRecordEnum::RRSIG(rrsig))
+        }
+
+        // records, followed by their RRSIG.
+        fn signed(&self, mut records: Vec<DnsResourceRecord>) -> Vec<DnsResourceRecord> {
+            let rrsig = self.rrsig(&records);
+            records.push(rrsig);
+            records
+        }
+
+        fn soa(&self) -> Vec<DnsResourceRecord> {
+            self.signed(vec![soa(self.name)])
+        }
+
+        // the answer to an SOA query for name, in this zone: the SOA itself at the apex, or NODATA with it.
+        fn soa_response(&self, name: &str) -> DnsResponse {
+            if name == self.name {
+                response(name, DnsQType::SOA, DnsRcode::NOERROR, self.soa(), vec![])
+            } else {
+                response(name, DnsQType::SOA, DnsRcode::NOERROR, vec![], self.soa())
+            }
+        }
+
+        fn dnskey_response(&self) -> DnsResponse {
+            let key = rr(self.name, DnsQType::DNSKEY, DnsResourceRecordEnum::DNSKEY(self.dnskey.clone()));
+            response(self.name, DnsQType::DNSKEY, DnsRcode::NOERROR, self.signed(vec![key]), vec![])
+        }
+
+        // the DS set for child, as this zone would serve it.
+        fn ds_response(&self, child: &Zone) -> DnsResponse {
+            let ds = rr(child.name, DnsQType::DS, DnsResourceRecordEnum::DS(child.ds()));
+            response(child.name, DnsQType::DS, DnsRcode::NOERROR, self.signed(vec![ds]), vec![])
+        }
+
+        fn nsec(&self, owner: &str, next: &str, types: Vec<DnsQType>) -> Vec<DnsResourceRecord> {
+            let nsec = DnsNSECRecord::new(String::from(next), types);
+            self.signed(vec![rr(owner, DnsQType::NSEC, DnsResourceRecordEnum::NSEC(nsec))])
+        }
+    }
+
+    fn rr(name: &str, qtype: DnsQType, record: DnsResourceRecordEnum) -> DnsResourceRecord {
+        DnsResourceRecord::new(String::from(name), qtype, DnsQClass::IN, 3600, record)
+    }
+
+    fn a(name: &str, addr: [u8; 4]) -> DnsResourceRecord {
+        rr(name, DnsQType::A, DnsResourceRecordEnum::A(DnsARecord::new(Ipv4Addr::from(addr))))
+    }
+
+    fn soa(zone: &str) -> DnsResourceRecord {
+        let apex = zone.trim_start_matches('.');
+        let soa = DnsSOARecord::new(format!("ns.{apex}"), format!("hostmaster.{apex}"), 1, 7200, 3600, 1209600, 3600);
+        rr(zone, DnsQType::SOA, DnsResourceRecordEnum::SOA(soa))
+    }
+
+    fn response(name: &str, qtype: DnsQType, rcode: DnsRcode, answers: Vec<DnsResourceRecord>,
+                authorities: Vec<DnsResourceRecord>) -> DnsResponse {
+        let h = DnsHeader::new(0x1234, true, DnsOpcode::QUERY, true, false, true, true, rcode);
+        let q = vec![DnsQuestionRecord::new(String::from(name), qtype, DnsQClass::IN)];
+        DnsResponse::new(h, q, answers, authorities, vec![])
+    }
+
+    // the raw NSEC3 hash of name, for the next hashed owner field.
+    fn raw_nsec3_hash(name: &str) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        for label in name.trim_end_matches('.').split('.') {
+            data.push(label.len() as u8);
+            data.extend_from_slice(label.as_bytes());
+        }
+        data.push(0);
+        let mut hash = data;
+        for _ in 0 ..= NSEC3_ITERATIONS {
+            hash.extend_from_slice(&NSEC3_SALT);
+            hash = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &hash).as_ref().to_vec();
+        }
+        hash
+    }
+
+    /* a signed tree, all made up here: the root (Ed25519) delegates to example. (ECDSA P-256,
+       denial with NSEC), which delegates to nsec3.example. (ECDSA P-384, with NSEC3),
+       rsa.example. (RSA/SHA-256), and insecure.example., which isn't signed. */
+    struct Tree {
+        root: Zone,
+        example: Zone,
+        nsec3: Zone,
+        rsa: Zone,
+        responses: HashMap<(String, u16), Vec<u8>>
+    }
+
+    impl Tree {
+        fn new() -> Tree {
+            let mut tree = Tree { root: Zone::new(".", 15), example: Zone::new("example.", 13),
+                                  nsec3: Zone::new("nsec3.example.", 14), rsa: Zone::new("rsa.example.", 8),
+                                  responses: HashMap::new() };
+            tree.add(tree.root.dnskey_response());
+            tree.add(tree.root.ds_response(&tree.example));
+            tree.add(tree.example.dnskey_response());
+            tree.add(tree.example.ds_response(&tree.nsec3));
+            tree.add(tree.example.ds_response(&tree.rsa));
+            tree.add(tree.nsec3.dnskey_response());
+            tree.add(tree.rsa.dnskey_response());
+
+            // which zone each name is in.
+            let soas = [tree.root.soa_response("."), tree.example.soa_response("example."),
+                        tree.example.soa_response("www.example."), tree.example.soa_response("nope.example."),
+                        tree.nsec3.soa_response("nsec3.example."), tree.nsec3.soa_response("www.nsec3.example."),
+                        tree.nsec3.soa_response("nope.nsec3.example."), tree.rsa.soa_response("rsa.example."),
+                        tree.rsa.soa_response("www.rsa.example.")];
+            for r in soas {
+                tree.add(r);
+            }
+
+            // no DS for insecure.example., and the NSEC there says so.
+            let mut authorities = tree.example.soa();
+            authorities.append(&mut tree.example.nsec("insecure.example.", "nsec3.example.",
+                                                      vec![DnsQType::NS, DnsQType::RRSIG, DnsQType::NSEC]));
+            tree.add(response("insecure.example.", DnsQType::DS, DnsRcode::NOERROR, vec![], authorities));
+            tree.add(response("host.insecure.example.", DnsQType::SOA, DnsRcode::NOERROR, vec![],
+                              vec![soa("insecure.example.")]));
+            tree
+        }
+
+        fn add(&mut self, r: DnsResponse) {
+            let q = &r.questions()[0];
+            self.responses.insert((q.name().to_ascii_lowercase(), q.qtype().to_u16()), r.to_bytes().unwrap());
+        }
+
+        fn remove(&mut self, name: &str, qtype: DnsQType) {
+            self.responses.remove(&(String::from(name), qtype.to_u16()));
+        }
+
+        fn validator(&self) -> DnsValidator {
+            let mut validator = DnsValidator::new(vec![(String::from("."), self.root.ds())]);
+            validator.set_now(Some(1800000000));
+            validator
+        }
+
+        fn validate(&self, validator: &DnsValidator, r: &DnsResponse) -> DnssecStatus {
+            validator.validate(r, |name, qtype| {
+                match self.responses.get(&(name.to_ascii_lowercase(), qtype.to_u16())) {
+                    Some(buf) => Ok(DnsResponse::from_bytes(buf, 0)?),
+                    None => Err(DnsError::Transport(format!("No test data for {name} {qtype}.")))
+                }
+            })
+        }
+
+        // the NSEC chain of example.
+        fn example_nsec(&self, owner: &str) -> Vec<DnsResourceRecord> {
+            let (next, types) = match owner {
+                "example." => ("insecure.example.", vec![DnsQType::NS, DnsQType::SOA, DnsQType::RRSIG,
+                                                         DnsQType::NSEC, DnsQType::DNSKEY]),
+                "insecure.example." => ("nsec3.example.", vec![DnsQType::NS, DnsQType::RRSIG, DnsQType::NSEC]),
+                "nsec3.example." => ("rsa.example.", vec![DnsQType::NS, DnsQType::DS, DnsQType::RRSIG,
+                                                          DnsQType::NSEC]),
+                "rsa.example." => ("www.example.", vec![DnsQType::NS, DnsQType::DS, DnsQType::RRSIG,
+                                                        DnsQType::NSEC]),
+                _ => ("example.", vec![DnsQType::A, DnsQType::RRSIG, DnsQType::NSEC])
+            };
+            self.example.nsec(owner, next, types)
+        }
+
+        // the NSEC3 chain of nsec3.example., which has the apex and www in it.
+        fn nsec3_chain(&self) -> Vec<DnsResourceRecord> {
+            let mut names = [("nsec3.example.", vec![DnsQType::NS, DnsQType::SOA, DnsQType::RRSIG,
+                                                         DnsQType::DNSKEY, DnsQType::NSEC3PARAM]),
+                                 ("www.nsec3.example.", vec![DnsQType::A, DnsQType::RRSIG])];
+            names.sort_by_key(|(name, _)| raw_nsec3_hash(name));
+            let mut ret: Vec<DnsResourceRecord> = Vec::new();
+            for (i, (name, types)) in names.iter().enumerate() {
+                let next = raw_nsec3_hash(names[(i + 1) % names.len()].0);
+                let nsec3 = DnsNSEC3Record::new(1, 0, NSEC3_ITERATIONS, NSEC3_SALT.to_vec(), next, types.clone());
+                let owner = format!("{}.nsec3.example.", nsec3_hash(name, &NSEC3_SALT, NSEC3_ITERATIONS));
+                ret.append(&mut self.nsec3.signed(vec![rr(&owner, DnsQType::NSEC3,
+                                                          DnsResourceRecordEnum::NSEC3(nsec3))]));
+            }
+            ret
+        }
+    }
+
+    #[test]
+    fn make_ds_test() {
+        // the example from rfc4034, section 5.4.
+        let key = BASE64.decode("AQOeiiR0GOMYkDshWoSKz9XzfwJr1AYtsmx3TGkJaNXVbfi/2pHm822aJ5iI9BMzNXxeYCmZ\
+                                 DRD99WYwYqUSdjMmmAphXdvxegXd/M5+X7OrzKBaMbCVdFLUUh6DhweJBjEVv5f2wwjM9Xzc\
+                                 nOf+EPbtG9DMBmADjFDc2w/rljwvFw==").unwrap();
+        let key = DnsDNSKEYRecord::new(256, 3, 5, key);
+        let ds = make_ds("dskey.example.com.", &key, 1).unwrap();
+        assert_eq!(ds.to_string(), "DS: 60485 5 1 2BB183AF5F22588179A53B0A98631FAD1A292118");
+        // the owner name is lowercased first.
+        assert_eq!(make_ds("DSKEY.Example.COM", &key, 1), Ok(ds));
+        assert!(matches!(make_ds("dskey.example.com.", &key, 3), Err(DnsError::Crypto(_))));
+
+        // KSK-2017, as published by iana.
+        let root = DnsValidator::root();
+        assert_eq!(root.anchors().len(), 2);
+        assert_eq!(root.anchors()[0].1.to_string(),
+                   "DS: 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D");
+    }
+
+    #[test]
+    fn secure_answer_test() {
+        let tree = Tree::new();
+        let validator = tree.validator();
+
+        let answers = tree.example.signed(vec![a("www.example.", [192, 0, 2, 1]), a("www.example.", [192, 0, 2, 2])]);
+        let mut r = response("www.example.", DnsQType::A, DnsRcode::NOERROR, answers, vec![]);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Secure);
+        r.set_dnssec_status(Some(DnssecStatus::Secure));
+        assert!(r.to_string().contains("DNSSEC: Secure"));
+
+        // names are compared without regard to case (rfc4034, section 6.2).
+        let answers = tree.example.signed(vec![a("WWW.Example.", [192, 0, 2, 1])]);
+        let r = response("www.example.", DnsQType::A, DnsRcode::NOERROR, answers, vec![]);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Secure);
+
+        let answers = tree.rsa.signed(vec![a("www.rsa.example.", [192, 0, 2, 3])]);
+        let r = response("www.rsa.example.", DnsQType::A, DnsRcode::NOERROR, answers, vec![]);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Secure);
+
+        let answers = tree.nsec3.signed(vec![a("www.nsec3.example.", [192, 0, 2, 4])]);
+        let r = response("www.nsec3.example.", DnsQType::A, DnsRcode::NOERROR, answers, vec![]);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Secure);
+    }
+
+    #[test]
+    fn bogus_answer_test() {
+        let tree = Tree::new();
+        let validator = tree.validator();
+
+        // the address changed after it was signed.
+        let mut answers = tree.example.signed(vec![a("www.example.", [192, 0, 2, 1])]);
+        answers[0] = a("www.example.", [192, 0, 2, 99]);
+        let r = response("www.example.", DnsQType::A, DnsRcode::NOERROR, answers, vec![]);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Bogus);
+
+        // not signed at all, in a zone that is.
+        let r = response("www.example.", DnsQType::A, DnsRcode::NOERROR, vec![a("www.example.", [192, 0, 2, 1])],
+                         vec![]);
+        let mut tree = tree;
+        tree.add(response("www.example.", DnsQType::SOA, DnsRcode::NOERROR, vec![], tree.example.soa()));
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Bogus);
+
+        // the signatures have expired.
+        let answers = tree.example.signed(vec![a("www.example.", [192, 0, 2, 1])]);
+        let r = response("www.example.", DnsQType::A, DnsRcode::NOERROR, answers, vec![]);
+        let mut expired = tree.validator();
+        expired.set_now(Some(EXPIRATION + 1));
+        assert_eq!(tree.validate(&expired, &r), DnssecStatus::Bogus);
+
+        // a trust anchor for a key that isn't there.
+        let other = Zone::new(".", 13);
+        let mut validator = DnsValidator::new(vec![(String::from("."), other.ds())]);
+        validator.set_now(Some(1800000000));
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Bogus);
+    }
+
+    #[test]
+    fn forged_signer_test() {
+        let mut tree = Tree::new();
+        let validator = tree.validator();
+        // www.example. has no DS, and example.'s NSEC there says so - but it's not a delegation either.
+        let mut authorities = tree.example.soa();
+        authorities.append(&mut tree.example_nsec("www.example."));
+        tree.add(response("www.example.", DnsQType::DS, DnsRcode::NOERROR, vec![], authorities));
+
+        // a forged address, with a junk RRSIG claiming www.example. signed it.
+        let junk = |signer: &str, key_tag: u16| {
+            let rrsig = DnsRRSIGRecord::new(DnsQType::A, 13, 2, 3600, EXPIRATION, INCEPTION, key_tag,
+                                            String::from(signer), vec![0x42; 64]);
+            rr("www.example.", DnsQType::RRSIG, DnsResourceRecordEnum::RRSIG(rrsig))
+        };
+        let forged = response("www.example.", DnsQType::A, DnsRcode::NOERROR,
+                              vec![a("www.example.", [6, 6, 6, 6]), junk("www.example.", 1234)], vec![]);
+        assert_eq!(tree.validate(&validator, &forged), DnssecStatus::Bogus);
+
+        // a junk RRSIG from example. alongside doesn't change that.
+        let both = response("www.example.", DnsQType::A, DnsRcode::NOERROR,
+                            vec![a("www.example.", [6, 6, 6, 6]), junk("example.", tree.example.dnskey.key_tag()),
+                                 junk("www.example.", 1234)], vec![]);
+        assert_eq!(tree.validate(&validator, &both), DnssecStatus::Bogus);
+
+        // nor does it help to have the SOA say www.example. is a zone: the DS denial doesn't show a delegation.
+        tree.add(response("www.example.", DnsQType::SOA, DnsRcode::NOERROR, vec![], vec![soa("www.example.")]));
+        assert_eq!(tree.validate(&validator, &forged), DnssecStatus::Bogus);
+        assert_eq!(tree.validate(&validator, &both), DnssecStatus::Bogus);
+    }
+
+    #[test]
+    fn foreign_nsec_test() {
+        let mut tree = Tree::new();
+        let validator = tree.validator();
+        // an NSEC from insecure.example., which isn't signed - so it can't say anything about example.
+        let foreign = || {
+            let nsec = DnsNSECRecord::new(String::from("zzz.example."), vec![DnsQType::A]);
+            rr("a.host.insecure.example.", DnsQType::NSEC, DnsResourceRecordEnum::NSEC(nsec))
+        };
+        let authorities = || {
+            let mut authorities = tree.example.soa();
+            authorities.push(foreign());
+            authorities
+        };
+
+        let r = response("www.example.", DnsQType::A, DnsRcode::NAMERR, vec![], authorities());
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Bogus);
+        let r = response("www.example.", DnsQType::A, DnsRcode::NOERROR, vec![], authorities());
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Bogus);
+
+        // nor can it make rsa.example. out to be unsigned, by denying its DS.
+        tree.add(response("rsa.example.", DnsQType::DS, DnsRcode::NOERROR, vec![], authorities()));
+        let answers = tree.rsa.signed(vec![a("www.rsa.example.", [192, 0, 2, 3])]);
+        let r = response("www.rsa.example.", DnsQType::A, DnsRcode::NOERROR, answers, vec![]);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Bogus);
+    }
+
+    #[test]
+    fn indeterminate_test() {
+        let mut tree = Tree::new();
+        let validator = tree.validator();
+        let answers = tree.example.signed(vec![a("www.example.", [192, 0, 2, 1])]);
+        let r = response("www.example.", DnsQType::A, DnsRcode::NOERROR, answers, vec![]);
+
+        // the chain can't be followed without example.'s keys.
+        tree.remove("example.", DnsQType::DNSKEY);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Indeterminate);
+
+        // nor does a SERVFAIL have anything to validate.
+        let r = response("www.example.", DnsQType::A, DnsRcode::SERVFAIL, vec![], vec![]);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Indeterminate);
+    }
+
+    #[test]
+    fn nsec_denial_test() {
+        let tree = Tree::new();
+        let validator = tree.validator();
+
+        // nope.example. falls between insecure.example. and nsec3.example., and *.example. before insecure.example.
+        let mut authorities = tree.example.soa();
+        authorities.append(&mut tree.example_nsec("insecure.example."));
+        authorities.append(&mut tree.example_nsec("example."));
+        let r = response("nope.example.", DnsQType::A, DnsRcode::NAMERR, vec![], authorities);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Secure);
+
+        // without the wildcard's NSEC, there could have been an answer.
+        let mut authorities = tree.example.soa();
+        authorities.append(&mut tree.example_nsec("insecure.example."));
+        let r = response("nope.example.", DnsQType::A, DnsRcode::NAMERR, vec![], authorities);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Bogus);
+
+        // www.example. has an A, but no AAAA.
+        let mut authorities = tree.example.soa();
+        authorities.append(&mut tree.example_nsec("www.example."));
+        let r = response("www.example.", DnsQType::AAAA, DnsRcode::NOERROR, vec![], authorities);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Secure);
+
+        // the same NSEC can't deny the A.
+        let mut authorities = tree.example.soa();
+        authorities.append(&mut tree.example_nsec("www.example."));
+        let r = response("www.example.", DnsQType::A, DnsRcode::NOERROR, vec![], authorities);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Bogus);
+
+        // nor can a denial without any NSEC records, from a signed zone.
+        let r = response("www.example.", DnsQType::AAAA, DnsRcode::NOERROR, vec![], tree.example.soa());
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Bogus);
+    }
+
+    #[test]
+    fn nsec3_denial_test() {
+        let tree = Tree::new();
+        let validator = tree.validator();
+
+        // the closest encloser is the apex, and the chain covers everything else.
+        let mut authorities = tree.nsec3.soa();
+        authorities.append(&mut tree.nsec3_chain());
+        let r = response("nope.nsec3.example.", DnsQType::A, DnsRcode::NAMERR, vec![], authorities);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Secure);
+
+        let mut authorities = tree.nsec3.soa();
+        authorities.append(&mut tree.nsec3_chain());
+        let r = response("www.nsec3.example.", DnsQType::TXT, DnsRcode::NOERROR, vec![], authorities);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Secure);
+
+        let mut authorities = tree.nsec3.soa();
+        authorities.append(&mut tree.nsec3_chain());
+        let r = response("www.nsec3.example.", DnsQType::A, DnsRcode::NOERROR, vec![], authorities);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Bogus);
+    }
+
+    #[test]
+    fn insecure_delegation_test() {
+        let tree = Tree::new();
+        let validator = tree.validator();
+
+        let r = response("host.insecure.example.", DnsQType::A, DnsRcode::NOERROR,
+                         vec![a("host.insecure.example.", [192, 0, 2, 5])], vec![]);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Insecure);
+
+        // with an anchor for some other tree, nothing here can be checked.
+        let other = Zone::new("other.", 15);
+        let mut validator = DnsValidator::new(vec![(String::from("other."), other.ds())]);
+        validator.set_now(Some(1800000000));
+        let answers = tree.example.signed(vec![a("www.example.", [192, 0, 2, 1])]);
+        let r = response("www.example.", DnsQType::A, DnsRcode::NOERROR, answers, vec![]);
+        assert_eq!(tree.validate(&validator, &r), DnssecStatus::Indeterminate);
+    }
+}
@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod tests {
+    use dns_client::dns_client_lib::*;
+    use dns_client::dns_client_lib::view::*;
+    use std::net::Ipv4Addr;
+
+    fn a_rr(name: &str, addr: Ipv4Addr) -> DnsResourceRecord {
+        DnsResourceRecord::new(String::from(name), DnsQType::A, DnsQClass::IN, 300,
+                               DnsResourceRecordEnum::A(DnsARecord::new(addr)))
+    }
+
+    fn response() -> DnsResponse {
+        let h = DnsHeader::new(0x1234, true, DnsOpcode::QUERY, false, false, true, true,
+                               DnsRcode::NOERROR);
+        let q = vec![DnsQuestionRecord::new(String::from("www.example.com."), DnsQType::A, DnsQClass::IN)];
+        let an = vec![a_rr("www.example.com.", Ipv4Addr::new(192, 0, 2, 1)),
+                      a_rr("www.example.com.", Ipv4Addr::new(192, 0, 2, 2))];
+        let ns = vec![DnsResourceRecord::new(String::from("example.com."), DnsQType::NS, DnsQClass::IN, 3600,
+                                             DnsResourceRecordEnum::NS(DnsNSRecord::new(String::from("ns1.example.com."))))];
+        let ar = vec![a_rr("ns1.example.com.", Ipv4Addr::new(192, 0, 2, 53))];
+        DnsResponse::new(h, q, an, ns, ar)
+    }
+
+    #[test]
+    fn message_view_sections_test() {
+        let r = response();
+        let buf = r.to_bytes().unwrap();
+        let view = DnsMessageView::new(&buf, 0).unwrap();
+        assert_eq!(view.header(), r.header());
+        assert_eq!(view.count(DnsSection::ANSWER), 2);
+
+        let questions: Vec<DnsQuestionView> = view.questions().map(|q| q.unwrap()).collect();
+        assert_eq!(questions.len(), 1);
+        assert!(questions[0].name().matches("WWW.Example.com"));
+        assert_eq!(questions[0].to_record().unwrap(), r.questions()[0]);
+
+        let answers: Vec<DnsRecordView> = view.answers().map(|rr| rr.unwrap().1).collect();
+        assert_eq!(answers.len(), 2);
+        assert_eq!(answers[1].rtype(), DnsQType::A);
+        assert_eq!(answers[1].ttl(), 300);
+        assert_eq!(answers[1].rdata(), &[192, 0, 2, 2]);
+        assert_eq!(answers[1].to_record().unwrap(), r.answers()[1]);
+
+        let (section, ns) = view.authorities().next().unwrap().unwrap();
+        assert_eq!(section, DnsSection::AUTHORITY);
+        assert_eq!(ns.to_record().unwrap(), r.authorities()[0]);
+        assert_eq!(view.additionals().count(), 1);
+
+        let sections: Vec<DnsSection> = view.records().map(|rr| rr.unwrap().0).collect();
+        assert_eq!(sections, vec![DnsSection::ANSWER, DnsSection::ANSWER, DnsSection::AUTHORITY,
+                                  DnsSection::ADDITIONAL]);
+        assert_eq!(DnsResponse::from_bytes(&buf, 0), Ok(r));
+    }
+
+    #[test]
+    fn name_ref_test() {
+        let buf: Vec<u8> = vec![0x06, 0x67, 0x6f, 0x6f,     // len 6, g, o, o
+                                0x67, 0x6c, 0x65, 0x03,     // g, l, e, len 3
+                                0x63, 0x6f, 0x6d, 0x00,     // c, o, m, null
+                                0x03, 0x77, 0x77, 0x77,     // len 3, w, w, w
+                                0xc0, 0x00];                // ptr to 0
+        let name = DnsNameRef::parse(&buf, 12).unwrap();
+        assert_eq!(name.wire_len(), 6);
+        assert!(!name.is_root());
+        let labels: Vec<&[u8]> = name.labels().map(|l| l.unwrap()).collect();
+        assert_eq!(labels, vec![&b"www"[..], &b"google"[..], &b"com"[..]]);
+        assert_eq!(name.to_name(), Ok(String::from("www.google.com.")));
+        assert!(name.matches("www.GOOGLE.com."));
+        assert!(!name.matches("www.google.co"));
+        assert!(!name.matches("www.google.com.au"));
+
+        assert!(DnsNameRef::parse(&[0x00], 0).unwrap().matches("."));
+
+        // what's behind a pointer is only checked once it's followed.
+        let buf: Vec<u8> = vec![0x80, 0x01, 0x77, 0xc0, 0x00]; // bad len byte at 0, then w + ptr to 0
+        let name = DnsNameRef::parse(&buf, 1).unwrap();
+        assert_eq!(name.wire_len(), 4);
+        let mut labels = name.labels();
+        assert_eq!(labels.next(), Some(Ok(&b"w"[..])));
+        assert_eq!(labels.next(), Some(Err(DnsError::BadLabel { offset: 0, lenbyte: 0x80 })));
+        assert_eq!(labels.next(), None);
+        assert_eq!(name.to_name(), Err(DnsError::BadLabel { offset: 0, lenbyte: 0x80 }));
+    }
+
+    #[test]
+    fn message_view_truncated_test() {
+        let buf = response().to_bytes().unwrap();
+        // cut the message off in the middle of the additional section's A record.
+        let buf = &buf[.. buf.len() - 2];
+        let view = DnsMessageView::new(buf, 0).unwrap();
+
+        // everything up to the bad record still reads fine.
+        assert_eq!(view.answers().filter(|rr| rr.is_ok()).count(), 2);
+        let mut additionals = view.additionals();
+        assert!(matches!(additionals.next(),
+                         Some(Err(DnsError::Truncated { context: "resource record rdata", .. }))));
+        assert!(additionals.next().is_none());
+        assert!(DnsResponse::from_bytes(buf, 0).is_err());
+
+        assert_eq!(DnsMessageView::new(&buf[.. 11], 0),
+                   Err(DnsError::Truncated { context: "DNS message", offset: 0, needed: 12, len: 11 }));
+    }
+}